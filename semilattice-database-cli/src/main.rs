@@ -0,0 +1,335 @@
+//! `semilattice` opens a database directory and prints what is in it.
+
+use std::{
+    num::{NonZeroI32, NonZeroU32},
+    path::PathBuf,
+    process::ExitCode,
+};
+
+use clap::{Parser, Subcommand};
+use semilattice_database_session::{search::Search, CollectionRow, PurgePolicy, SessionDatabase};
+
+#[derive(Parser)]
+#[command(
+    name = "semilattice",
+    version,
+    about = "Inspects a semilattice-database directory"
+)]
+struct Cli {
+    /// The database directory.
+    dir: PathBuf,
+
+    /// Opens the database for writing, which `purge`, `sessions clear` and `sessions gc` need.
+    /// Without it the database is opened read-only and nothing in the directory is changed.
+    #[arg(long, global = true)]
+    write: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Lists the collections with their row counts.
+    Collections,
+    /// Prints rows with their uuid, activity, term and fields.
+    Dump {
+        collection: String,
+        /// Only these rows.
+        #[arg(long, value_delimiter = ',')]
+        rows: Vec<NonZeroU32>,
+        #[arg(long)]
+        limit: Option<usize>,
+    },
+    /// Prints the rows a row depends on and the rows that depend on it.
+    Relations { collection: String, row: NonZeroU32 },
+    /// Runs a search given as JSON (see "Serialized search" in the semilattice-database README) and prints the rows.
+    Search {
+        /// The search, or `-` to read it from stdin.
+        json: String,
+    },
+    /// Deletes the rows whose term ended at or before a time, and the rows depending on them.
+    Purge {
+        /// Seconds since the UNIX epoch.
+        #[arg(long)]
+        before: u64,
+        /// Makes the rows inactive instead of deleting them.
+        #[arg(long)]
+        deactivate: bool,
+        /// Confirms the purge, which cannot be undone.
+        #[arg(long)]
+        yes: bool,
+    },
+    #[command(subcommand)]
+    Sessions(SessionsCommand),
+}
+
+#[derive(Subcommand)]
+enum SessionsCommand {
+    /// Lists the sessions with their last access and expiry.
+    List,
+    /// Prints the rows a session holds.
+    Inspect { name: String },
+    /// Deletes a session.
+    Clear { name: String },
+    /// Deletes the sessions that have expired.
+    Gc {
+        /// Expiry in seconds of sessions that were created without one.
+        #[arg(long, default_value_t = 60 * 60 * 24)]
+        expire: i64,
+    },
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    if !cli.dir.is_dir() {
+        eprintln!("{} is not a directory", cli.dir.display());
+        return ExitCode::FAILURE;
+    }
+    let database = if cli.write {
+        SessionDatabase::open(cli.dir, None, 10)
+    } else {
+        SessionDatabase::open_read_only(cli.dir, None)
+    };
+    let mut database = match database {
+        Ok(database) => database,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    match futures::executor::block_on(run(&mut database, cli.command)) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(message) => {
+            eprintln!("{}", message);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(database: &mut SessionDatabase, command: Command) -> Result<(), String> {
+    if database.is_read_only()
+        && matches!(
+            command,
+            Command::Purge { .. }
+                | Command::Sessions(SessionsCommand::Clear { .. } | SessionsCommand::Gc { .. })
+        )
+    {
+        return Err("this command writes to the database and needs --write".into());
+    }
+    match command {
+        Command::Collections => {
+            for (collection_id, name) in database.collection_entries() {
+                if let Some(collection) = database.collection(collection_id) {
+                    println!(
+                        "{}\t{}\t{}",
+                        collection_id,
+                        name,
+                        collection.data().all().len()
+                    );
+                }
+            }
+        }
+        Command::Dump {
+            collection,
+            rows,
+            limit,
+        } => {
+            let collection = collection_by_name(database, &collection)?;
+            let rows: Vec<_> = if rows.is_empty() {
+                collection.data().all().into_iter().collect()
+            } else {
+                rows
+            };
+            for row in rows.into_iter().take(limit.unwrap_or(usize::MAX)) {
+                let Some(uuid) = collection.uuid_string(row) else {
+                    println!("{}\t(deleted)", row);
+                    continue;
+                };
+                println!(
+                    "{}\tuuid={} activity={:?} term_begin={} term_end={}",
+                    row,
+                    uuid,
+                    collection.activity(row).unwrap(),
+                    collection.term_begin(row).unwrap(),
+                    collection.term_end(row).unwrap(),
+                );
+                let mut field_names: Vec<_> = collection.data().fields().keys().collect();
+                field_names.sort();
+                for field_name in field_names {
+                    let bytes = collection.field_bytes(row, field_name);
+                    if !bytes.is_empty() {
+                        println!("\t{}={}", field_name, format_bytes(bytes));
+                    }
+                }
+            }
+        }
+        Command::Relations { collection, row } => {
+            let collection_id = collection_by_name(database, &collection)?.id();
+            let target = CollectionRow::new(collection_id, row);
+            let relation = database.relation();
+            println!("depends:");
+            for depend in database.depends(None, collection_id, row) {
+                println!(
+                    "\t{}\t{}",
+                    depend.key(),
+                    format_collection_row(database, &depend)
+                );
+            }
+            println!("pends:");
+            for relation_row in relation.rows_by_depend(&target) {
+                if let Some(pend) = relation.pend(relation_row) {
+                    println!(
+                        "\t{}\t{}",
+                        relation.key(relation_row),
+                        format_collection_row(database, pend)
+                    );
+                }
+            }
+        }
+        Command::Search { json } => {
+            let json = if json == "-" {
+                std::io::read_to_string(std::io::stdin()).map_err(|e| e.to_string())?
+            } else {
+                json
+            };
+            let search: Search = serde_json::from_str(&json).map_err(|e| e.to_string())?;
+            for row in search.result(database).await.rows() {
+                println!("{}", row);
+            }
+        }
+        Command::Sessions(SessionsCommand::List) => {
+            for session in database.sessions() {
+                println!(
+                    "{}\taccess_at={} expire={}",
+                    session.name(),
+                    session.access_at(),
+                    session.expire()
+                );
+            }
+        }
+        Command::Sessions(SessionsCommand::Inspect { name }) => {
+            if !database.sessions().iter().any(|s| s.name() == name) {
+                return Err(format!("no session named {}", name));
+            }
+            let Some(session) = database.inspect_session(&name) else {
+                return Ok(());
+            };
+            if let Some(cursor) = session.sequence_cursor() {
+                println!("sequence {}/{}", cursor.current, cursor.max);
+            }
+            for name in database.collections() {
+                let Some(collection_id) = database.collection_id(&name) else {
+                    continue;
+                };
+                let Some(rows) = session.temporary_collection(collection_id) else {
+                    continue;
+                };
+                let mut rows: Vec<_> = rows.iter().collect();
+                rows.sort_by_key(|(row, _)| **row);
+                for (row, entity) in rows {
+                    println!(
+                        "{}\t{}\tuuid={} activity={:?} term_begin={} term_end={}",
+                        name,
+                        row,
+                        entity.uuid_string(),
+                        entity.activity(),
+                        entity.term_begin(),
+                        entity.term_end()
+                    );
+                    let mut fields: Vec<_> = entity.fields().iter().collect();
+                    fields.sort();
+                    for (field_name, bytes) in fields {
+                        println!("\t{}={}", field_name, format_bytes(bytes));
+                    }
+                    for depend in entity.depends() {
+                        println!(
+                            "\tdepend {}\t{}",
+                            depend.key(),
+                            format_collection_row(database, depend)
+                        );
+                    }
+                }
+            }
+        }
+        Command::Sessions(SessionsCommand::Clear { name }) => {
+            if !database.sessions().iter().any(|s| s.name() == name) {
+                return Err(format!("no session named {}", name));
+            }
+            let mut session = database.session(&name, None).map_err(|e| e.to_string())?;
+            database
+                .session_clear(&mut session)
+                .map_err(|e| e.to_string())?;
+        }
+        Command::Purge {
+            before,
+            deactivate,
+            yes,
+        } => {
+            if !yes {
+                return Err("purge cannot be undone; confirm it with --yes".into());
+            }
+            let policy = if deactivate {
+                PurgePolicy::Deactivate
+            } else {
+                PurgePolicy::Delete
+            };
+            let report = database
+                .purge_expired(before, policy)
+                .await
+                .map_err(|e| e.to_string())?;
+            for row in report.rows.iter() {
+                println!("{}", format_collection_row(database, row));
+            }
+            for row in report.cascaded.iter() {
+                println!("{} (dependant)", format_collection_row(database, row));
+            }
+            println!(
+                "{} rows {}, {} dependants deleted",
+                report.rows.len(),
+                if deactivate { "deactivated" } else { "deleted" },
+                report.cascaded.len()
+            );
+        }
+        Command::Sessions(SessionsCommand::Gc { expire }) => {
+            let before = database.sessions().len();
+            database.session_gc(expire).map_err(|e| e.to_string())?;
+            println!("{} sessions deleted", before - database.sessions().len());
+        }
+    }
+    Ok(())
+}
+
+fn collection_by_name<'a>(
+    database: &'a SessionDatabase,
+    name: &str,
+) -> Result<&'a semilattice_database_session::Collection, String> {
+    database
+        .collection_id(name)
+        .and_then(|id| database.collection(id))
+        .ok_or_else(|| format!("no collection named {}", name))
+}
+
+/// `name:row`, with a negative collection id for rows that exist only in a session.
+fn format_collection_row(database: &SessionDatabase, collection_row: &CollectionRow) -> String {
+    let collection_id = collection_row.collection_id();
+    let name = NonZeroI32::new(collection_id.get().abs())
+        .and_then(|id| database.collection(id))
+        .map_or("?", |collection| collection.name());
+    if collection_id.get() < 0 {
+        format!("{}:{} (session)", name, collection_row.row())
+    } else {
+        format!("{}:{}", name, collection_row.row())
+    }
+}
+
+/// UTF-8 as is, anything else as hex.
+fn format_bytes(bytes: &[u8]) -> String {
+    match std::str::from_utf8(bytes) {
+        Ok(s) if !s.chars().any(char::is_control) => s.to_owned(),
+        _ => {
+            let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+            format!("0x{}", hex)
+        }
+    }
+}
@@ -0,0 +1,198 @@
+use std::path::Path;
+
+use assert_cmd::Command;
+use semilattice_database_session::*;
+
+/// A database with two people related by "friend", and a session holding a third.
+fn setup(dir: &str) {
+    if Path::new(dir).exists() {
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+    std::fs::create_dir_all(dir).unwrap();
+
+    let field_name = FieldName::new("name".into());
+    let mut database = SessionDatabase::new(dir.into(), None, 10);
+    let collection_person = database.collection_id_or_create("person").unwrap();
+    futures::executor::block_on(async {
+        let mut sess = database.session("login", None).unwrap();
+        database
+            .update(
+                &mut sess,
+                vec![SessionRecord::update(collection_person, None)
+                    .field_bytes(field_name.clone(), "Joe".into())
+                    .pend(Pend {
+                        key: "friend".to_owned().into(),
+                        records: vec![SessionRecord::update(collection_person, None)
+                            .field_bytes(field_name.clone(), "Ann".into())],
+                    })],
+            )
+            .await
+            .unwrap();
+        database.commit(&mut sess).await.unwrap();
+
+        let collection = database.collection_mut(collection_person).unwrap();
+        collection
+            .insert(
+                Activity::Active,
+                Term::Default,
+                Term::Overwrite(1000),
+                [(field_name.clone(), "Old".into())].into(),
+            )
+            .await
+            .unwrap();
+
+        let mut sess = database.session("draft", Some(3600)).unwrap();
+        database
+            .update(
+                &mut sess,
+                vec![SessionRecord::update(collection_person, None)
+                    .field_bytes(field_name.clone(), "Bob".into())],
+            )
+            .await
+            .unwrap();
+    });
+}
+
+fn semilattice(dir: &str) -> Command {
+    let mut command = Command::cargo_bin("semilattice").unwrap();
+    command.arg(dir);
+    command
+}
+
+fn stdout(command: &mut Command) -> String {
+    String::from_utf8(command.assert().success().get_output().stdout.clone()).unwrap()
+}
+
+#[cfg(test)]
+#[test]
+fn collections() {
+    let dir = "./sl-test-cli-collections/";
+    setup(dir);
+    assert_eq!(
+        stdout(semilattice(dir).arg("collections")),
+        "1\tperson\t3\n"
+    );
+}
+
+#[cfg(test)]
+#[test]
+fn dump() {
+    let dir = "./sl-test-cli-dump/";
+    setup(dir);
+    let out = stdout(semilattice(dir).args(["dump", "person", "--rows", "2"]));
+    assert!(out.starts_with("2\tuuid="));
+    assert!(out.ends_with("\tname=Ann\n"));
+    assert_eq!(
+        stdout(semilattice(dir).args(["dump", "person", "--limit", "1"]))
+            .lines()
+            .count(),
+        2
+    );
+    semilattice(dir).args(["dump", "nobody"]).assert().failure();
+}
+
+#[cfg(test)]
+#[test]
+fn relations() {
+    let dir = "./sl-test-cli-relations/";
+    setup(dir);
+    assert_eq!(
+        stdout(semilattice(dir).args(["relations", "person", "2"])),
+        "depends:\n\tfriend\tperson:1\npends:\n"
+    );
+    assert_eq!(
+        stdout(semilattice(dir).args(["relations", "person", "1"])),
+        "depends:\npends:\n\tfriend\tperson:2\n"
+    );
+}
+
+#[cfg(test)]
+#[test]
+fn search() {
+    let dir = "./sl-test-cli-search/";
+    setup(dir);
+    assert_eq!(
+        stdout(semilattice(dir).args([
+            "search",
+            r#"{"collection_id":1,"conditions":[{"Field":["name",{"Match":[65,110,110]}]}],"join":{}}"#
+        ])),
+        "2\n"
+    );
+    assert_eq!(
+        stdout(
+            semilattice(dir)
+                .args(["search", "-"])
+                .write_stdin(r#"{"collection_id":1,"conditions":[{"Row":{"Min":2}}],"join":{}}"#)
+        ),
+        "2\n3\n"
+    );
+    semilattice(dir).args(["search", "{}"]).assert().failure();
+}
+
+#[cfg(test)]
+#[test]
+fn purge() {
+    let dir = "./sl-test-cli-purge/";
+    setup(dir);
+    semilattice(dir)
+        .args(["purge", "--before", "2000"])
+        .assert()
+        .failure();
+    semilattice(dir)
+        .args(["--write", "purge", "--before", "2000"])
+        .assert()
+        .failure();
+    assert_eq!(
+        stdout(semilattice(dir).arg("collections")),
+        "1\tperson\t3\n"
+    );
+    assert_eq!(
+        stdout(semilattice(dir).args(["--write", "purge", "--before", "2000", "--yes"])),
+        "person:3\n1 rows deleted, 0 dependants deleted\n"
+    );
+    assert_eq!(
+        stdout(semilattice(dir).arg("collections")),
+        "1\tperson\t2\n"
+    );
+}
+
+#[cfg(test)]
+#[test]
+fn sessions() {
+    let dir = "./sl-test-cli-sessions/";
+    setup(dir);
+    let list = stdout(semilattice(dir).args(["sessions", "list"]));
+    assert!(list.contains("draft\t") && list.contains("expire=3600"));
+
+    let expire = Path::new(dir).join("sessions/draft/expire");
+    let modified = std::fs::metadata(&expire).unwrap().modified().unwrap();
+    let bytes = std::fs::read(&expire).unwrap();
+    let out = stdout(semilattice(dir).args(["sessions", "inspect", "draft"]));
+    assert!(out.starts_with("sequence 1/1\nperson\t-1\t"));
+    assert!(out.ends_with("\tname=Bob\n"));
+    assert_eq!(
+        std::fs::metadata(&expire).unwrap().modified().unwrap(),
+        modified
+    );
+    assert_eq!(std::fs::read(&expire).unwrap(), bytes);
+    semilattice(dir)
+        .args(["sessions", "inspect", "nobody"])
+        .assert()
+        .failure();
+
+    semilattice(dir)
+        .args(["sessions", "clear", "draft"])
+        .assert()
+        .failure();
+    semilattice(dir)
+        .args(["--write", "sessions", "clear", "draft"])
+        .assert()
+        .success();
+    assert!(!stdout(semilattice(dir).args(["sessions", "list"])).contains("draft"));
+
+    semilattice(dir).args(["sessions", "gc"]).assert().failure();
+    assert_eq!(
+        stdout(semilattice(dir).args(["--write", "sessions", "gc", "--expire", "0"])),
+        "0 sessions deleted\n"
+    );
+}
@@ -0,0 +1,210 @@
+//! `#[derive(CollectionRecord)]` for structs with named fields whose types implement `semilattice_database::codec::Codec`.
+//!
+//! Each struct field becomes a collection field of the same name. `Option` fields are optional.
+//! `to_fields` leaves out `None` fields, so an update written from it keeps their stored values; fields cannot be cleared through it.
+//! A field can be configured with `#[record(rename = "name", max_length = 64)]`.
+//!
+//! Besides the `CollectionRecord` impl, a `<field>_condition` function is generated for each field,
+//! which builds a `Condition` from a `FieldValue` of the field's type.
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::{format_ident, quote, quote_spanned};
+use syn::{
+    parse_macro_input, spanned::Spanned, Data, DeriveInput, Fields, GenericArgument, Ident, LitInt,
+    LitStr, PathArguments, Type,
+};
+
+#[proc_macro_derive(CollectionRecord, attributes(record))]
+pub fn derive_collection_record(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+struct RecordField {
+    ident: Ident,
+    name: String,
+    ty: Type,
+    optional: bool,
+    max_length: Option<LitInt>,
+}
+
+fn expand(input: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new(
+            Span::call_site(),
+            "CollectionRecord can only be derived for structs",
+        ));
+    };
+    let Fields::Named(fields) = &data.fields else {
+        return Err(syn::Error::new(
+            Span::call_site(),
+            "CollectionRecord requires named fields",
+        ));
+    };
+    let fields = fields
+        .named
+        .iter()
+        .map(|field| {
+            let ident = field.ident.clone().unwrap();
+            let mut name = ident.to_string();
+            let mut max_length = None;
+            for attr in field.attrs.iter().filter(|a| a.path().is_ident("record")) {
+                attr.parse_nested_meta(|meta| {
+                    if meta.path.is_ident("rename") {
+                        name = meta.value()?.parse::<LitStr>()?.value();
+                        Ok(())
+                    } else if meta.path.is_ident("max_length") {
+                        max_length = Some(meta.value()?.parse::<LitInt>()?);
+                        Ok(())
+                    } else {
+                        Err(meta.error("expected `rename` or `max_length`"))
+                    }
+                })?;
+            }
+            let (ty, optional) = match option_inner(&field.ty) {
+                Some(inner) => (inner.clone(), true),
+                None => (field.ty.clone(), false),
+            };
+            Ok(RecordField {
+                ident,
+                name,
+                ty,
+                optional,
+                max_length,
+            })
+        })
+        .collect::<syn::Result<Vec<_>>>()?;
+
+    let db = quote!(::semilattice_database);
+    let field_name = |name: &str| quote!(#db::FieldName::new(#name.to_owned()));
+    // Spanned on the field type, so an unsupported type is reported on the field.
+    let codec = |ty: &Type| quote_spanned!(ty.span()=> <#ty as #db::codec::Codec>);
+
+    let schema = fields.iter().map(|f| {
+        let name = field_name(&f.name);
+        let codec = codec(&f.ty);
+        let required = !f.optional;
+        let max_length = match &f.max_length {
+            Some(max_length) => quote!(Some(#max_length)),
+            None => quote!(None),
+        };
+        quote! {
+            .field(#name, #db::FieldSchema {
+                field_type: #codec::FIELD_TYPE,
+                required: #required,
+                max_length: #max_length,
+            })
+        }
+    });
+
+    let to_fields = fields.iter().map(|f| {
+        let name = field_name(&f.name);
+        let ident = &f.ident;
+        let codec = codec(&f.ty);
+        if f.optional {
+            quote! {
+                if let Some(value) = &self.#ident {
+                    fields.insert(#name, #codec::encode(value));
+                }
+            }
+        } else {
+            quote! {
+                fields.insert(#name, #codec::encode(&self.#ident));
+            }
+        }
+    });
+
+    let from_fields = fields.iter().map(|f| {
+        let name = field_name(&f.name);
+        let ident = &f.ident;
+        let codec = codec(&f.ty);
+        let decode = quote! {
+            #codec::decode(bytes).ok_or_else(|| #db::FieldError::Type {
+                field_name: field_name.clone(),
+                expected: #codec::FIELD_TYPE,
+            })?
+        };
+        if f.optional {
+            quote! {
+                #ident: {
+                    let field_name = #name;
+                    match fields.get(&field_name) {
+                        Some(bytes) => Some(#decode),
+                        None => None,
+                    }
+                }
+            }
+        } else {
+            quote! {
+                #ident: {
+                    let field_name = #name;
+                    let bytes = fields
+                        .get(&field_name)
+                        .ok_or_else(|| #db::FieldError::Missing(field_name.clone()))?;
+                    #decode
+                }
+            }
+        }
+    });
+
+    let conditions = fields.iter().map(|f| {
+        let name = field_name(&f.name);
+        let ty = &f.ty;
+        let field_value = quote_spanned!(ty.span()=> #db::codec::FieldValue<#ty>);
+        let function = format_ident!("{}_condition", f.ident);
+        quote! {
+            pub fn #function(condition: #field_value) -> #db::Condition {
+                #db::Condition::Field(#name, condition.into())
+            }
+        }
+    });
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics #db::CollectionRecord for #ident #ty_generics #where_clause {
+            fn schema() -> #db::Schema {
+                #db::Schema::new()
+                    #(#schema)*
+            }
+
+            fn to_fields(&self) -> #db::hashbrown::HashMap<#db::FieldName, Vec<u8>> {
+                let mut fields = #db::hashbrown::HashMap::new();
+                #(#to_fields)*
+                fields
+            }
+
+            fn from_fields(
+                fields: &#db::hashbrown::HashMap<#db::FieldName, Vec<u8>>,
+            ) -> Result<Self, #db::FieldError> {
+                Ok(Self {
+                    #(#from_fields,)*
+                })
+            }
+        }
+
+        impl #impl_generics #ident #ty_generics #where_clause {
+            #(#conditions)*
+        }
+    })
+}
+
+fn option_inner(ty: &Type) -> Option<&Type> {
+    let Type::Path(path) = ty else {
+        return None;
+    };
+    let segment = path.path.segments.last()?;
+    if segment.ident != "Option" {
+        return None;
+    }
+    let PathArguments::AngleBracketed(args) = &segment.arguments else {
+        return None;
+    };
+    match args.args.first()? {
+        GenericArgument::Type(ty) => Some(ty),
+        _ => None,
+    }
+}
@@ -0,0 +1,247 @@
+use std::{
+    collections::BTreeMap,
+    num::{NonZeroI32, NonZeroI64, NonZeroU32},
+    sync::Arc,
+};
+
+use semilattice_database_session::{
+    Activity, Collection, CollectionRow, Depend, Depends, FieldName, Pend, SearchResult,
+    SessionRecord, SessionSearchResult, Term,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "Activity")]
+enum ActivityDef {
+    Inactive = 0,
+    Active = 1,
+}
+
+fn active() -> Activity {
+    Activity::Active
+}
+
+/// A field value: a string when it is UTF-8, otherwise an array of bytes.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Value {
+    Text(String),
+    Bytes(Vec<u8>),
+}
+impl From<&[u8]> for Value {
+    fn from(bytes: &[u8]) -> Self {
+        match std::str::from_utf8(bytes) {
+            Ok(s) => Self::Text(s.to_owned()),
+            Err(_) => Self::Bytes(bytes.to_vec()),
+        }
+    }
+}
+impl From<Value> for Vec<u8> {
+    fn from(value: Value) -> Self {
+        match value {
+            Value::Text(s) => s.into_bytes(),
+            Value::Bytes(bytes) => bytes,
+        }
+    }
+}
+
+fn fields(fields: BTreeMap<String, Value>) -> hashbrown::HashMap<FieldName, Vec<u8>> {
+    fields
+        .into_iter()
+        .map(|(name, value)| (FieldName::new(name), value.into()))
+        .collect()
+}
+
+fn term(value: Option<u64>) -> Term {
+    value.map_or(Term::Default, Term::Overwrite)
+}
+
+/// The body of a row insert or update. Terms left out are set to now and to none.
+#[derive(Deserialize)]
+pub struct Write {
+    #[serde(default = "active", with = "ActivityDef")]
+    pub activity: Activity,
+    pub term_begin: Option<u64>,
+    pub term_end: Option<u64>,
+    #[serde(default)]
+    pub fields: BTreeMap<String, Value>,
+}
+impl Write {
+    pub fn into_parts(self) -> (Activity, Term, Term, hashbrown::HashMap<FieldName, Vec<u8>>) {
+        (
+            self.activity,
+            term(self.term_begin),
+            term(self.term_end),
+            fields(self.fields),
+        )
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Relation {
+    pub key: String,
+    pub collection_id: NonZeroI32,
+    pub row: NonZeroU32,
+}
+impl From<&Depend> for Relation {
+    fn from(depend: &Depend) -> Self {
+        Self {
+            key: depend.key().to_string(),
+            collection_id: depend.collection_id(),
+            row: depend.row(),
+        }
+    }
+}
+impl From<Relation> for (Arc<String>, CollectionRow) {
+    fn from(relation: Relation) -> Self {
+        (
+            Arc::new(relation.key),
+            CollectionRow::new(relation.collection_id, relation.row),
+        )
+    }
+}
+
+#[derive(Deserialize)]
+pub struct RegisterRelation {
+    pub key: String,
+    pub depend: CollectionRow,
+    pub pend: CollectionRow,
+}
+
+#[derive(Serialize)]
+pub struct Row {
+    pub row: NonZeroU32,
+    pub uuid: String,
+    #[serde(with = "ActivityDef")]
+    pub activity: Activity,
+    pub term_begin: u64,
+    pub term_end: u64,
+    pub fields: BTreeMap<String, Value>,
+    pub depends: Vec<Relation>,
+}
+impl Row {
+    pub fn new(collection: &Collection, row: NonZeroU32, depends: &[Depend]) -> Option<Self> {
+        Some(Self {
+            row,
+            uuid: collection.uuid_string(row)?,
+            activity: collection.activity(row)?,
+            term_begin: *collection.term_begin(row)?,
+            term_end: *collection.term_end(row)?,
+            fields: collection
+                .data()
+                .fields()
+                .keys()
+                .filter_map(|field_name| {
+                    let bytes = collection.field_bytes(row, field_name);
+                    (!bytes.is_empty()).then(|| (field_name.to_string(), bytes.into()))
+                })
+                .collect(),
+            depends: depends.iter().map(Relation::from).collect(),
+        })
+    }
+}
+
+/// A session write, in serde's externally tagged form: `{"Update": {...}}` or `{"Delete": {...}}`.
+#[derive(Deserialize)]
+pub enum Record {
+    Update {
+        collection_id: NonZeroI32,
+        row: Option<NonZeroU32>,
+        #[serde(default = "active", with = "ActivityDef")]
+        activity: Activity,
+        term_begin: Option<u64>,
+        term_end: Option<u64>,
+        #[serde(default)]
+        fields: BTreeMap<String, Value>,
+        /// Replaces the relations of the row when given.
+        depends: Option<Vec<Relation>>,
+        #[serde(default)]
+        pends: Vec<RecordPend>,
+    },
+    Delete {
+        collection_id: NonZeroI32,
+        row: NonZeroU32,
+    },
+}
+
+#[derive(Deserialize)]
+pub struct RecordPend {
+    pub key: String,
+    pub records: Vec<Record>,
+}
+
+impl From<Record> for SessionRecord {
+    fn from(record: Record) -> Self {
+        match record {
+            Record::Update {
+                collection_id,
+                row,
+                activity,
+                term_begin,
+                term_end,
+                fields: f,
+                depends,
+                pends,
+            } => SessionRecord::Update {
+                collection_id,
+                row,
+                activity,
+                term_begin: term(term_begin),
+                term_end: term(term_end),
+                fields: fields(f),
+                depends: depends.map_or(Depends::Default, |depends| {
+                    Depends::Overwrite(depends.into_iter().map(Into::into).collect())
+                }),
+                pends: pends
+                    .into_iter()
+                    .map(|pend| Pend {
+                        key: Arc::new(pend.key),
+                        records: pend.records.into_iter().map(Into::into).collect(),
+                    })
+                    .collect(),
+            },
+            Record::Delete { collection_id, row } => SessionRecord::Delete { collection_id, row },
+        }
+    }
+}
+
+/// Rows of a search and, for each join, the joined result of every row.
+#[derive(Serialize)]
+pub struct Found<R: Ord> {
+    pub rows: Vec<R>,
+    pub join: BTreeMap<String, BTreeMap<R, Found<R>>>,
+}
+impl From<&SearchResult> for Found<NonZeroU32> {
+    fn from(result: &SearchResult) -> Self {
+        Self {
+            rows: result.rows().iter().cloned().collect(),
+            join: result
+                .join()
+                .iter()
+                .map(|(name, join)| {
+                    (
+                        name.to_string(),
+                        join.iter().map(|(row, r)| (*row, r.into())).collect(),
+                    )
+                })
+                .collect(),
+        }
+    }
+}
+impl From<&SessionSearchResult> for Found<NonZeroI64> {
+    fn from(result: &SessionSearchResult) -> Self {
+        Self {
+            rows: result.rows().iter().cloned().collect(),
+            join: result
+                .join()
+                .iter()
+                .map(|(name, join)| {
+                    (
+                        name.to_string(),
+                        join.iter().map(|(row, r)| (*row, r.into())).collect(),
+                    )
+                })
+                .collect(),
+        }
+    }
+}
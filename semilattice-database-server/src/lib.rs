@@ -0,0 +1,325 @@
+//! HTTP server answering JSON requests against a local database directory.
+//!
+//! Requests are handled one at a time on the thread that runs [Server::run]. See the README for the endpoints.
+
+mod json;
+
+use std::{
+    net::{SocketAddr, ToSocketAddrs},
+    num::{NonZeroI32, NonZeroU32},
+    panic::AssertUnwindSafe,
+    path::PathBuf,
+};
+
+use hashbrown::HashMap;
+use semilattice_database_session::{
+    search::Search, CollectionRow, Session, SessionDatabase, WriteError,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use json::{Found, Record, RegisterRelation, Row, Write};
+
+/// An error response: the status and the message returned as `{"error": message}`.
+#[derive(Debug)]
+pub struct HttpError {
+    pub status: u16,
+    pub message: String,
+}
+impl HttpError {
+    fn new(status: u16, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+        }
+    }
+
+    fn not_found() -> Self {
+        Self::new(404, "not found")
+    }
+}
+impl From<serde_json::Error> for HttpError {
+    fn from(e: serde_json::Error) -> Self {
+        Self::new(400, e.to_string())
+    }
+}
+impl From<WriteError> for HttpError {
+    fn from(e: WriteError) -> Self {
+        match e {
+            WriteError::Validation(_) => Self::new(422, e.to_string()),
+            WriteError::Conflict(_) => Self::new(409, e.to_string()),
+            WriteError::ReadOnly | WriteError::Encrypted => Self::new(403, e.to_string()),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct CreateCollection {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct Cursor {
+    current: usize,
+}
+
+pub struct Server {
+    http: tiny_http::Server,
+    database: SessionDatabase,
+    sessions: HashMap<String, Session>,
+}
+impl Server {
+    /// Opens the database in `dir` and listens on `addr`. Port 0 picks a free port, see [Server::local_addr].
+    /// Fails if another process has the database open for writing.
+    pub fn new(dir: PathBuf, addr: impl ToSocketAddrs) -> std::io::Result<Self> {
+        let database = SessionDatabase::open(dir, None, 10)?;
+        let http = tiny_http::Server::http(addr).map_err(std::io::Error::other)?;
+        Ok(Self {
+            http,
+            database,
+            sessions: HashMap::new(),
+        })
+    }
+
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.http.server_addr().to_ip()
+    }
+
+    /// Handles requests until the listener fails.
+    /// A request that panics is answered with status 500 and the server goes on with the next one.
+    pub fn run(mut self) {
+        while let Ok(mut request) = self.http.recv() {
+            let mut body = String::new();
+            let result = match request.as_reader().read_to_string(&mut body) {
+                Ok(_) => std::panic::catch_unwind(AssertUnwindSafe(|| {
+                    futures::executor::block_on(self.handle(
+                        request.method().as_str(),
+                        request.url(),
+                        &body,
+                    ))
+                }))
+                .unwrap_or_else(|_| Err(HttpError::new(500, "internal error"))),
+                Err(e) => Err(HttpError::new(400, e.to_string())),
+            };
+            let (status, value) = match result {
+                Ok(value) => (200, value),
+                Err(e) => (e.status, json!({ "error": e.message })),
+            };
+            let response = tiny_http::Response::from_string(value.to_string())
+                .with_status_code(status)
+                .with_header(
+                    tiny_http::Header::from_bytes("Content-Type", "application/json").unwrap(),
+                );
+            let _ = request.respond(response);
+        }
+    }
+
+    /// Answers one request. `path` may carry a query string, which is ignored.
+    pub async fn handle(
+        &mut self,
+        method: &str,
+        path: &str,
+        body: &str,
+    ) -> Result<Value, HttpError> {
+        let path = path.split('?').next().unwrap_or_default();
+        let segments: Vec<_> = path.split('/').filter(|s| !s.is_empty()).collect();
+        match (method, segments.as_slice()) {
+            ("GET", ["collections"]) => Ok(self.collections()),
+            ("POST", ["collections"]) => {
+                let request: CreateCollection = serde_json::from_str(body)?;
+                if !is_name(&request.name) {
+                    return Err(HttpError::new(400, "invalid collection name"));
+                }
+                let collection_id = self.database.collection_id_or_create(&request.name)?;
+                Ok(json!({ "collection_id": collection_id }))
+            }
+            ("GET", ["collections", collection_id, "rows"]) => {
+                let collection_id = collection_id_from(collection_id)?;
+                let collection = self
+                    .database
+                    .collection(collection_id)
+                    .ok_or_else(HttpError::not_found)?;
+                Ok(json!({ "rows": collection.data().all() }))
+            }
+            ("POST", ["collections", collection_id, "rows"]) => {
+                let collection_id = collection_id_from(collection_id)?;
+                let (activity, term_begin, term_end, fields) =
+                    serde_json::from_str::<Write>(body)?.into_parts();
+                let row = self
+                    .database
+                    .collection_mut(collection_id)
+                    .ok_or_else(HttpError::not_found)?
+                    .insert(activity, term_begin, term_end, fields)
+                    .await?;
+                Ok(json!({ "row": row }))
+            }
+            ("GET", ["collections", collection_id, "rows", row]) => {
+                let collection_id = collection_id_from(collection_id)?;
+                let row = row_from(row)?;
+                let collection = self
+                    .database
+                    .collection(collection_id)
+                    .ok_or_else(HttpError::not_found)?;
+                let depends = self.database.depends(None, collection_id, row);
+                let row = Row::new(collection, row, &depends).ok_or_else(HttpError::not_found)?;
+                Ok(serde_json::to_value(row)?)
+            }
+            ("PUT", ["collections", collection_id, "rows", row]) => {
+                let collection_id = collection_id_from(collection_id)?;
+                let row = row_from(row)?;
+                let (activity, term_begin, term_end, fields) =
+                    serde_json::from_str::<Write>(body)?.into_parts();
+                let collection = self
+                    .database
+                    .collection_mut(collection_id)
+                    .filter(|collection| collection.uuid(row).is_some())
+                    .ok_or_else(HttpError::not_found)?;
+                collection
+                    .update(row, activity, term_begin, term_end, fields)
+                    .await?;
+                Ok(json!({}))
+            }
+            ("DELETE", ["collections", collection_id, "rows", row]) => {
+                let collection_id = collection_id_from(collection_id)?;
+                let row = row_from(row)?;
+                self.database
+                    .delete(&CollectionRow::new(collection_id, row))
+                    .await?;
+                Ok(json!({}))
+            }
+            ("POST", ["relations"]) => {
+                let request: RegisterRelation = serde_json::from_str(body)?;
+                for collection_row in [&request.depend, &request.pend] {
+                    if !self.row_exists(collection_row) {
+                        return Err(HttpError::new(
+                            400,
+                            format!(
+                                "no row {} in collection {}",
+                                collection_row.row(),
+                                collection_row.collection_id()
+                            ),
+                        ));
+                    }
+                }
+                self.database
+                    .register_relation(&request.key, &request.depend, &request.pend)
+                    .await?;
+                Ok(json!({}))
+            }
+            ("POST", ["search"]) => {
+                let search: Search = serde_json::from_str(body)?;
+                let result = search.result(&self.database).await;
+                Ok(serde_json::to_value(Found::from(&result))?)
+            }
+            ("GET", ["sessions"]) => Ok(Value::Array(
+                self.database
+                    .sessions()
+                    .iter()
+                    .map(|session| {
+                        json!({
+                            "name": session.name(),
+                            "access_at": session.access_at(),
+                            "expire": session.expire(),
+                        })
+                    })
+                    .collect(),
+            )),
+            ("POST", ["sessions", name, action]) => {
+                if !is_name(name) {
+                    return Err(HttpError::new(400, "invalid session name"));
+                }
+                self.session_action(name, action, body).await
+            }
+            _ => Err(HttpError::not_found()),
+        }
+    }
+
+    async fn session_action(
+        &mut self,
+        name: &str,
+        action: &str,
+        body: &str,
+    ) -> Result<Value, HttpError> {
+        if !matches!(action, "update" | "search" | "cursor" | "commit" | "clear") {
+            return Err(HttpError::not_found());
+        }
+        let database = &mut self.database;
+        if !self.sessions.contains_key(name) {
+            self.sessions
+                .insert(name.to_owned(), database.session(name, None)?);
+        }
+        let session = self.sessions.get_mut(name).unwrap();
+        match action {
+            "update" => {
+                let records: Vec<Record> = serde_json::from_str(body)?;
+                let rows = database
+                    .update(session, records.into_iter().map(Into::into).collect())
+                    .await?;
+                Ok(json!({ "rows": rows }))
+            }
+            "search" => {
+                let search: Search = serde_json::from_str(body)?;
+                let result = session.result_with(&search.result(database).await).await;
+                Ok(serde_json::to_value(Found::from(&result))?)
+            }
+            "cursor" => {
+                let cursor: Cursor = serde_json::from_str(body)?;
+                session.set_sequence_cursor(cursor.current);
+                Ok(json!({}))
+            }
+            "commit" => {
+                let rows = database.commit(session).await?;
+                Ok(json!({ "rows": rows }))
+            }
+            "clear" => {
+                database.session_clear(session)?;
+                self.sessions.remove(name);
+                Ok(json!({}))
+            }
+            _ => Err(HttpError::not_found()),
+        }
+    }
+
+    fn row_exists(&self, collection_row: &CollectionRow) -> bool {
+        collection_row.collection_id().get() > 0
+            && self
+                .database
+                .collection(collection_row.collection_id())
+                .is_some_and(|collection| collection.uuid(collection_row.row()).is_some())
+    }
+
+    fn collections(&self) -> Value {
+        Value::Array(
+            self.database
+                .collection_entries()
+                .filter_map(|(collection_id, name)| {
+                    let collection = self.database.collection(collection_id)?;
+                    Some(json!({
+                        "collection_id": collection_id,
+                        "name": name,
+                        "rows": collection.data().all().len(),
+                    }))
+                })
+                .collect(),
+        )
+    }
+}
+
+fn collection_id_from(s: &str) -> Result<NonZeroI32, HttpError> {
+    s.parse()
+        .ok()
+        .filter(|id: &NonZeroI32| id.get() > 0)
+        .ok_or_else(HttpError::not_found)
+}
+
+fn row_from(s: &str) -> Result<NonZeroU32, HttpError> {
+    s.parse().map_err(|_| HttpError::not_found())
+}
+
+/// Names of collections and sessions become directory names.
+fn is_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
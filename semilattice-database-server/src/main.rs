@@ -0,0 +1,28 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+use semilattice_database_server::Server;
+
+#[derive(Parser)]
+#[command(
+    name = "semilattice-server",
+    version,
+    about = "Serves a semilattice-database directory over HTTP/JSON"
+)]
+struct Cli {
+    /// The database directory. Created if it does not exist.
+    dir: PathBuf,
+
+    #[arg(long, default_value = "127.0.0.1:7700")]
+    listen: String,
+}
+
+fn main() -> std::io::Result<()> {
+    let cli = Cli::parse();
+    let server = Server::new(cli.dir, cli.listen.as_str())?;
+    if let Some(addr) = server.local_addr() {
+        eprintln!("listening on {}", addr);
+    }
+    server.run();
+    Ok(())
+}
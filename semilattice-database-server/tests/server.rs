@@ -0,0 +1,186 @@
+#[cfg(test)]
+#[test]
+fn server() {
+    use std::{
+        io::{Read, Write},
+        net::{SocketAddr, TcpStream},
+    };
+
+    use semilattice_database_server::Server;
+    use serde_json::{json, Value};
+
+    let dir = "./sl-test-server/";
+
+    if std::path::Path::new(dir).exists() {
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    let (sender, receiver) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        let server = Server::new(dir.into(), "127.0.0.1:0").unwrap();
+        sender.send(server.local_addr().unwrap()).unwrap();
+        server.run();
+    });
+    let addr: SocketAddr = receiver.recv().unwrap();
+
+    let request = |method: &str, path: &str, body: Value| -> (u16, Value) {
+        let body = body.to_string();
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(
+            stream,
+            "{} {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+            method,
+            path,
+            body.len(),
+            body
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        let status = head.split(' ').nth(1).unwrap().parse().unwrap();
+        (status, serde_json::from_str(body).unwrap())
+    };
+
+    let (_, person) = request("POST", "/collections", json!({"name": "person"}));
+    let person = person["collection_id"].as_i64().unwrap();
+    let (_, history) = request("POST", "/collections", json!({"name": "history"}));
+    let history = history["collection_id"].as_i64().unwrap();
+
+    let (status, joe) = request(
+        "POST",
+        &format!("/collections/{}/rows", person),
+        json!({"fields": {"name": "Joe", "birthday": "1972-08-02"}}),
+    );
+    assert_eq!(status, 200);
+    let joe = joe["row"].as_u64().unwrap();
+    let (_, event) = request(
+        "POST",
+        &format!("/collections/{}/rows", history),
+        json!({"fields": {"event": "Birth"}}),
+    );
+    request(
+        "POST",
+        "/relations",
+        json!({
+            "key": "history",
+            "depend": {"collection_id": person, "row": joe},
+            "pend": {"collection_id": history, "row": event["row"]},
+        }),
+    );
+    request(
+        "PUT",
+        &format!("/collections/{}/rows/{}", person, joe),
+        json!({"fields": {"name": "Joseph"}}),
+    );
+
+    let (_, row) = request(
+        "GET",
+        &format!("/collections/{}/rows/{}", person, joe),
+        json!({}),
+    );
+    assert_eq!(row["fields"]["name"], "Joseph");
+    assert_eq!(row["fields"]["birthday"], "1972-08-02");
+    assert_eq!(row["activity"], "Active");
+
+    let search = json!({
+        "collection_id": person,
+        "conditions": [{"Field": ["name", {"Forward": "Jo"}]}],
+        "join": {
+            "history": {
+                "collection_id": history,
+                "relation_key": "history",
+                "conditions": [],
+                "join": {}
+            }
+        }
+    });
+    let (_, found) = request("POST", "/search", search.clone());
+    assert_eq!(found["rows"], json!([joe]));
+    assert_eq!(
+        found["join"]["history"][joe.to_string()]["rows"],
+        json!([1])
+    );
+
+    let (status, _) = request(
+        "POST",
+        "/sessions/edit/update",
+        json!([{"Update": {"collection_id": person, "row": null, "fields": {"name": "Jocelyn"}}}]),
+    );
+    assert_eq!(status, 200);
+    let (_, found) = request("POST", "/sessions/edit/search", search.clone());
+    assert_eq!(found["rows"], json!([-1, joe]));
+
+    request("POST", "/sessions/edit/cursor", json!({"current": 0}));
+    let (_, found) = request("POST", "/sessions/edit/search", search.clone());
+    assert_eq!(found["rows"], json!([joe]));
+    request("POST", "/sessions/edit/cursor", json!({"current": 1}));
+
+    let (_, committed) = request("POST", "/sessions/edit/commit", json!({}));
+    assert_eq!(
+        committed["rows"],
+        json!([{"collection_id": person, "row": 2}])
+    );
+    let (_, found) = request("POST", "/search", search);
+    assert_eq!(found["rows"], json!([joe, 2]));
+
+    request(
+        "POST",
+        "/sessions/draft/update",
+        json!([{"Delete": {"collection_id": person, "row": joe}}]),
+    );
+    request("POST", "/sessions/draft/clear", json!({}));
+    let (_, sessions) = request("GET", "/sessions", json!({}));
+    assert!(sessions.as_array().unwrap().is_empty());
+
+    let (status, _) = request(
+        "DELETE",
+        &format!("/collections/{}/rows/{}", person, joe),
+        json!({}),
+    );
+    assert_eq!(status, 200);
+    let (status, _) = request(
+        "GET",
+        &format!("/collections/{}/rows/{}", person, joe),
+        json!({}),
+    );
+    assert_eq!(status, 404);
+    let (_, collections) = request("GET", "/collections", json!({}));
+    assert_eq!(
+        collections,
+        json!([
+            {"collection_id": person, "name": "person", "rows": 1},
+            {"collection_id": history, "name": "history", "rows": 0},
+        ])
+    );
+
+    let (status, error) = request("POST", "/sessions/../update", json!([]));
+    assert_eq!(status, 400);
+    assert!(error["error"].is_string());
+    let (status, _) = request("POST", "/sessions/typo/updat", json!([]));
+    assert_eq!(status, 404);
+    assert!(!std::path::Path::new(dir).join("sessions/typo").exists());
+
+    let (status, _) = request("POST", "/collections", json!({"name": "../../escaped"}));
+    assert_eq!(status, 400);
+    assert!(!std::path::Path::new(dir).join("escaped").exists());
+
+    for pend in [
+        json!({}),
+        json!({"collection_id": person, "row": 99}),
+        json!({"collection_id": 99, "row": 1}),
+    ] {
+        let (status, _) = request(
+            "POST",
+            "/relations",
+            json!({"key": "history", "depend": {"collection_id": person, "row": 2}, "pend": pend}),
+        );
+        assert_eq!(status, 400);
+    }
+    let (status, _) = request(
+        "DELETE",
+        &format!("/collections/{}/rows/2", person),
+        json!({}),
+    );
+    assert_eq!(status, 200);
+}
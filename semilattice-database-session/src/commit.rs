@@ -1,11 +1,14 @@
 use std::{
+    collections::BTreeMap,
     num::{NonZeroI32, NonZeroU32},
     sync::Arc,
 };
 
-use semilattice_database::{idx_binary::AvltrieeSearch, Activity, Term};
+use semilattice_database::{
+    audit::AuditContext, idx_binary::AvltrieeSearch, FieldName, Term, UniqueConflict, WriteError,
+};
 
-use hashbrown::HashMap;
+use hashbrown::{HashMap, HashSet};
 
 use crate::{
     session::{SessionData, SessionOperation},
@@ -13,17 +16,117 @@ use crate::{
 };
 
 impl SessionDatabase {
-    pub async fn commit(&mut self, session: &mut Session) -> Vec<CollectionRow> {
+    /// Writes the session to the main database and clears it.
+    /// Nothing is written and the session is kept if a row would break the current schema or a unique constraint.
+    pub async fn commit(
+        &mut self,
+        session: &mut Session,
+    ) -> Result<Vec<CollectionRow>, WriteError> {
+        self.check_writable()?;
         if let Some(ref mut data) = session.session_data {
-            let r = self.commit_inner(data).await;
-            self.session_clear(session);
-            r
+            self.check_writes(data)?;
+            let r = self.commit_inner(data).await?;
+            self.session_clear(session)?;
+            Ok(r)
         } else {
-            vec![]
+            Ok(vec![])
         }
     }
 
-    async fn commit_inner(&mut self, session_data: &SessionData) -> Vec<CollectionRow> {
+    /// [SessionDatabase::commit] with the changes recorded in the audit log as made by `actor` in this session.
+    pub async fn commit_as(
+        &mut self,
+        session: &mut Session,
+        actor: &str,
+    ) -> Result<Vec<CollectionRow>, WriteError> {
+        let context = self.audit_context();
+        self.set_audit_context(Some(AuditContext::new(actor).session(session.name())));
+        let r = self.commit(session).await;
+        self.set_audit_context(context);
+        r
+    }
+
+    /// The schema may have changed since the rows were written to the session, so they are validated again here.
+    fn check_writes(&self, session_data: &SessionData) -> Result<(), WriteError> {
+        // The fields each committed row ends up with. New rows are keyed by their session row under a negative collection id.
+        let mut writes: HashMap<NonZeroI32, BTreeMap<CollectionRow, HashMap<FieldName, Vec<u8>>>> =
+            HashMap::new();
+        let mut deleted: HashSet<CollectionRow> = HashSet::new();
+        for sequence in 1..=session_data.sequence_number.current() {
+            for session_row in session_data
+                .sequence
+                .iter_by(&sequence)
+                .collect::<Vec<_>>()
+                .into_iter()
+                .rev()
+            {
+                if let (Some(op), Some(collection_id), Some(row)) = (
+                    session_data.operation.value(session_row),
+                    session_data.collection_id.value(session_row),
+                    session_data.row.value(session_row),
+                ) {
+                    let collection_id = NonZeroI32::new(*collection_id).unwrap();
+                    let target = CollectionRow::new(
+                        collection_id,
+                        NonZeroU32::new(*row).unwrap_or(session_row),
+                    );
+                    let writes = writes.entry(collection_id.abs()).or_default();
+                    if *op == SessionOperation::Delete {
+                        writes.remove(&target);
+                        deleted.insert(target);
+                    } else {
+                        writes
+                            .entry(target)
+                            .or_default()
+                            .extend(session_fields(session_data, session_row));
+                    }
+                }
+            }
+        }
+
+        for (collection_id, writes) in writes.iter() {
+            let Some(collection) = self.collection(*collection_id) else {
+                continue;
+            };
+            let Some(schema) = collection.schema() else {
+                continue;
+            };
+            let mut keys: HashMap<(usize, Vec<Vec<u8>>), &CollectionRow> = HashMap::new();
+            for (target, fields) in writes.iter() {
+                let row = (target.collection_id().get() > 0).then(|| target.row());
+                collection.validate(fields, row.is_none())?;
+                for (i, field_names) in schema.unique.iter().enumerate() {
+                    let Some(key) = collection.unique_key(field_names, row, fields) else {
+                        continue;
+                    };
+                    let existing = collection
+                        .unique_rows(field_names, &key)
+                        .into_iter()
+                        .map(|row| CollectionRow::new(*collection_id, row))
+                        .find(|existing| {
+                            existing != target
+                                && !deleted.contains(existing)
+                                && !writes.contains_key(existing)
+                        })
+                        .or_else(|| keys.insert((i, key), target).cloned());
+                    if let Some(existing) = existing {
+                        return Err(UniqueConflict {
+                            collection_id: *collection_id,
+                            field_names: field_names.clone(),
+                            existing,
+                        }
+                        .into());
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn commit_inner(
+        &mut self,
+        session_data: &SessionData,
+    ) -> Result<Vec<CollectionRow>, WriteError> {
         let mut commit_rows = Vec::new();
 
         let mut session_collection_row_map: HashMap<CollectionRow, CollectionRow> = HashMap::new();
@@ -62,42 +165,27 @@ impl SessionDatabase {
                         let fields = if op == SessionOperation::Delete {
                             HashMap::new()
                         } else {
-                            session_data
-                                .fields
-                                .iter()
-                                .filter_map(|(field_name, field_data)| {
-                                    field_data
-                                        .value(session_row)
-                                        .map(|val| (field_name.clone(), val.to_owned()))
-                                })
-                                .collect()
+                            session_fields(session_data, session_row)
                         };
 
                         let session_collection_row =
                             CollectionRow::new(NonZeroI32::new(collection_id).unwrap(), row);
                         match op {
                             SessionOperation::New | SessionOperation::Update => {
-                                let activity = if *unsafe {
-                                    session_data.activity.value_unchecked(session_row)
-                                } == 1
-                                {
-                                    Activity::Active
-                                } else {
-                                    Activity::Inactive
-                                };
-                                let term_begin = Term::Overwrite(*unsafe {
-                                    session_data.term_begin.value_unchecked(session_row)
-                                });
-                                let term_end = Term::Overwrite(*unsafe {
-                                    session_data.term_end.value_unchecked(session_row)
-                                });
+                                let head = session_data.row_head(session_row).unwrap();
+                                let activity = head.activity;
+                                let term_begin = Term::Overwrite(head.term_begin);
+                                let term_end = Term::Overwrite(head.term_end);
 
+                                // Fields were validated by SessionDatabase::update.
                                 let collection_row = CollectionRow::new(
                                     main_collection_id,
                                     if op == SessionOperation::New {
                                         collection
-                                            .insert(activity, term_begin, term_end, fields)
-                                            .await
+                                            .insert_unchecked(
+                                                activity, term_begin, term_end, fields,
+                                            )
+                                            .await?
                                     } else {
                                         let row = if in_session {
                                             let main_collection_row = session_collection_row_map
@@ -108,15 +196,17 @@ impl SessionDatabase {
                                             row
                                         };
                                         collection
-                                            .update(row, activity, term_begin, term_end, fields)
-                                            .await;
+                                            .update_unchecked(
+                                                row, activity, term_begin, term_end, fields,
+                                            )
+                                            .await?;
                                         row
                                     },
                                 );
                                 commit_rows.push(collection_row.clone());
                                 self.relation_mut()
                                     .delete_pends_by_collection_row(&collection_row)
-                                    .await; //Delete once and re-register later
+                                    .await?; //Delete once and re-register later
 
                                 for relation_row in session_data
                                     .relation
@@ -130,22 +220,9 @@ impl SessionDatabase {
                                     ) {
                                         relation_temporary
                                             .entry(depend.clone())
-                                            .or_insert_with(|| Vec::new())
+                                            .or_insert_with(Vec::new)
                                             .push((
-                                                Arc::new(
-                                                    unsafe {
-                                                        std::str::from_utf8_unchecked(
-                                                            session_data
-                                                                .relation
-                                                                .key_names
-                                                                .value(
-                                                                    NonZeroU32::new(*key).unwrap(),
-                                                                )
-                                                                .unwrap(),
-                                                        )
-                                                    }
-                                                    .to_owned(),
-                                                ),
+                                                Arc::new(session_data.relation.key_name(*key)),
                                                 session_collection_row.clone(),
                                             ));
                                     }
@@ -158,11 +235,11 @@ impl SessionDatabase {
                                     if let Some(registered) =
                                         session_collection_row_map.get(&session_collection_row)
                                     {
-                                        self.delete(registered).await;
+                                        self.delete(registered).await?;
                                     }
                                 } else {
                                     self.delete(&CollectionRow::new(main_collection_id, row))
-                                        .await;
+                                        .await?;
                                 }
                                 session_collection_row_map.remove(&session_collection_row);
                             }
@@ -179,13 +256,20 @@ impl SessionDatabase {
                         pends,
                         &session_collection_row_map,
                     )
-                    .await;
+                    .await?;
                 }
             } else {
                 self.register_relations_with_session(&depend, pends, &session_collection_row_map)
-                    .await;
+                    .await?;
             }
         }
-        commit_rows
+        Ok(commit_rows)
     }
 }
+
+fn session_fields(
+    session_data: &SessionData,
+    session_row: NonZeroU32,
+) -> HashMap<FieldName, Vec<u8>> {
+    session_data.field_values(session_row).collect()
+}
@@ -2,17 +2,21 @@ mod commit;
 mod session;
 mod update;
 
+#[cfg(feature = "encryption")]
+pub use semilattice_database::EncryptionKey;
 pub use semilattice_database::{
-    search, Activity, Collection, CollectionRow, Condition, CustomOrderKey, CustomSort, DataOption,
-    Depend, FieldName, Order, OrderKey, SearchResult, Term, Uuid,
+    audit, fulltext, search, Activity, Cipher, Collection, CollectionRecord, CollectionRow,
+    CollectionStats, Condition, CustomOrderKey, CustomSort, DataOption, DatabaseStats, Depend,
+    FieldError, FieldName, FieldSchema, FieldType, Order, OrderKey, PurgePolicy, PurgeReport,
+    RelationStats, Schema, SearchResult, Term, UniqueConflict, Uuid, ValidationError, WriteError,
 };
 pub use session::{
-    Depends, Pend, Session, SessionCustomOrder, SessionOrder, SessionOrderKey, SessionRecord,
-    SessionSearchResult,
+    Depends, Pend, Session, SessionCustomOrder, SessionExplain, SessionInfo, SessionOrder,
+    SessionOrderKey, SessionOverlay, SessionRecord, SessionSearchResult, SessionStats,
 };
 
 use std::{
-    io::Read,
+    io::{self, Read},
     num::{NonZeroI32, NonZeroI64, NonZeroU32},
     path::PathBuf,
     sync::Arc,
@@ -21,7 +25,6 @@ use std::{
 
 use hashbrown::HashMap;
 use semilattice_database::{idx_binary::AvltrieeUpdate, Database, Field, FileMmap, IdxFile};
-use session::SessionInfo;
 
 pub struct SessionDatabase {
     database: Database,
@@ -46,14 +49,87 @@ impl SessionDatabase {
         collection_settings: Option<std::collections::HashMap<String, DataOption>>,
         relation_reserve_unit: u32,
     ) -> Self {
-        let database = Database::new(dir.clone(), collection_settings, relation_reserve_unit);
-        let mut sessions_dir = dir.to_path_buf();
-        sessions_dir.push("sessions");
+        Self::open(dir, collection_settings, relation_reserve_unit)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Opens the database with [Database::open], failing if another writer has the directory locked.
+    pub fn open(
+        dir: PathBuf,
+        collection_settings: Option<std::collections::HashMap<String, DataOption>>,
+        relation_reserve_unit: u32,
+    ) -> io::Result<Self> {
+        let database = Database::open(dir.clone(), collection_settings, relation_reserve_unit)?;
+        Ok(Self {
+            database,
+            sessions_dir: dir.join("sessions"),
+        })
+    }
+
+    /// Opens the database with [Database::open_encrypted]. What is written to sessions is encrypted under the same key.
+    #[cfg(feature = "encryption")]
+    pub fn open_encrypted(
+        dir: PathBuf,
+        collection_settings: Option<std::collections::HashMap<String, DataOption>>,
+        relation_reserve_unit: u32,
+        key: &EncryptionKey,
+    ) -> io::Result<Self> {
+        let database =
+            Database::open_encrypted(dir.clone(), collection_settings, relation_reserve_unit, key)?;
+        Ok(Self {
+            database,
+            sessions_dir: dir.join("sessions"),
+        })
+    }
+
+    /// A database with its sessions in a temporary directory, removed when dropped, see [Database::temporary].
+    pub fn temporary(
+        collection_settings: Option<std::collections::HashMap<String, DataOption>>,
+        relation_reserve_unit: u32,
+    ) -> Self {
+        let database = Database::temporary(collection_settings, relation_reserve_unit);
+        let sessions_dir = database.dir().join("sessions");
         Self {
             database,
             sessions_dir,
         }
     }
+
+    /// Opens an existing database for reading with [Database::open_read_only].
+    /// Opening, clearing, updating and committing sessions fail with [WriteError::ReadOnly].
+    pub fn open_read_only(
+        dir: PathBuf,
+        collection_settings: Option<std::collections::HashMap<String, DataOption>>,
+    ) -> io::Result<Self> {
+        let database = Database::open_read_only(dir.clone(), collection_settings)?;
+        Ok(Self {
+            database,
+            sessions_dir: dir.join("sessions"),
+        })
+    }
+
+    /// [SessionDatabase::open_read_only] for a database created with [SessionDatabase::open_encrypted] under `key`.
+    #[cfg(feature = "encryption")]
+    pub fn open_read_only_encrypted(
+        dir: PathBuf,
+        collection_settings: Option<std::collections::HashMap<String, DataOption>>,
+        key: &EncryptionKey,
+    ) -> io::Result<Self> {
+        let database = Database::open_read_only_encrypted(dir.clone(), collection_settings, key)?;
+        Ok(Self {
+            database,
+            sessions_dir: dir.join("sessions"),
+        })
+    }
+
+    fn check_writable(&self) -> Result<(), WriteError> {
+        if self.is_read_only() {
+            Err(WriteError::ReadOnly)
+        } else {
+            Ok(())
+        }
+    }
+
     pub fn sessions(&self) -> Vec<SessionInfo> {
         let mut sessions = Vec::new();
         if self.sessions_dir.exists() {
@@ -73,7 +149,7 @@ impl SessionDatabase {
                                     access_at = m.duration_since(UNIX_EPOCH).unwrap().as_secs();
                                     let mut file = std::fs::File::open(expire_file).unwrap();
                                     let mut buf = [0u8; 8];
-                                    file.read(&mut buf).unwrap();
+                                    file.read_exact(&mut buf).unwrap();
                                     expire = i64::from_be_bytes(buf);
                                 }
                             }
@@ -89,7 +165,13 @@ impl SessionDatabase {
         }
         sessions
     }
-    pub fn session_gc(&self, default_expire_interval_sec: i64) {
+    /// None if there is no session of that name.
+    pub fn session_stats(&self, session_name: &str) -> Option<SessionStats> {
+        SessionStats::read(&self.session_dir(session_name))
+    }
+
+    pub fn session_gc(&self, default_expire_interval_sec: i64) -> Result<(), WriteError> {
+        self.check_writable()?;
         for session in self.sessions().into_iter() {
             let expire = if session.expire < 0 {
                 default_expire_interval_sec
@@ -107,22 +189,38 @@ impl SessionDatabase {
                 std::fs::remove_dir_all(&path).unwrap();
             }
         }
+        Ok(())
     }
 
-    pub fn session(&self, session_name: &str, expire_interval_sec: Option<i64>) -> Session {
+    pub fn session(
+        &self,
+        session_name: &str,
+        expire_interval_sec: Option<i64>,
+    ) -> Result<Session, WriteError> {
+        self.check_writable()?;
         let session_dir = self.session_dir(session_name);
         if !session_dir.exists() {
             std::fs::create_dir_all(&session_dir).unwrap();
         }
-        Session::new(self, session_name, expire_interval_sec)
+        Ok(Session::new(self, session_name, expire_interval_sec))
+    }
+    /// Reads an existing session without opening it, so its expiry is left alone. Works on a read-only database.
+    /// None if there is no session of that name or nothing has been written to it.
+    pub fn inspect_session(&self, session_name: &str) -> Option<Session> {
+        if self.is_read_only() {
+            file_mmap::read_only(|| Session::inspect(self, session_name))
+        } else {
+            Session::inspect(self, session_name)
+        }
     }
+
     pub fn session_dir(&self, session_name: &str) -> PathBuf {
         let mut dir = self.sessions_dir.clone();
         dir.push(session_name);
         dir
     }
     fn delete_dir(dir: PathBuf) {
-        for d in dir.read_dir().unwrap().into_iter() {
+        for d in dir.read_dir().unwrap() {
             let d = d.unwrap();
             if d.file_type().unwrap().is_dir() {
                 let dir = d.path();
@@ -134,24 +232,32 @@ impl SessionDatabase {
         }
         let _ = std::fs::remove_dir_all(dir);
     }
-    pub fn session_clear(&self, session: &mut Session) {
+    pub fn session_clear(&self, session: &mut Session) -> Result<(), WriteError> {
+        self.check_writable()?;
         let session_dir = self.session_dir(session.name());
         session.session_data = None;
         if session_dir.exists() {
             Self::delete_dir(session_dir);
         }
         session.temporary_data.clear();
+        Ok(())
     }
 
-    pub fn session_restart(&self, session: &mut Session, expire_interval_sec: Option<i64>) {
-        self.session_clear(session);
-        self.init_session(session, expire_interval_sec)
+    pub fn session_restart(
+        &self,
+        session: &mut Session,
+        expire_interval_sec: Option<i64>,
+    ) -> Result<(), WriteError> {
+        self.session_clear(session)?;
+        self.init_session(session, expire_interval_sec);
+        Ok(())
     }
 
     fn init_session(&self, session: &mut Session, expire_interval_sec: Option<i64>) {
         let session_dir = self.session_dir(session.name());
         std::fs::create_dir_all(&session_dir).unwrap();
-        let session_data = Session::new_data(&session_dir, expire_interval_sec);
+        let mut session_data = Session::new_data(&session_dir, expire_interval_sec);
+        session_data.set_cipher(&session_dir, self.cipher().cloned());
         let temporary_data = session_data.init_temporary_data();
         session.session_data = Some(session_data);
         session.temporary_data = temporary_data;
@@ -161,10 +267,12 @@ impl SessionDatabase {
         &self,
         session: &mut Session,
         records: Vec<SessionRecord>,
-    ) -> Vec<CollectionRow> {
+    ) -> Result<Vec<CollectionRow>, WriteError> {
+        self.check_writable()?;
+        self.validate(&records)?;
         let mut ret = vec![];
         let session_dir = self.session_dir(session.name());
-        if let None = session.session_data {
+        if session.session_data.is_none() {
             self.init_session(session, None);
         }
         if let Some(ref mut session_data) = session.session_data {
@@ -229,7 +337,7 @@ impl SessionDatabase {
                 .await,
             );
         }
-        ret
+        Ok(ret)
     }
 
     pub fn depends_with_session(
@@ -277,15 +385,16 @@ impl SessionDatabase {
         depend: &CollectionRow,
         pends: Vec<(Arc<String>, CollectionRow)>,
         row_map: &HashMap<CollectionRow, CollectionRow>,
-    ) {
+    ) -> Result<(), WriteError> {
         for (key_name, pend) in pends.iter() {
             if pend.collection_id().get() < 0 {
                 if let Some(pend) = row_map.get(pend) {
-                    self.register_relation(&key_name, depend, pend).await;
+                    self.register_relation(key_name, depend, pend).await?;
                 }
             } else {
-                self.register_relation(&key_name, depend, pend).await;
+                self.register_relation(key_name, depend, pend).await?;
             }
         }
+        Ok(())
     }
 }
@@ -1,5 +1,6 @@
 mod data;
 mod operation;
+mod record;
 mod relation;
 mod search;
 mod sequence;
@@ -8,7 +9,7 @@ mod temporary_data;
 
 pub use data::SessionData;
 pub use operation::{Depends, Pend, SessionOperation, SessionRecord};
-pub use search::SessionSearchResult;
+pub use search::{SessionExplain, SessionOverlay, SessionSearchResult};
 use semilattice_database::{FieldName, Fields};
 pub use sort::{SessionCustomOrder, SessionOrder, SessionOrderKey};
 pub use temporary_data::{TemporaryData, TemporaryDataEntity};
@@ -30,6 +31,49 @@ pub struct SessionInfo {
     pub(super) expire: i64,
 }
 
+/// Size of a session, see [SessionDatabase::session_stats].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SessionStats {
+    /// Number of updates, including those undone by moving the cursor back.
+    pub sequences: usize,
+    pub current: usize,
+    /// Rows written by all the updates.
+    pub rows: usize,
+    pub bytes: u64,
+}
+impl SessionStats {
+    /// Reads the files of a session without opening it, so its expiry is left alone.
+    pub(crate) fn read(session_dir: &Path) -> Option<Self> {
+        if !session_dir.join("sequence.i").exists() {
+            return None;
+        }
+        let sequence_number = SequenceNumber::new(session_dir.join("sequence_number.i"));
+        let sequence: IdxFile<usize> = IdxFile::new(session_dir.join("sequence.i"), 1);
+        Some(Self {
+            sequences: sequence_number.max(),
+            current: sequence_number.current(),
+            rows: sequence.iter().count(),
+            bytes: dir_bytes(session_dir),
+        })
+    }
+}
+
+fn dir_bytes(dir: &Path) -> u64 {
+    dir.read_dir()
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|d| {
+            let path = d.path();
+            if path.is_dir() {
+                dir_bytes(&path)
+            } else {
+                d.metadata().map_or(0, |m| m.len())
+            }
+        })
+        .sum()
+}
+
 impl SessionInfo {
     pub fn name(&self) -> &str {
         &self.name
@@ -54,15 +98,16 @@ impl Session {
         expire_interval_sec: Option<i64>,
     ) -> Self {
         let mut name: String = name.into();
-        assert!(name != "");
-        if name == "" {
+        assert!(!name.is_empty());
+        if name.is_empty() {
             name = "untitiled".to_owned();
         }
         let session_dir = main_database.session_dir(&name);
         if !session_dir.exists() {
             std::fs::create_dir_all(&session_dir).unwrap();
         }
-        let session_data = Self::new_data(&session_dir, expire_interval_sec);
+        let mut session_data = Self::new_data(&session_dir, expire_interval_sec);
+        session_data.set_cipher(&session_dir, main_database.cipher().cloned());
         let temporary_data = session_data.init_temporary_data();
         Self {
             name,
@@ -71,13 +116,31 @@ impl Session {
         }
     }
 
+    /// An existing session as stored. Its files are not created or rewritten, so the expiry and access time stay as they were.
+    pub(super) fn inspect(main_database: &SessionDatabase, name: &str) -> Option<Self> {
+        let session_dir = main_database.session_dir(name);
+        if !session_dir.join("sequence.i").exists() {
+            return None;
+        }
+        let mut session_data = Self::load_data(&session_dir);
+        session_data.set_cipher(&session_dir, main_database.cipher().cloned());
+        let temporary_data = session_data.init_temporary_data();
+        Some(Self {
+            name: name.to_owned(),
+            session_data: Some(session_data),
+            temporary_data,
+        })
+    }
+
     pub fn name(&self) -> &str {
         &self.name
     }
 
+    /// Moves to an earlier or later state of the session. Searches see the session as of `current`.
     pub fn set_sequence_cursor(&mut self, current: usize) {
         if let Some(session_data) = &mut self.session_data {
             session_data.sequence_number.set_current(current);
+            self.temporary_data = session_data.init_temporary_data();
         }
     }
 
@@ -96,18 +159,26 @@ impl Session {
         let mut file = std::fs::OpenOptions::new()
             .create(true)
             .write(true)
+            .truncate(true)
             .open(access)
             .unwrap();
         let expire = expire_interval_sec.unwrap_or(-1);
-        file.write(&expire.to_be_bytes()).unwrap();
+        file.write_all(&expire.to_be_bytes()).unwrap();
 
-        let mut fields = Fields::new();
         let mut fields_dir = session_dir.to_path_buf();
         fields_dir.push("fields");
         if !fields_dir.exists() {
-            std::fs::create_dir_all(&fields_dir.to_owned()).unwrap();
+            std::fs::create_dir_all(&fields_dir).unwrap();
         }
-        for p in fields_dir.read_dir().unwrap().into_iter() {
+        Self::load_data(session_dir)
+    }
+
+    /// The data of a session as stored, without touching its expiry.
+    fn load_data(session_dir: &Path) -> SessionData {
+        let mut fields = Fields::new();
+        let mut fields_dir = session_dir.to_path_buf();
+        fields_dir.push("fields");
+        for p in fields_dir.read_dir().into_iter().flatten() {
             let p = p.unwrap();
             let path = p.path();
             if path.is_dir() {
@@ -190,6 +261,8 @@ impl Session {
             ),
             fields,
             relation: SessionRelation::new(session_dir, 1),
+            cipher: None,
+            head: None,
         }
     }
 }
@@ -8,7 +8,7 @@ use hashbrown::HashMap;
 
 use semilattice_database::{
     idx_binary::{AvltrieeSearch, AvltrieeUpdate},
-    Activity, CollectionRow, Depend, Field, FieldName, Fields, IdxFile,
+    Activity, Cipher, CollectionRow, Depend, Field, FieldName, Fields, IdxFile,
 };
 
 use super::{
@@ -28,9 +28,99 @@ pub struct SessionData {
     pub(crate) uuid: IdxFile<u128>,
     pub(crate) fields: Fields,
     pub(crate) relation: SessionRelation,
+    /// Encrypts the field values, relation keys and row heads written to the session directory when the database is encrypted.
+    pub(crate) cipher: Option<Cipher>,
+    /// Under a cipher, the activity, terms and uuid of each session row are kept encrypted here and their indexes hold zeros.
+    pub(crate) head: Option<Field>,
+}
+
+/// The activity, terms and uuid written to a session row.
+pub(crate) struct SessionRowHead {
+    pub(crate) activity: Activity,
+    pub(crate) term_begin: u64,
+    pub(crate) term_end: u64,
+    pub(crate) uuid: u128,
+}
+impl SessionRowHead {
+    fn to_bytes(&self) -> Vec<u8> {
+        [
+            &[self.activity as u8][..],
+            &self.term_begin.to_le_bytes(),
+            &self.term_end.to_le_bytes(),
+            &self.uuid.to_le_bytes(),
+        ]
+        .concat()
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != 33 {
+            return None;
+        }
+        Some(Self {
+            activity: activity(bytes[0]),
+            term_begin: u64::from_le_bytes(bytes[1..9].try_into().unwrap()),
+            term_end: u64::from_le_bytes(bytes[9..17].try_into().unwrap()),
+            uuid: u128::from_le_bytes(bytes[17..33].try_into().unwrap()),
+        })
+    }
+}
+
+fn activity(value: u8) -> Activity {
+    if value == 1 {
+        Activity::Active
+    } else {
+        Activity::Inactive
+    }
 }
 
 impl SessionData {
+    /// Encrypts what is written from now on under `cipher`, and reads what was written under it.
+    pub(crate) fn set_cipher(&mut self, session_dir: &Path, cipher: Option<Cipher>) {
+        let head_dir = session_dir.join("head");
+        self.head = (cipher.is_some() && head_dir.exists()).then(|| Field::new(head_dir, 1));
+        self.relation.cipher = cipher.clone();
+        self.cipher = cipher;
+    }
+
+    /// The activity, terms and uuid written to `session_row`.
+    pub(crate) fn row_head(&self, session_row: NonZeroU32) -> Option<SessionRowHead> {
+        if let Some(cipher) = &self.cipher {
+            let bytes = self.head.as_ref()?.value(session_row)?;
+            return Some(
+                cipher
+                    .decrypt(&self.head_context(session_row), bytes)
+                    .and_then(|bytes| SessionRowHead::from_bytes(&bytes))
+                    .unwrap_or_else(|| panic!("session row {} fails authentication", session_row)),
+            );
+        }
+        Some(SessionRowHead {
+            activity: activity(*self.activity.value(session_row)?),
+            term_begin: *self.term_begin.value(session_row)?,
+            term_end: *self.term_end.value(session_row)?,
+            uuid: *self.uuid.value(session_row)?,
+        })
+    }
+
+    /// Values are bound to the collection of their session row, written before them.
+    fn collection_context(&self, session_row: NonZeroU32) -> Vec<u8> {
+        let collection_id = self.collection_id.value(session_row).cloned().unwrap_or(0);
+        [b"session\0", &collection_id.to_le_bytes()[..]].concat()
+    }
+
+    fn field_context(&self, session_row: NonZeroU32, field_name: &FieldName) -> Vec<u8> {
+        [
+            &self.collection_context(session_row)[..],
+            b"\0field\0",
+            field_name.as_bytes(),
+        ]
+        .concat()
+    }
+
+    fn head_context(&self, session_row: NonZeroU32) -> Vec<u8> {
+        [&self.collection_context(session_row)[..], b"\0head"].concat()
+    }
+
+    #[allow(clippy::too_many_arguments)]
     pub async fn update(
         &mut self,
         session_dir: &Path,
@@ -42,15 +132,45 @@ impl SessionData {
         uuid: &u128,
         fields: &HashMap<FieldName, Vec<u8>>,
     ) {
+        let head = SessionRowHead {
+            activity: *activity,
+            term_begin: *term_begin,
+            term_end: *term_end,
+            uuid: *uuid,
+        };
+        if let Some(cipher) = &self.cipher {
+            let bytes = cipher.encrypt(&self.head_context(session_row), &head.to_bytes());
+            self.head
+                .get_or_insert_with(|| {
+                    let dir = session_dir.join("head");
+                    std::fs::create_dir_all(&dir).unwrap();
+                    Field::new(dir, 1)
+                })
+                .update(session_row, &bytes);
+        }
+        let head = if self.cipher.is_some() {
+            SessionRowHead {
+                activity: Activity::Inactive,
+                term_begin: 0,
+                term_end: 0,
+                uuid: 0,
+            }
+        } else {
+            head
+        };
         futures::join!(
             async { self.row.update(session_row, row) },
-            async { self.activity.update(session_row, &(*activity as u8)) },
-            async { self.term_begin.update(session_row, term_begin) },
-            async { self.term_end.update(session_row, term_end) },
-            async { self.uuid.update(session_row, uuid) }
+            async { self.activity.update(session_row, &(head.activity as u8)) },
+            async { self.term_begin.update(session_row, &head.term_begin) },
+            async { self.term_end.update(session_row, &head.term_end) },
+            async { self.uuid.update(session_row, &head.uuid) }
         );
 
         for (field_name, value) in fields.into_iter() {
+            let value = match &self.cipher {
+                Some(cipher) => cipher.encrypt(&self.field_context(session_row, field_name), value),
+                None => value.to_vec(),
+            };
             let field = if self.fields.contains_key(field_name) {
                 self.fields.get_mut(field_name).unwrap()
             } else {
@@ -66,7 +186,7 @@ impl SessionData {
                 }
             };
             //TODO: multi thread
-            field.update(session_row, value);
+            field.update(session_row, &value);
         }
     }
 
@@ -96,6 +216,30 @@ impl SessionData {
             .await;
     }
 
+    /// The fields written to `session_row`, decrypted.
+    pub(crate) fn field_values(
+        &self,
+        session_row: NonZeroU32,
+    ) -> impl Iterator<Item = (FieldName, Vec<u8>)> + '_ {
+        self.fields.iter().filter_map(move |(field_name, field)| {
+            let value = field.value(session_row)?;
+            Some((
+                field_name.clone(),
+                match &self.cipher {
+                    Some(cipher) => cipher
+                        .decrypt(&self.field_context(session_row, field_name), value)
+                        .unwrap_or_else(|| {
+                            panic!(
+                                "{} in the session fails authentication",
+                                field_name.as_str()
+                            )
+                        }),
+                    None => value.to_vec(),
+                },
+            ))
+        })
+    }
+
     #[inline(always)]
     pub(crate) fn init_temporary_data(&self) -> TemporaryData {
         let mut temporary_data = TemporaryData::new();
@@ -113,9 +257,8 @@ impl SessionData {
                             collection_id
                         })
                         .unwrap();
-                        let temporary_collection = temporary_data
-                            .entry(main_collection_id)
-                            .or_insert(HashMap::new());
+                        let temporary_collection =
+                            temporary_data.entry(main_collection_id).or_default();
                         let row = *unsafe { self.row.value_unchecked(session_row) };
 
                         let temporary_row = NonZeroI64::new(if row == 0 {
@@ -153,29 +296,20 @@ impl SessionData {
                                     )
                                 })
                                 .or_insert(HashMap::new());
-                            self.fields.iter().for_each(|(field_name, val)| {
-                                if let Some(v) = val.value(session_row) {
-                                    row_fields.insert(field_name.clone(), v.into());
-                                }
+                            row_fields.extend(self.field_values(session_row));
+                            let head = self.row_head(session_row).unwrap_or(SessionRowHead {
+                                activity: Activity::Inactive,
+                                term_begin: 0,
+                                term_end: 0,
+                                uuid: 0,
                             });
                             temporary_collection.insert(
                                 temporary_row,
                                 TemporaryDataEntity {
-                                    activity: if *unsafe {
-                                        self.activity.value_unchecked(session_row)
-                                    } == 1
-                                    {
-                                        Activity::Active
-                                    } else {
-                                        Activity::Inactive
-                                    },
-                                    term_begin: *unsafe {
-                                        self.term_begin.value_unchecked(session_row)
-                                    },
-                                    term_end: *unsafe {
-                                        self.term_end.value_unchecked(session_row)
-                                    },
-                                    uuid: *self.uuid.value(session_row).unwrap_or(&0),
+                                    activity: head.activity,
+                                    term_begin: head.term_begin,
+                                    term_end: head.term_end,
+                                    uuid: head.uuid,
                                     operation,
                                     fields: row_fields.clone(),
                                     depends: {
@@ -189,20 +323,7 @@ impl SessionData {
                                                     self.relation.rows.depend.value(relation_row),
                                                 ) {
                                                     Some(Depend::new(
-                                                        Arc::new(
-                                                            unsafe {
-                                                                std::str::from_utf8_unchecked(
-                                                                    self.relation
-                                                                        .key_names
-                                                                        .value(
-                                                                            NonZeroU32::new(*key)
-                                                                                .unwrap(),
-                                                                        )
-                                                                        .unwrap(),
-                                                                )
-                                                            }
-                                                            .into(),
-                                                        ),
+                                                        Arc::new(self.relation.key_name(*key)),
                                                         depend.clone(),
                                                     ))
                                                 } else {
@@ -4,7 +4,7 @@ use std::{
 };
 
 use hashbrown::HashMap;
-use semilattice_database::{Activity, FieldName, Term};
+use semilattice_database::{codec::Codec, Activity, CollectionRecord, FieldName, Term};
 
 use crate::CollectionRow;
 
@@ -45,3 +45,64 @@ pub enum SessionRecord {
         row: NonZeroU32,
     },
 }
+impl SessionRecord {
+    /// An update of `row`, or a new row when `row` is None, that keeps everything not set afterwards.
+    pub fn update(collection_id: NonZeroI32, row: Option<NonZeroU32>) -> Self {
+        Self::Update {
+            collection_id,
+            row,
+            activity: Activity::Active,
+            term_begin: Term::Default,
+            term_end: Term::Default,
+            fields: HashMap::new(),
+            depends: Depends::Default,
+            pends: vec![],
+        }
+    }
+
+    /// An update carrying every field of `record`, see [SessionRecord::update].
+    pub fn from_record<R: CollectionRecord>(
+        collection_id: NonZeroI32,
+        row: Option<NonZeroU32>,
+        record: &R,
+    ) -> Self {
+        let mut this = Self::update(collection_id, row);
+        if let Self::Update { fields, .. } = &mut this {
+            *fields = record.to_fields();
+        }
+        this
+    }
+
+    /// Sets a field to the encoding of `value`. Has no effect on a delete.
+    pub fn field<T: Codec>(self, field_name: FieldName, value: T) -> Self {
+        self.field_bytes(field_name, value.encode())
+    }
+
+    pub fn field_bytes(mut self, field_name: FieldName, value: Vec<u8>) -> Self {
+        if let Self::Update { fields, .. } = &mut self {
+            fields.insert(field_name, value);
+        }
+        self
+    }
+
+    pub fn activity(mut self, value: Activity) -> Self {
+        if let Self::Update { activity, .. } = &mut self {
+            *activity = value;
+        }
+        self
+    }
+
+    pub fn depends(mut self, value: Depends) -> Self {
+        if let Self::Update { depends, .. } = &mut self {
+            *depends = value;
+        }
+        self
+    }
+
+    pub fn pend(mut self, value: Pend) -> Self {
+        if let Self::Update { pends, .. } = &mut self {
+            pends.push(value);
+        }
+        self
+    }
+}
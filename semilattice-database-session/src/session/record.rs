@@ -0,0 +1,99 @@
+use std::{collections::BTreeSet, num::NonZeroI64, sync::Arc};
+
+use semilattice_database::{
+    search::{FieldDecoder, Record, RecordSelect},
+    uuid_string, FieldName,
+};
+
+use crate::{Session, SessionDatabase};
+
+use super::SessionSearchResult;
+
+impl Session {
+    /// Reads the rows of a result with the session's changes applied, in ascending order.
+    pub fn records<D: FieldDecoder>(
+        &self,
+        database: &SessionDatabase,
+        result: &SessionSearchResult,
+        select: &RecordSelect,
+        decoder: &D,
+    ) -> Vec<Record<D::Value>> {
+        self.records_in(
+            database,
+            result,
+            result.rows().iter().cloned(),
+            select,
+            decoder,
+        )
+    }
+
+    /// Reads the given rows of a result in the given order, for example the output of [Session::sort].
+    pub fn records_in<D: FieldDecoder>(
+        &self,
+        database: &SessionDatabase,
+        result: &SessionSearchResult,
+        rows: impl IntoIterator<Item = NonZeroI64>,
+        select: &RecordSelect,
+        decoder: &D,
+    ) -> Vec<Record<D::Value>> {
+        let Some(collection) = result
+            .collection_id()
+            .try_into()
+            .ok()
+            .and_then(|collection_id| database.collection(collection_id))
+        else {
+            return vec![];
+        };
+        let collection_id = collection.id();
+        let temporary_collection = self.temporary_data.get(&collection_id);
+        let default_select = RecordSelect::default();
+        rows.into_iter()
+            .filter_map(|row| {
+                let mut record = if let Some(ent) =
+                    temporary_collection.and_then(|temporary| temporary.get(&row))
+                {
+                    let read = |field_name: &FieldName| {
+                        let bytes = self.collection_field_bytes(collection, row, field_name);
+                        (
+                            Arc::clone(field_name),
+                            decoder.decode(collection_id, field_name, &bytes),
+                        )
+                    };
+                    Record {
+                        collection_id,
+                        row,
+                        uuid: uuid_string(ent.uuid()),
+                        activity: ent.activity(),
+                        term_begin: ent.term_begin(),
+                        term_end: ent.term_end(),
+                        last_updated: None,
+                        fields: if let Some(field_names) = &select.fields {
+                            field_names.iter().map(read).collect()
+                        } else {
+                            let mut field_names: BTreeSet<&FieldName> =
+                                ent.fields().keys().collect();
+                            if row.get() > 0 {
+                                field_names.extend(collection.data().fields().keys());
+                            }
+                            field_names.into_iter().map(read).collect()
+                        },
+                        depends: select.depends.then(|| ent.depends().clone()),
+                        join: Default::default(),
+                    }
+                } else {
+                    Record::read(database, collection, row.try_into().ok()?, select, decoder)
+                };
+                for (name, join) in result.join().iter() {
+                    if let Some(result) = join.get(&row) {
+                        let select = select.join.get(name).unwrap_or(&default_select);
+                        record.join.insert(
+                            Arc::clone(name),
+                            self.records(database, result, select, decoder),
+                        );
+                    }
+                }
+                Some(record)
+            })
+            .collect()
+    }
+}
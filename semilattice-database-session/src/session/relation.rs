@@ -1,6 +1,9 @@
-use std::{num::NonZeroU32, path::Path, sync::Arc};
+use std::{borrow::Cow, num::NonZeroU32, path::Path, sync::Arc};
 
-use semilattice_database::idx_binary::{AvltrieeSearch, AvltrieeUpdate, IdxBinary};
+use semilattice_database::{
+    idx_binary::{AvltrieeSearch, AvltrieeUpdate, IdxBinary},
+    Cipher,
+};
 
 use crate::IdxFile;
 
@@ -14,6 +17,8 @@ pub struct SessionRelationRows {
 pub struct SessionRelation {
     pub(crate) key_names: IdxBinary,
     pub(crate) rows: SessionRelationRows,
+    /// Encrypts the key names when the database is encrypted.
+    pub(crate) cipher: Option<Cipher>,
 }
 impl SessionRelation {
     pub fn new<P: AsRef<Path>>(session_dir: P, relation_allocation_lot: u32) -> Self {
@@ -42,6 +47,31 @@ impl SessionRelation {
                 session_row: IdxFile::new(path_session_row, relation_allocation_lot),
                 depend: IdxFile::new(path_depend, relation_allocation_lot),
             },
+            cipher: None,
+        }
+    }
+
+    fn stored_key<'a>(&self, key: &'a str) -> Cow<'a, [u8]> {
+        match &self.cipher {
+            Some(cipher) => Cow::Owned(cipher.encrypt_relation_key(key)),
+            None => Cow::Borrowed(key.as_bytes()),
+        }
+    }
+
+    pub(crate) fn key_id(&self, key: &str) -> Option<NonZeroU32> {
+        self.key_names.row(&self.stored_key(key))
+    }
+
+    pub(crate) fn key_name(&self, key_id: u32) -> String {
+        let bytes = self
+            .key_names
+            .value(NonZeroU32::new(key_id).unwrap())
+            .unwrap();
+        match &self.cipher {
+            Some(cipher) => cipher
+                .decrypt_relation_key(bytes)
+                .unwrap_or_else(|| panic!("relation key {} fails authentication", key_id)),
+            None => unsafe { std::str::from_utf8_unchecked(bytes) }.into(),
         }
     }
 
@@ -51,17 +81,19 @@ impl SessionRelation {
         session_row: NonZeroU32,
         depend: &CollectionRow,
     ) {
+        let key = self.stored_key(relation_key).into_owned();
         futures::join!(
             async {
                 self.rows
                     .key
-                    .insert(&self.key_names.row_or_insert(relation_key.as_bytes()).get())
+                    .insert(&self.key_names.row_or_insert(&key).get())
             },
             async { self.rows.session_row.insert(&session_row.get()) },
             async { self.rows.depend.insert(depend) }
         );
     }
 
+    #[allow(clippy::wrong_self_convention)]
     pub(crate) async fn from_session_row(
         &mut self,
         session_row: NonZeroU32,
@@ -79,24 +111,13 @@ impl SessionRelation {
                 self.rows.key.value(session_relation_row).cloned(),
                 self.rows.depend.value(session_relation_row).cloned(),
             ) {
+                let key_name = self.key_name(key);
                 futures::join!(
                     async { self.rows.key.insert(&key) },
                     async { self.rows.session_row.insert(&new_session_row.get()) },
                     async { self.rows.depend.insert(&depend) },
                     async {
-                        ret.push(Depend::new(
-                            Arc::new(
-                                unsafe {
-                                    std::str::from_utf8_unchecked(
-                                        self.key_names
-                                            .value(NonZeroU32::new(key).unwrap())
-                                            .unwrap(),
-                                    )
-                                }
-                                .into(),
-                            ),
-                            depend.clone(),
-                        ));
+                        ret.push(Depend::new(Arc::new(key_name), depend.clone()));
                     }
                 );
             }
@@ -1,13 +1,16 @@
 use std::{
+    borrow::Cow,
     collections::BTreeSet,
     num::{NonZeroI32, NonZeroI64, NonZeroU32},
     sync::Arc,
+    time::{Duration, Instant},
 };
 
 use async_recursion::async_recursion;
 use hashbrown::HashMap;
 use semilattice_database::{
-    idx_binary::AvltrieeSearch, search, Collection, Condition, Depend, FieldName, SearchResult,
+    fulltext::FullTextOption, idx_binary::AvltrieeSearch, search, search::Explain, Collection,
+    Condition, Depend, FieldName, Search, SearchResult,
 };
 
 use crate::{Session, SessionDatabase};
@@ -21,7 +24,30 @@ pub struct SessionSearchResult {
     join: HashMap<Arc<String>, HashMap<NonZeroI64, SessionSearchResult>>,
 }
 
+/// How the temporary rows of a session changed a search result, summed over every result it was applied to.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SessionOverlay {
+    /// Rows after applying the session.
+    pub rows: usize,
+    /// Temporary rows that matched the conditions, new ones and those overwriting a main row.
+    pub matched: usize,
+    /// Main rows hidden because the session deleted them or they no longer match.
+    pub excluded: usize,
+    pub elapsed: Duration,
+    pub join: HashMap<Arc<String>, SessionOverlay>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionExplain {
+    pub explain: Explain,
+    pub overlay: SessionOverlay,
+}
+
 impl SessionSearchResult {
+    pub fn collection_id(&self) -> i32 {
+        self.collection_id
+    }
+
     pub fn rows(&self) -> &BTreeSet<NonZeroI64> {
         &self.rows
     }
@@ -54,7 +80,7 @@ impl Session {
                 search::Term::Future(c) => ent.term_begin >= *c,
             },
             Condition::Field(field_id, cond) => {
-                ent.fields.get(field_id).map_or(false, |f| match cond {
+                ent.fields.get(field_id).is_some_and(|f| match cond {
                     search::Field::Match(v) => f == v,
                     search::Field::Range(min, max) => min <= f && max >= f,
                     search::Field::Min(min) => min <= f,
@@ -79,9 +105,13 @@ impl Session {
                     }
                 })
             }
+            // Temporary rows are matched on whole words, as if the field had no n-gram index.
+            Condition::FullText(field_name, query) => ent.fields.get(field_name).is_some_and(|f| {
+                query.is_match_text(&FullTextOption::default(), &String::from_utf8_lossy(f))
+            }),
             Condition::Narrow(conditions) => {
                 let mut is_match = true;
-                for c in conditions.into_iter() {
+                for c in conditions.iter() {
                     is_match &= Self::temporary_data_match(row, ent, c);
                     if !is_match {
                         break;
@@ -91,7 +121,7 @@ impl Session {
             }
             Condition::Wide(conditions) => {
                 let mut is_match = false;
-                for c in conditions.into_iter() {
+                for c in conditions.iter() {
                     is_match |= Self::temporary_data_match(row, ent, c);
                     if is_match {
                         break;
@@ -102,7 +132,7 @@ impl Session {
             Condition::Depend(key, collection_row) => {
                 let mut is_match = true;
                 for depend in &ent.depends {
-                    is_match = key.as_ref().map_or(true, |key| key == depend.key())
+                    is_match = key.as_ref().is_none_or(|key| key == depend.key())
                         && collection_row == &**depend;
                     if is_match {
                         break;
@@ -115,11 +145,11 @@ impl Session {
     }
 
     fn temprary_data_match_conditions(
-        conditions: &Vec<Condition>,
+        conditions: &[Condition],
         row: NonZeroI64,
         ent: &TemporaryDataEntity,
     ) -> bool {
-        for c in conditions.into_iter() {
+        for c in conditions.iter() {
             if !Self::temporary_data_match(row, ent, c) {
                 return false;
             }
@@ -131,12 +161,14 @@ impl Session {
     async fn join(
         &self,
         join_result: &HashMap<Arc<String>, HashMap<NonZeroU32, SearchResult>>,
+        overlay: &mut HashMap<Arc<String>, SessionOverlay>,
     ) -> HashMap<Arc<String>, HashMap<NonZeroI64, SessionSearchResult>> {
         let mut map = HashMap::new();
         for (name, row_join) in join_result {
+            let overlay = overlay.entry(Arc::clone(name)).or_default();
             let mut map_inner = HashMap::new();
             for (row, result) in row_join {
-                let result = self.result_with(result).await;
+                let result = self.result_with_overlay(result, overlay).await;
                 map_inner.insert((*row).into(), result);
             }
             map.insert(name.to_owned(), map_inner);
@@ -145,23 +177,49 @@ impl Session {
     }
 
     pub async fn result_with(&self, search_result: &SearchResult) -> SessionSearchResult {
+        self.result_with_overlay(search_result, &mut SessionOverlay::default())
+            .await
+    }
+
+    /// Runs the search on the main database and reports how the session's temporary rows changed its result.
+    pub async fn explain(
+        &self,
+        database: &SessionDatabase,
+        search: Search,
+    ) -> (SessionSearchResult, SessionExplain) {
+        let (search_result, explain) = search.result_explain(database).await;
+        let mut overlay = SessionOverlay::default();
+        let result = self.result_with_overlay(&search_result, &mut overlay).await;
+        (result, SessionExplain { explain, overlay })
+    }
+
+    #[async_recursion(?Send)]
+    async fn result_with_overlay(
+        &self,
+        search_result: &SearchResult,
+        overlay: &mut SessionOverlay,
+    ) -> SessionSearchResult {
+        let started = Instant::now();
         let (collection_id, rows) = if let Some(search) = search_result.search() {
             let collection_id = search.collection_id();
             (
                 collection_id.get(),
                 if let Some(tmp) = self.temporary_data.get(&collection_id) {
                     let mut rows: BTreeSet<NonZeroI64> = BTreeSet::new();
-                    for row in search_result.rows().into_iter() {
+                    for row in search_result.rows().iter() {
                         let row = NonZeroI64::from(*row);
                         if let Some(ent) = tmp.get(&row) {
-                            if ent.operation != SessionOperation::Delete {
-                                if Self::temprary_data_match_conditions(
+                            if ent.operation != SessionOperation::Delete
+                                && Self::temprary_data_match_conditions(
                                     search.conditions(),
                                     row,
                                     ent,
-                                ) {
-                                    rows.insert(row);
-                                }
+                                )
+                            {
+                                rows.insert(row);
+                                overlay.matched += 1;
+                            } else {
+                                overlay.excluded += 1;
                             }
                         } else {
                             rows.insert(row);
@@ -170,14 +228,15 @@ impl Session {
                     for (row, _) in tmp.into_iter() {
                         if row.get() < 0 {
                             if let Some(ent) = tmp.get(row) {
-                                if ent.operation != SessionOperation::Delete {
-                                    if Self::temprary_data_match_conditions(
+                                if ent.operation != SessionOperation::Delete
+                                    && Self::temprary_data_match_conditions(
                                         search.conditions(),
                                         *row,
                                         ent,
-                                    ) {
-                                        rows.insert(*row);
-                                    }
+                                    )
+                                {
+                                    rows.insert(*row);
+                                    overlay.matched += 1;
                                 }
                             }
                         }
@@ -186,7 +245,7 @@ impl Session {
                 } else {
                     search_result
                         .rows()
-                        .into_iter()
+                        .iter()
                         .map(|x| NonZeroI64::from(*x))
                         .collect()
                 },
@@ -194,7 +253,9 @@ impl Session {
         } else {
             (0, BTreeSet::new())
         };
-        let join = self.join(search_result.join()).await;
+        overlay.rows += rows.len();
+        overlay.elapsed += started.elapsed();
+        let join = self.join(search_result.join(), &mut overlay.join).await;
 
         SessionSearchResult {
             collection_id,
@@ -203,45 +264,44 @@ impl Session {
         }
     }
 
+    /// Values of committed rows are decrypted, see [Collection::decrypted_bytes].
     pub fn field_bytes<'a>(
         &'a self,
         database: &'a SessionDatabase,
         collection_id: NonZeroI32,
         row: NonZeroI64,
         field_name: &FieldName,
-    ) -> &[u8] {
-        if let Some(temporary_collection) = self.temporary_data.get(&collection_id) {
-            if let Some(tmp_row) = temporary_collection.get(&row) {
-                if let Some(val) = tmp_row.fields.get(field_name) {
-                    return val;
-                }
-            }
+    ) -> Cow<'a, [u8]> {
+        match database.collection(collection_id) {
+            Some(collection) => self.collection_field_bytes(collection, row, field_name),
+            None => Cow::Borrowed(
+                self.temporary_data
+                    .get(&collection_id)
+                    .and_then(|temporary_collection| temporary_collection.get(&row))
+                    .and_then(|tmp_row| tmp_row.fields.get(field_name))
+                    .map_or(b"", |val| val),
+            ),
         }
-        if row.get() > 0 {
-            if let Some(collection) = database.collection(collection_id) {
-                return collection.field_bytes(row.try_into().unwrap(), field_name);
-            }
-        }
-        b""
     }
 
+    /// Values of committed rows are decrypted, see [Collection::decrypted_bytes].
     pub fn collection_field_bytes<'a>(
         &'a self,
         collection: &'a Collection,
         row: NonZeroI64,
         field_name: &FieldName,
-    ) -> &[u8] {
+    ) -> Cow<'a, [u8]> {
         if let Some(temprary_collection) = self.temporary_data.get(&collection.id()) {
             if let Some(temprary_row) = temprary_collection.get(&row) {
                 if let Some(val) = temprary_row.fields.get(field_name) {
-                    return val;
+                    return Cow::Borrowed(val);
                 }
             }
         }
         if row.get() > 0 {
-            collection.field_bytes(row.try_into().unwrap(), field_name)
+            collection.decrypted_bytes(row.try_into().unwrap(), field_name)
         } else {
-            b""
+            Cow::Borrowed(b"")
         }
     }
 
@@ -268,18 +328,7 @@ impl Session {
                                     session_data.relation.rows.depend.value(relation_row),
                                 ) {
                                     return Some(Depend::new(
-                                        Arc::new(
-                                            unsafe {
-                                                std::str::from_utf8_unchecked(
-                                                    session_data
-                                                        .relation
-                                                        .key_names
-                                                        .value(NonZeroU32::new(*key).unwrap())
-                                                        .unwrap(),
-                                                )
-                                            }
-                                            .into(),
-                                        ),
+                                        Arc::new(session_data.relation.key_name(*key)),
                                         depend.clone(),
                                     ));
                                 }
@@ -289,32 +338,28 @@ impl Session {
                     )
                 },
                 |key_name| {
-                    session_data
-                        .relation
-                        .key_names
-                        .row(key_name.as_bytes())
-                        .map(|key_id| {
-                            session_data
-                                .relation
-                                .rows
-                                .session_row
-                                .iter_by(&pend_row.get())
-                                .filter_map(|relation_row| {
-                                    if let (Some(key), Some(depend)) = (
-                                        session_data.relation.rows.key.value(relation_row),
-                                        session_data.relation.rows.depend.value(relation_row),
-                                    ) {
-                                        if *key == key_id.get() {
-                                            return Some(Depend::new(
-                                                Arc::clone(&key_name),
-                                                depend.clone(),
-                                            ));
-                                        }
+                    session_data.relation.key_id(&key_name).map(|key_id| {
+                        session_data
+                            .relation
+                            .rows
+                            .session_row
+                            .iter_by(&pend_row.get())
+                            .filter_map(|relation_row| {
+                                if let (Some(key), Some(depend)) = (
+                                    session_data.relation.rows.key.value(relation_row),
+                                    session_data.relation.rows.depend.value(relation_row),
+                                ) {
+                                    if *key == key_id.get() {
+                                        return Some(Depend::new(
+                                            Arc::clone(&key_name),
+                                            depend.clone(),
+                                        ));
                                     }
-                                    None
-                                })
-                                .collect()
-                        })
+                                }
+                                None
+                            })
+                            .collect()
+                    })
                 },
             )
         })
@@ -1,4 +1,4 @@
-use std::{cmp::Ordering, fmt::Debug, num::NonZeroI64};
+use std::{borrow::Cow, cmp::Ordering, fmt::Debug, num::NonZeroI64};
 
 use hashbrown::HashMap;
 use semilattice_database::{
@@ -43,14 +43,14 @@ impl Session {
         &self,
         collection: &Collection,
         mut rows: Vec<NonZeroI64>,
-        orders: &Vec<SessionOrder<C>>,
+        orders: &[SessionOrder<C>],
     ) -> Vec<NonZeroI64> {
-        if orders.len() > 0 {
+        if !orders.is_empty() {
             let collection_id = collection.id();
             if let Some(tmp) = self.temporary_data.get(&collection_id) {
                 rows.sort_by(|a, b| {
-                    for i in 0..orders.len() {
-                        match &orders[i] {
+                    for order in orders.iter() {
+                        match order {
                             SessionOrder::Asc(order_key) => match order_key {
                                 SessionOrderKey::Serial => {
                                     let (a, b) = serial(collection, *a, *b);
@@ -83,8 +83,8 @@ impl Session {
                                 }
                                 SessionOrderKey::Field(field_name) => {
                                     let ord = IdxBinary::cmp(
-                                        field(tmp, collection, *a, field_name),
-                                        field(tmp, collection, *b, field_name),
+                                        &field(tmp, collection, *a, field_name),
+                                        &field(tmp, collection, *b, field_name),
                                     );
                                     if ord != Ordering::Equal {
                                         return ord;
@@ -133,8 +133,8 @@ impl Session {
                                 }
                                 SessionOrderKey::Field(field_name) => {
                                     let ord = IdxBinary::cmp(
-                                        field(tmp, collection, *b, field_name),
-                                        field(tmp, collection, *a, field_name),
+                                        &field(tmp, collection, *b, field_name),
+                                        &field(tmp, collection, *a, field_name),
                                     );
                                     if ord != Ordering::Equal {
                                         return ord;
@@ -232,15 +232,17 @@ fn field<'a>(
     collection: &'a Collection,
     row: NonZeroI64,
     field_name: &FieldName,
-) -> &'a [u8] {
+) -> Cow<'a, [u8]> {
     if row.get() < 0 {
-        temporary_collection
-            .get(&row)
-            .unwrap()
-            .fields()
-            .get(field_name)
-            .map_or(b"", |v| v)
+        Cow::Borrowed(
+            temporary_collection
+                .get(&row)
+                .unwrap()
+                .fields()
+                .get(field_name)
+                .map_or(b"", |v| v),
+        )
     } else {
-        collection.field_bytes(row.try_into().unwrap(), field_name)
+        collection.decrypted_bytes(row.try_into().unwrap(), field_name)
     }
 }
@@ -7,7 +7,7 @@ use std::{
 
 use async_recursion::async_recursion;
 use hashbrown::HashMap;
-use semilattice_database::idx_binary::{AvltrieeSearch, AvltrieeUpdate};
+use semilattice_database::{idx_binary::AvltrieeUpdate, ValidationError};
 
 use crate::{
     session::{Depends, SessionData, SessionOperation, TemporaryData, TemporaryDataEntity},
@@ -15,6 +15,33 @@ use crate::{
 };
 
 impl SessionDatabase {
+    /// Checks the fields of the records and their pends against the schemas of their collections.
+    pub fn validate(&self, records: &[SessionRecord]) -> Result<(), ValidationError> {
+        for record in records.iter() {
+            if let SessionRecord::Update {
+                collection_id,
+                row,
+                fields,
+                pends,
+                ..
+            } = record
+            {
+                let collection_id = if collection_id.get() < 0 {
+                    -*collection_id
+                } else {
+                    *collection_id
+                };
+                if let Some(collection) = self.collection(collection_id) {
+                    collection.validate(fields, row.is_none())?;
+                }
+                for pend in pends.iter() {
+                    self.validate(&pend.records)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
     #[async_recursion(?Send)]
     pub(super) async fn update_recursive(
         &self,
@@ -22,11 +49,11 @@ impl SessionDatabase {
         temporary_data: &mut TemporaryData,
         session_dir: &Path,
         sequence_number: &usize,
-        records: &Vec<SessionRecord>,
+        records: &[SessionRecord],
         depend_by_pend: Option<(&'async_recursion str, NonZeroU32)>,
     ) -> Vec<CollectionRow> {
         let mut ret = vec![];
-        for record in records.into_iter() {
+        for record in records.iter() {
             let session_row = session_data.sequence.insert(sequence_number);
 
             match record {
@@ -79,10 +106,11 @@ impl SessionDatabase {
 
                         let uuid = {
                             if in_session {
-                                session_data.uuid.value(*row).map_or_else(
-                                    || semilattice_database::create_uuid(),
-                                    |uuid| *uuid,
-                                )
+                                session_data
+                                    .row_head(*row)
+                                    .map_or_else(semilattice_database::create_uuid, |head| {
+                                        head.uuid
+                                    })
                             } else {
                                 if let Some(collection) = self.collection(master_collection_id) {
                                     let uuid = *collection.uuid(*row).unwrap_or(&0);
@@ -133,8 +161,7 @@ impl SessionDatabase {
                                 } else {
                                     for i in self
                                         .relation()
-                                        .index_pend()
-                                        .iter_by(&CollectionRow::new(collection_id, *row))
+                                        .rows_by_pend(&CollectionRow::new(collection_id, *row))
                                     {
                                         if let Some(depend) = self.relation().depend(i) {
                                             let key = self.relation().key(i).to_owned();
@@ -149,7 +176,7 @@ impl SessionDatabase {
                                 }
                             }
                             Depends::Overwrite(depends) => {
-                                for (key, depend) in depends.into_iter() {
+                                for (key, depend) in depends.iter() {
                                     session_data.relation.insert(key, session_row, depend).await;
                                     tmp_depends.push(Depend::new(Arc::clone(key), depend.clone()));
                                 }
@@ -181,7 +208,7 @@ impl SessionDatabase {
                                 .incidentally_depend(session_row, key, depend_session_row)
                                 .await;
                         }
-                        for pend in pends.into_iter() {
+                        for pend in pends.iter() {
                             self.update_recursive(
                                 session_data,
                                 temporary_data,
@@ -252,7 +279,7 @@ impl SessionDatabase {
                                     .collect(),
                                 depends: if let Depends::Overwrite(depends) = depends {
                                     let mut tmp = vec![];
-                                    for (key, depend) in depends.into_iter() {
+                                    for (key, depend) in depends.iter() {
                                         session_data
                                             .relation
                                             .insert(key, session_row, depend)
@@ -271,7 +298,7 @@ impl SessionDatabase {
                                 .incidentally_depend(session_row, key, depend_session_row)
                                 .await;
                         }
-                        for pend in pends.into_iter() {
+                        for pend in pends.iter() {
                             self.update_recursive(
                                 session_data,
                                 temporary_data,
@@ -0,0 +1,38 @@
+#[cfg(test)]
+#[test]
+fn commit_as() {
+    use semilattice_database_session::{
+        audit::{AuditChange, AuditQuery},
+        *,
+    };
+
+    let mut database = SessionDatabase::temporary(None, 10);
+    database.enable_audit().unwrap();
+    let collection_person = database.collection_id_or_create("person").unwrap();
+    let field_name = FieldName::new("name".into());
+
+    futures::executor::block_on(async {
+        let mut sess = database.session("edit", None).unwrap();
+        database
+            .update(
+                &mut sess,
+                vec![SessionRecord::update(collection_person, None)
+                    .field_bytes(field_name.clone(), "Joe".into())],
+            )
+            .await
+            .unwrap();
+        let rows = database.commit_as(&mut sess, "alice").await.unwrap();
+
+        let entries = database.audit(&AuditQuery::new().actor("alice"));
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].session.as_deref(), Some("edit"));
+        assert_eq!(
+            entries[0].change,
+            AuditChange::Insert {
+                target: rows[0].clone(),
+                after: [(field_name.clone(), b"Joe".to_vec())].into(),
+            }
+        );
+        assert_eq!(database.audit_context(), None);
+    });
+}
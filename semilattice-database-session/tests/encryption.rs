@@ -0,0 +1,125 @@
+#[cfg(test)]
+#[test]
+fn session_encryption() {
+    use std::sync::Arc;
+
+    use semilattice_database_session::*;
+
+    let dir = "./sl-test-session-encryption/";
+
+    if std::path::Path::new(dir).exists() {
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    let key = EncryptionKey::new([7; 32]);
+    let field_name = FieldName::new("name".into());
+    let owner = Arc::new("owner-secret".to_owned());
+
+    fn contains_secret(dir: &std::path::Path) -> bool {
+        std::fs::read_dir(dir).unwrap().any(|entry| {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                contains_secret(&path)
+            } else {
+                std::fs::read(path)
+                    .unwrap()
+                    .windows(6)
+                    .any(|window| window == b"secret")
+            }
+        })
+    }
+
+    futures::executor::block_on(async {
+        let mut database = SessionDatabase::open_encrypted(dir.into(), None, 10, &key).unwrap();
+        let collection_person = database.collection_id_or_create("person").unwrap();
+
+        let mut sess = database.session("base", None).unwrap();
+        database
+            .update(
+                &mut sess,
+                vec![SessionRecord::update(collection_person, None)
+                    .field_bytes(field_name.clone(), "Ann".into())],
+            )
+            .await
+            .unwrap();
+        let ann = database.commit(&mut sess).await.unwrap()[0].clone();
+
+        let mut sess = database.session("encrypted", None).unwrap();
+        database
+            .update(
+                &mut sess,
+                vec![SessionRecord::Update {
+                    collection_id: collection_person,
+                    row: None,
+                    activity: Activity::Active,
+                    term_begin: Term::Overwrite(1000),
+                    term_end: Default::default(),
+                    fields: [(field_name.clone(), b"Joe-secret".to_vec())].into(),
+                    depends: Depends::Overwrite(vec![(Arc::clone(&owner), ann.clone())]),
+                    pends: vec![],
+                }],
+            )
+            .await
+            .unwrap();
+        assert!(!contains_secret(std::path::Path::new(dir)));
+
+        let sess = database.session("encrypted", None).unwrap();
+        let result = sess
+            .result_with(
+                &database
+                    .search(collection_person)
+                    .search_field(
+                        field_name.clone(),
+                        search::Field::Match(b"Joe-secret".to_vec()),
+                    )
+                    .result(&database)
+                    .await,
+            )
+            .await;
+        let row = *result.rows().iter().next().unwrap();
+        assert_eq!(
+            sess.field_bytes(&database, collection_person, row, &field_name),
+            &b"Joe-secret"[..]
+        );
+        assert_eq!(
+            database.depends_with_session(
+                Some(Arc::clone(&owner)),
+                collection_person,
+                row,
+                Some(&sess)
+            ),
+            vec![Depend::new(Arc::clone(&owner), ann.clone())]
+        );
+        drop(sess);
+        drop(database);
+
+        let database = SessionDatabase::open_read_only_encrypted(dir.into(), None, &key).unwrap();
+        let sess = database.inspect_session("encrypted").unwrap();
+        assert_eq!(
+            database.depends_with_session(
+                Some(Arc::clone(&owner)),
+                collection_person,
+                row,
+                Some(&sess)
+            ),
+            vec![Depend::new(Arc::clone(&owner), ann.clone())]
+        );
+        let joe = &sess.temporary_collection(collection_person).unwrap()[&row];
+        assert_eq!(joe.term_begin(), 1000);
+        assert_eq!(joe.activity(), Activity::Active);
+        drop(sess);
+        drop(database);
+
+        let mut database = SessionDatabase::open_encrypted(dir.into(), None, 10, &key).unwrap();
+        let mut sess = database.session("encrypted", None).unwrap();
+        let rows = database.commit(&mut sess).await.unwrap();
+        let joe = rows[0].row().into();
+        let sess = database.session("after", None).unwrap();
+        assert_eq!(
+            sess.field_bytes(&database, collection_person, joe, &field_name),
+            &b"Joe-secret"[..]
+        );
+        assert_eq!(database.relation().rows_by_key("owner-secret").len(), 1);
+        assert!(!contains_secret(std::path::Path::new(dir)));
+    });
+}
@@ -0,0 +1,65 @@
+#[cfg(test)]
+#[test]
+fn read_only() {
+    use semilattice_database_session::*;
+
+    let dir = "./sl-test-session-read_only/";
+
+    if std::path::Path::new(dir).exists() {
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+    std::fs::create_dir_all(dir).unwrap();
+
+    let field_name = FieldName::new("name".into());
+
+    futures::executor::block_on(async {
+        let mut database = SessionDatabase::new(dir.into(), None, 10);
+        let collection_person = database.collection_id_or_create("person").unwrap();
+        let mut sess = database.session("draft", None).unwrap();
+        database
+            .update(
+                &mut sess,
+                vec![SessionRecord::update(collection_person, None)
+                    .field_bytes(field_name.clone(), "Joe".into())],
+            )
+            .await
+            .unwrap();
+        drop(sess);
+        drop(database);
+
+        let mut database = SessionDatabase::open_read_only(dir.into(), None).unwrap();
+        assert!(matches!(
+            database.session("draft", None),
+            Err(WriteError::ReadOnly)
+        ));
+        assert_eq!(database.session_gc(0), Err(WriteError::ReadOnly));
+        let mut sess = database.inspect_session("draft").unwrap();
+        assert_eq!(
+            database
+                .update(
+                    &mut sess,
+                    vec![SessionRecord::update(collection_person, None)
+                        .field_bytes(field_name.clone(), "Ann".into())],
+                )
+                .await,
+            Err(WriteError::ReadOnly)
+        );
+        assert_eq!(database.commit(&mut sess).await, Err(WriteError::ReadOnly));
+        assert_eq!(database.session_clear(&mut sess), Err(WriteError::ReadOnly));
+        assert_eq!(database.sessions().len(), 1);
+        #[cfg(target_os = "linux")]
+        {
+            let sessions_dir = std::fs::canonicalize(format!("{dir}sessions/draft")).unwrap();
+            let maps = std::fs::read_to_string("/proc/self/maps").unwrap();
+            let permissions: Vec<_> = maps
+                .lines()
+                .filter(|line| line.contains(sessions_dir.to_str().unwrap()))
+                .map(|line| line.split_whitespace().nth(1).unwrap())
+                .collect();
+            assert!(!permissions.is_empty());
+            assert!(permissions
+                .iter()
+                .all(|permissions| !permissions.contains('w')));
+        }
+    });
+}
@@ -0,0 +1,150 @@
+#[cfg(test)]
+#[test]
+fn schema() {
+    use semilattice_database_session::*;
+
+    let mut database = SessionDatabase::temporary(None, 10);
+    let collection_person = database.collection_id_or_create("person").unwrap();
+    let collection_history = database.collection_id_or_create("history").unwrap();
+    let field_name = FieldName::new("name".into());
+    database
+        .set_schema(
+            collection_history,
+            Some(Schema::new().field(
+                field_name.clone(),
+                FieldSchema::new(FieldType::Utf8).required(),
+            )),
+        )
+        .unwrap();
+
+    futures::executor::block_on(async {
+        let mut sess = database.session("schema", None).unwrap();
+        let error = database
+            .update(
+                &mut sess,
+                vec![SessionRecord::update(collection_person, None)
+                    .field_bytes(field_name.clone(), "Joe".into())
+                    .pend(Pend {
+                        key: "history".to_owned().into(),
+                        records: vec![SessionRecord::update(collection_history, None)],
+                    })],
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error,
+            WriteError::Validation(ValidationError {
+                collection_id: collection_history,
+                errors: vec![FieldError::Missing(field_name.clone())],
+            })
+        );
+        assert!(database.commit(&mut sess).await.unwrap().is_empty());
+
+        // A row written before the schema required a field is rejected at commit.
+        database
+            .update(
+                &mut sess,
+                vec![SessionRecord::update(collection_person, None).pend(Pend {
+                    key: "history".to_owned().into(),
+                    records: vec![SessionRecord::update(collection_history, None)
+                        .field_bytes(field_name.clone(), "born".into())],
+                })],
+            )
+            .await
+            .unwrap();
+        let field_date = FieldName::new("date".into());
+        database
+            .set_schema(
+                collection_history,
+                Some(
+                    Schema::new()
+                        .field(field_name.clone(), FieldSchema::new(FieldType::Utf8))
+                        .field(
+                            field_date.clone(),
+                            FieldSchema::new(FieldType::Utf8).required(),
+                        ),
+                ),
+            )
+            .unwrap();
+        assert_eq!(
+            database.commit(&mut sess).await,
+            Err(WriteError::Validation(ValidationError {
+                collection_id: collection_history,
+                errors: vec![FieldError::Missing(field_date)],
+            }))
+        );
+        assert!(database
+            .collection(collection_history)
+            .unwrap()
+            .data()
+            .all()
+            .is_empty());
+    });
+}
+
+#[cfg(test)]
+#[test]
+fn unique() {
+    use semilattice_database_session::*;
+
+    let mut database = SessionDatabase::temporary(None, 10);
+    let collection_id = database.collection_id_or_create("account").unwrap();
+    let field_login = FieldName::new("login".into());
+    database
+        .set_schema(
+            collection_id,
+            Some(
+                Schema::new()
+                    .field(field_login.clone(), FieldSchema::new(FieldType::Utf8))
+                    .unique(vec![field_login.clone()]),
+            ),
+        )
+        .unwrap();
+    let account = |row: Option<u32>, login: &str| {
+        SessionRecord::update(collection_id, row.and_then(std::num::NonZeroU32::new))
+            .field_bytes(field_login.clone(), login.into())
+    };
+
+    futures::executor::block_on(async {
+        let mut sess = database.session("unique", None).unwrap();
+        database
+            .update(&mut sess, vec![account(None, "joe")])
+            .await
+            .unwrap();
+        database.commit(&mut sess).await.unwrap();
+
+        let mut sess = database.session("unique", None).unwrap();
+        let rows = database
+            .update(&mut sess, vec![account(None, "ann"), account(None, "ann")])
+            .await
+            .unwrap();
+        assert_eq!(
+            database.commit(&mut sess).await,
+            Err(WriteError::Conflict(UniqueConflict {
+                collection_id,
+                field_names: vec![field_login.clone()],
+                existing: rows[0].clone(),
+            }))
+        );
+
+        let mut sess = database.session("unique2", None).unwrap();
+        database
+            .update(&mut sess, vec![account(None, "joe")])
+            .await
+            .unwrap();
+        assert_eq!(
+            database.commit(&mut sess).await.map_err(|e| match e {
+                WriteError::Conflict(conflict) => conflict.existing,
+                e => panic!("{e}"),
+            }),
+            Err(CollectionRow::new(collection_id, 1.try_into().unwrap()))
+        );
+
+        // Renaming the existing row in the same session frees its login.
+        database
+            .update(&mut sess, vec![account(Some(1), "joseph")])
+            .await
+            .unwrap();
+        assert_eq!(database.commit(&mut sess).await.unwrap().len(), 2);
+    });
+}
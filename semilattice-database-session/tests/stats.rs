@@ -0,0 +1,29 @@
+#[cfg(test)]
+#[test]
+fn session_stats() {
+    use semilattice_database_session::*;
+
+    let mut database = SessionDatabase::temporary(None, 10);
+    let collection_person = database.collection_id_or_create("person").unwrap();
+    let field_name = FieldName::new("name".into());
+
+    futures::executor::block_on(async {
+        let mut sess = database.session("stats", None).unwrap();
+        for name in ["Joe", "Ann"] {
+            database
+                .update(
+                    &mut sess,
+                    vec![SessionRecord::update(collection_person, None)
+                        .field_bytes(field_name.clone(), name.into())],
+                )
+                .await
+                .unwrap();
+        }
+        sess.set_sequence_cursor(1);
+
+        let stats = database.session_stats("stats").unwrap();
+        assert_eq!((stats.sequences, stats.current, stats.rows), (2, 1, 2));
+        assert!(stats.bytes > 0);
+        assert!(database.session_stats("none").is_none());
+    });
+}
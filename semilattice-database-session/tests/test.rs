@@ -7,21 +7,14 @@ fn test() {
     use semilattice_database::FieldName;
     use semilattice_database_session::*;
 
-    let dir = "./sl-test/";
+    let mut database = SessionDatabase::temporary(None, 10);
 
-    if std::path::Path::new(dir).exists() {
-        std::fs::remove_dir_all(dir).unwrap();
-    }
-    std::fs::create_dir_all(dir).unwrap();
-
-    let mut database = SessionDatabase::new(dir.into(), None, 10);
-
-    let collection_admin = database.collection_id_or_create("admin");
+    let collection_admin = database.collection_id_or_create("admin").unwrap();
 
     let field_id = FieldName::new("id".into());
     let field_password = FieldName::new("password".into());
 
-    let mut sess = database.session("creatre_account_1st", None);
+    let mut sess = database.session("creatre_account_1st", None).unwrap();
     futures::executor::block_on(async {
         database
             .update(
@@ -41,11 +34,12 @@ fn test() {
                     pends: vec![],
                 }],
             )
-            .await;
-        database.commit(&mut sess).await;
+            .await
+            .unwrap();
+        database.commit(&mut sess).await.unwrap();
 
-        let collection_login = database.collection_id_or_create("login");
-        let mut sess = database.session("login", None);
+        let collection_login = database.collection_id_or_create("login").unwrap();
+        let mut sess = database.session("login", None).unwrap();
 
         let search = database
             .search(collection_admin)
@@ -77,7 +71,8 @@ fn test() {
                         pends: vec![],
                     }],
                 )
-                .await;
+                .await
+                .unwrap();
         }
         let search = database.search(collection_login);
         for row in sess
@@ -89,7 +84,7 @@ fn test() {
             let depends = database.depends_with_session(
                 Some(Arc::new("admin".into())),
                 collection_login,
-                (*row).try_into().unwrap(),
+                *row,
                 Some(&sess),
             );
             for d in depends {
@@ -115,8 +110,8 @@ fn test() {
             }
         }
 
-        let collection_person = database.collection_id_or_create("person");
-        let collection_history = database.collection_id_or_create("history");
+        let collection_person = database.collection_id_or_create("person").unwrap();
+        let collection_history = database.collection_id_or_create("history").unwrap();
 
         let field_name = FieldName::new("name".into());
         let field_birthday = FieldName::new("birthday".into());
@@ -124,7 +119,7 @@ fn test() {
         let field_date = FieldName::new("date".into());
         let field_event = FieldName::new("event".into());
 
-        let mut sess = database.session("test", None);
+        let mut sess = database.session("test", None).unwrap();
         database
             .update(
                 &mut sess,
@@ -221,8 +216,9 @@ fn test() {
                     },
                 ],
             )
-            .await;
-        database.commit(&mut sess).await;
+            .await
+            .unwrap();
+        database.commit(&mut sess).await.unwrap();
 
         if let (Some(person), Some(history)) = (
             database.collection(collection_person),
@@ -234,7 +230,7 @@ fn test() {
                 .await
                 .sort(
                     &database,
-                    &vec![Order::Asc(OrderKey::Field(field_birthday.clone()))],
+                    &[Order::Asc(OrderKey::Field(field_birthday.clone()))],
                 );
             for i in person_rows {
                 println!(
@@ -260,7 +256,7 @@ fn test() {
                 }
             }
         }
-        let mut sess = database.session("test", None);
+        let mut sess = database.session("test", None).unwrap();
         database
             .update(
                 &mut sess,
@@ -275,9 +271,10 @@ fn test() {
                     pends: vec![],
                 }],
             )
-            .await;
+            .await
+            .unwrap();
 
-        let mut sess = database.session("test", None);
+        let mut sess = database.session("test", None).unwrap();
         let search = database
             .search(collection_person)
             .search_activity(Activity::Active);
@@ -288,14 +285,14 @@ fn test() {
         {
             println!(
                 "session_search : {},{}",
-                std::str::from_utf8(sess.field_bytes(
+                std::str::from_utf8(&sess.field_bytes(
                     &database,
                     collection_person,
                     *r,
                     &field_name
                 ))
                 .unwrap(),
-                std::str::from_utf8(sess.field_bytes(
+                std::str::from_utf8(&sess.field_bytes(
                     &database,
                     collection_person,
                     *r,
@@ -304,15 +301,15 @@ fn test() {
                 .unwrap()
             );
         }
-        database.commit(&mut sess).await;
+        database.commit(&mut sess).await.unwrap();
 
-        let test1 = database.collection_id_or_create("test1");
+        let test1 = database.collection_id_or_create("test1").unwrap();
 
         let field_num = FieldName::new("num".into());
         let field_num_by3 = FieldName::new("num_by3".into());
 
         let range = 1u32..=10;
-        let mut sess = database.session("test", None);
+        let mut sess = database.session("test", None).unwrap();
         for i in range.clone() {
             database
                 .update(
@@ -332,11 +329,12 @@ fn test() {
                         pends: vec![],
                     }],
                 )
-                .await;
+                .await
+                .unwrap();
         }
-        database.commit(&mut sess).await;
+        database.commit(&mut sess).await.unwrap();
 
-        let mut sess = database.session("test", None);
+        let mut sess = database.session("test", None).unwrap();
         database
             .update(
                 &mut sess,
@@ -351,8 +349,9 @@ fn test() {
                     pends: vec![],
                 }],
             )
-            .await;
-        database.commit(&mut sess).await;
+            .await
+            .unwrap();
+        database.commit(&mut sess).await.unwrap();
 
         if let Some(t1) = database.collection(test1) {
             let mut sum = 0.0;
@@ -4,14 +4,8 @@ fn test2() {
     use semilattice_database::FieldName;
     use semilattice_database_session::*;
 
-    let dir = "./sl-test2/";
-
-    if std::path::Path::new(dir).exists() {
-        std::fs::remove_dir_all(dir).unwrap();
-    }
-    std::fs::create_dir_all(dir).unwrap();
-
     futures::executor::block_on(async {
+        let mut database = SessionDatabase::temporary(None, 10);
         let field_name = FieldName::new("name".into());
         let field_text = FieldName::new("text".into());
         let field_image_type = FieldName::new("image_type".into());
@@ -19,10 +13,9 @@ fn test2() {
         let field_image_data = FieldName::new("image_data".into());
 
         {
-            let mut database = SessionDatabase::new(dir.into(), None, 10);
-            let collection_bbs = database.collection_id_or_create("bbs");
+            let collection_bbs = database.collection_id_or_create("bbs").unwrap();
 
-            let mut sess = database.session("bbs", None);
+            let mut sess = database.session("bbs", None).unwrap();
             database
                 .update(
                     &mut sess,
@@ -44,13 +37,13 @@ fn test2() {
                         pends: vec![],
                     }],
                 )
-                .await;
-            database.commit(&mut sess).await;
+                .await
+                .unwrap();
+            database.commit(&mut sess).await.unwrap();
         }
         {
-            let mut database = SessionDatabase::new(dir.into(), None, 10);
-            let collection_bbs = database.collection_id_or_create("bbs");
-            let mut sess = database.session("bbs", None);
+            let collection_bbs = database.collection_id_or_create("bbs").unwrap();
+            let mut sess = database.session("bbs", None).unwrap();
             database
                 .update(
                     &mut sess,
@@ -59,16 +52,16 @@ fn test2() {
                         row: 1.try_into().unwrap(),
                     }],
                 )
-                .await;
-            database.commit(&mut sess).await;
+                .await
+                .unwrap();
+            database.commit(&mut sess).await.unwrap();
 
             println!("OK1");
         }
         {
-            let mut database = SessionDatabase::new(dir.into(), None, 10);
-            let collection_bbs = database.collection_id_or_create("bbs");
+            let collection_bbs = database.collection_id_or_create("bbs").unwrap();
 
-            let mut sess = database.session("bbs", None);
+            let mut sess = database.session("bbs", None).unwrap();
             database
                 .update(
                     &mut sess,
@@ -90,8 +83,9 @@ fn test2() {
                         pends: vec![],
                     }],
                 )
-                .await;
-            database.commit(&mut sess).await;
+                .await
+                .unwrap();
+            database.commit(&mut sess).await.unwrap();
 
             println!("OK2");
         }
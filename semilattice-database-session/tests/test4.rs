@@ -6,21 +6,14 @@ fn test4() {
     use semilattice_database::FieldName;
     use semilattice_database_session::*;
 
-    let dir = "./sl-test4/";
-
-    if std::path::Path::new(dir).exists() {
-        std::fs::remove_dir_all(dir).unwrap();
-    }
-    std::fs::create_dir_all(dir).unwrap();
-
-    let mut database = SessionDatabase::new(dir.into(), None, 10);
-    let collection_widget = database.collection_id_or_create("widget");
+    let mut database = SessionDatabase::temporary(None, 10);
+    let collection_widget = database.collection_id_or_create("widget").unwrap();
     let field_name = FieldName::new("name".into());
 
-    let collection_field = database.collection_id_or_create("field");
+    let collection_field = database.collection_id_or_create("field").unwrap();
     futures::executor::block_on(async {
         {
-            let mut sess = database.session("widget", None);
+            let mut sess = database.session("widget", None).unwrap();
             database
                 .update(
                     &mut sess,
@@ -35,9 +28,10 @@ fn test4() {
                         pends: vec![],
                     }],
                 )
-                .await;
+                .await
+                .unwrap();
         }
-        let mut sess = database.session("widget", None);
+        let mut sess = database.session("widget", None).unwrap();
         database
             .update(
                 &mut sess,
@@ -55,7 +49,8 @@ fn test4() {
                     pends: vec![],
                 }],
             )
-            .await;
+            .await
+            .unwrap();
         database
             .update(
                 &mut sess,
@@ -73,7 +68,8 @@ fn test4() {
                     pends: vec![],
                 }],
             )
-            .await;
+            .await
+            .unwrap();
         database
             .update(
                 &mut sess,
@@ -91,10 +87,11 @@ fn test4() {
                     pends: vec![],
                 }],
             )
-            .await;
+            .await
+            .unwrap();
         sess.set_sequence_cursor(3);
 
-        //let mut sess = database.session("widget", None);
+        //let mut sess = database.session("widget", None).unwrap();
         database
             .update(
                 &mut sess,
@@ -112,9 +109,10 @@ fn test4() {
                     pends: vec![],
                 }],
             )
-            .await;
+            .await
+            .unwrap();
 
-        let sess = database.session("widget", None);
+        let sess = database.session("widget", None).unwrap();
         let search = database
             .search(collection_field)
             .search(semilattice_database::Condition::Depend(
@@ -122,16 +120,32 @@ fn test4() {
                 CollectionRow::new(-collection_widget, 1.try_into().unwrap()),
             ))
             .search_activity(Activity::Active);
-        for r in sess
-            .result_with(&search.result(&database).await)
-            .await
-            .rows()
-        {
+        let result = sess.result_with(&search.result(&database).await).await;
+        for r in result.rows() {
             println!(
                 "session_search : {}",
-                std::str::from_utf8(sess.field_bytes(&database, collection_field, *r, &field_name))
-                    .unwrap()
+                std::str::from_utf8(&sess.field_bytes(
+                    &database,
+                    collection_field,
+                    *r,
+                    &field_name
+                ))
+                .unwrap()
             );
         }
+
+        let records = sess.records(
+            &database,
+            &result,
+            &search::RecordSelect::default(),
+            &search::Utf8Decoder,
+        );
+        assert_eq!(
+            records
+                .iter()
+                .map(|record| record.fields[&field_name].as_str())
+                .collect::<Vec<_>>(),
+            vec!["3-r", "2", "1"]
+        );
     });
 }
@@ -0,0 +1,429 @@
+use std::{
+    borrow::Cow,
+    num::NonZeroU32,
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use hashbrown::HashMap;
+use parking_lot::Mutex;
+use versatile_data::{
+    idx_binary::{AvltrieeSearch, AvltrieeUpdate},
+    AvltrieeIter, Field, FieldName, Fields, IdxBinary, IdxFile, RowSet,
+};
+
+use crate::{Cipher, Collection, CollectionRow, Database, WriteError};
+
+const INSERT: u8 = 1;
+const UPDATE: u8 = 2;
+const DELETE: u8 = 3;
+const RELATE: u8 = 4;
+const UNRELATE: u8 = 5;
+
+/// Who is writing, recorded with every change while set. See [Database::set_audit_context].
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct AuditContext {
+    pub actor: String,
+    pub session: Option<String>,
+}
+impl AuditContext {
+    pub fn new(actor: impl Into<String>) -> Self {
+        Self {
+            actor: actor.into(),
+            session: None,
+        }
+    }
+
+    pub fn session(mut self, session: impl Into<String>) -> Self {
+        self.session = Some(session.into());
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AuditChange {
+    /// `after` holds the fields written.
+    Insert {
+        target: CollectionRow,
+        after: HashMap<FieldName, Vec<u8>>,
+    },
+    /// `after` holds the fields written and `before` the values they replaced.
+    Update {
+        target: CollectionRow,
+        before: HashMap<FieldName, Vec<u8>>,
+        after: HashMap<FieldName, Vec<u8>>,
+    },
+    /// `before` holds every field the row had.
+    Delete {
+        target: CollectionRow,
+        before: HashMap<FieldName, Vec<u8>>,
+    },
+    Relate {
+        key: String,
+        depend: CollectionRow,
+        pend: CollectionRow,
+    },
+    Unrelate {
+        key: String,
+        depend: CollectionRow,
+        pend: CollectionRow,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuditEntry {
+    /// Seconds since the UNIX epoch.
+    pub at: u64,
+    /// Empty for changes made while no context was set.
+    pub actor: String,
+    pub session: Option<String>,
+    pub change: AuditChange,
+}
+
+/// Filters for [Database::audit]. Entries must match every filter given.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct AuditQuery {
+    row: Option<CollectionRow>,
+    actor: Option<String>,
+    time: Option<RangeInclusive<u64>>,
+}
+impl AuditQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Changes to the row, including relations it is either side of.
+    pub fn row(mut self, row: CollectionRow) -> Self {
+        self.row = Some(row);
+        self
+    }
+
+    pub fn actor(mut self, actor: impl Into<String>) -> Self {
+        self.actor = Some(actor.into());
+        self
+    }
+
+    pub fn time(mut self, time: RangeInclusive<u64>) -> Self {
+        self.time = Some(time);
+        self
+    }
+}
+
+/// Append-only log of row and relation changes, shared by the collections and the relation index.
+pub(crate) struct AuditLog {
+    dir: PathBuf,
+    context: Option<AuditContext>,
+    at: IdxFile<u64>,
+    operation: IdxFile<u8>,
+    actor: IdxBinary,
+    session: IdxBinary,
+    target: IdxFile<CollectionRow>,
+    depend: IdxFile<CollectionRow>,
+    key: IdxBinary,
+    before: Fields,
+    after: Fields,
+    /// Encrypts relation keys. Field values arrive already encrypted by their collection.
+    cipher: Option<Cipher>,
+}
+
+pub(crate) type SharedAuditLog = Arc<Mutex<AuditLog>>;
+
+fn load_fields(dir: &Path) -> Fields {
+    if !dir.exists() {
+        std::fs::create_dir_all(dir).unwrap();
+    }
+    let mut fields = Fields::new();
+    for d in dir.read_dir().unwrap().flatten() {
+        if d.path().is_dir() {
+            if let Some(name) = d.file_name().to_str() {
+                fields.insert(FieldName::new(name.into()), Field::new(d.path(), 1));
+            }
+        }
+    }
+    fields
+}
+
+fn write_fields(
+    fields: &mut Fields,
+    dir: &Path,
+    entry: NonZeroU32,
+    values: &HashMap<FieldName, Vec<u8>>,
+) {
+    for (name, value) in values.iter() {
+        if !fields.contains_key(name) {
+            let dir = dir.join(name.as_str());
+            std::fs::create_dir_all(&dir).unwrap();
+            fields.insert(name.clone(), Field::new(dir, 1));
+        }
+        fields.get_mut(name).unwrap().update(entry, value);
+    }
+}
+
+fn read_fields(fields: &Fields, entry: NonZeroU32) -> HashMap<FieldName, Vec<u8>> {
+    fields
+        .iter()
+        .filter_map(|(name, field)| {
+            field
+                .value(entry)
+                .map(|value| (name.clone(), value.to_vec()))
+        })
+        .collect()
+}
+
+impl AuditLog {
+    fn new(dir: PathBuf, cipher: Option<Cipher>) -> Self {
+        if !dir.exists() {
+            std::fs::create_dir_all(&dir).unwrap();
+        }
+        Self {
+            context: None,
+            at: IdxFile::new(dir.join("at.i"), 1),
+            operation: IdxFile::new(dir.join("operation.i"), 1),
+            actor: IdxBinary::new_ext(dir.join("actor"), 1),
+            session: IdxBinary::new_ext(dir.join("session"), 1),
+            target: IdxFile::new(dir.join("target.i"), 1),
+            depend: IdxFile::new(dir.join("depend.i"), 1),
+            key: IdxBinary::new_ext(dir.join("key"), 1),
+            before: load_fields(&dir.join("before")),
+            after: load_fields(&dir.join("after")),
+            dir,
+            cipher,
+        }
+    }
+
+    fn stored_key<'a>(&self, key: &'a str) -> Cow<'a, [u8]> {
+        match &self.cipher {
+            Some(cipher) => Cow::Owned(cipher.encrypt_relation_key(key)),
+            None => Cow::Borrowed(key.as_bytes()),
+        }
+    }
+
+    fn read_key(&self, bytes: &[u8]) -> Option<String> {
+        match &self.cipher {
+            Some(cipher) => cipher.decrypt_relation_key(bytes),
+            None => Some(String::from_utf8_lossy(bytes).into_owned()),
+        }
+    }
+
+    pub(crate) fn push(&mut self, change: AuditChange) {
+        let entry = self.at.insert(
+            &SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
+        );
+        let context = self.context.clone().unwrap_or_default();
+        self.actor.update(entry, context.actor.as_bytes());
+        if let Some(session) = context.session {
+            self.session.update(entry, session.as_bytes());
+        }
+        let (operation, target) = match change {
+            AuditChange::Insert { target, after } => {
+                write_fields(&mut self.after, &self.dir.join("after"), entry, &after);
+                (INSERT, target)
+            }
+            AuditChange::Update {
+                target,
+                before,
+                after,
+            } => {
+                write_fields(&mut self.before, &self.dir.join("before"), entry, &before);
+                write_fields(&mut self.after, &self.dir.join("after"), entry, &after);
+                (UPDATE, target)
+            }
+            AuditChange::Delete { target, before } => {
+                write_fields(&mut self.before, &self.dir.join("before"), entry, &before);
+                (DELETE, target)
+            }
+            AuditChange::Relate { key, depend, pend } => {
+                self.key.update(entry, &self.stored_key(&key));
+                self.depend.update(entry, &depend);
+                (RELATE, pend)
+            }
+            AuditChange::Unrelate { key, depend, pend } => {
+                self.key.update(entry, &self.stored_key(&key));
+                self.depend.update(entry, &depend);
+                (UNRELATE, pend)
+            }
+        };
+        self.operation.update(entry, &operation);
+        self.target.update(entry, &target);
+    }
+
+    fn entry(&self, entry: NonZeroU32) -> Option<AuditEntry> {
+        let target = self.target.value(entry)?.clone();
+        let relation = || {
+            Some((
+                self.read_key(self.key.value(entry)?)?,
+                self.depend.value(entry)?.clone(),
+            ))
+        };
+        let change = match *self.operation.value(entry)? {
+            INSERT => AuditChange::Insert {
+                target,
+                after: read_fields(&self.after, entry),
+            },
+            UPDATE => AuditChange::Update {
+                target,
+                before: read_fields(&self.before, entry),
+                after: read_fields(&self.after, entry),
+            },
+            DELETE => AuditChange::Delete {
+                target,
+                before: read_fields(&self.before, entry),
+            },
+            RELATE => {
+                let (key, depend) = relation()?;
+                AuditChange::Relate {
+                    key,
+                    depend,
+                    pend: target,
+                }
+            }
+            UNRELATE => {
+                let (key, depend) = relation()?;
+                AuditChange::Unrelate {
+                    key,
+                    depend,
+                    pend: target,
+                }
+            }
+            _ => return None,
+        };
+        Some(AuditEntry {
+            at: *self.at.value(entry)?,
+            actor: String::from_utf8_lossy(self.actor.value(entry)?).into_owned(),
+            session: self
+                .session
+                .value(entry)
+                .map(|session| String::from_utf8_lossy(session).into_owned()),
+            change,
+        })
+    }
+
+    fn query(&self, query: &AuditQuery) -> Vec<AuditEntry> {
+        let mut entries: Option<RowSet> = None;
+        let mut narrow = |rows: RowSet| {
+            if let Some(ref mut entries) = entries {
+                entries.retain(|entry| rows.contains(entry));
+            } else {
+                entries = Some(rows);
+            }
+        };
+        if let Some(row) = &query.row {
+            narrow(
+                self.target
+                    .iter_by(row)
+                    .chain(self.depend.iter_by(row))
+                    .collect(),
+            );
+        }
+        if let Some(actor) = &query.actor {
+            narrow(AvltrieeIter::by(&self.actor, actor.as_bytes()).collect());
+        }
+        if let Some(time) = &query.time {
+            narrow(self.at.iter_range(time.start(), time.end()).collect());
+        }
+        entries
+            .unwrap_or_else(|| self.at.iter().collect())
+            .into_iter()
+            .filter_map(|entry| self.entry(entry))
+            .collect()
+    }
+}
+
+impl Collection {
+    /// Values of `row` for the fields in `only`, or for every field.
+    pub(crate) fn audit_values(
+        &self,
+        row: NonZeroU32,
+        only: Option<&HashMap<FieldName, Vec<u8>>>,
+    ) -> HashMap<FieldName, Vec<u8>> {
+        self.data()
+            .fields()
+            .iter()
+            .filter(|(name, _)| only.is_none_or(|only| only.contains_key(*name)))
+            .filter_map(|(name, field)| {
+                field.value(row).map(|value| (name.clone(), value.to_vec()))
+            })
+            .collect()
+    }
+
+    pub(crate) fn audit_change(&self, change: AuditChange) {
+        if let Some(audit) = &self.audit {
+            audit.lock().push(change);
+        }
+    }
+
+    /// Records the changes under a single lock of the log.
+    pub(crate) fn audit_changes(&self, changes: impl IntoIterator<Item = AuditChange>) {
+        if let Some(audit) = &self.audit {
+            let mut audit = audit.lock();
+            for change in changes {
+                audit.push(change);
+            }
+        }
+    }
+}
+
+impl Database {
+    /// Starts recording every row and relation change with the current [AuditContext].
+    pub fn enable_audit(&mut self) -> Result<(), WriteError> {
+        self.check_writable()?;
+        if self.audit.is_none() {
+            self.set_audit(Some(Arc::new(Mutex::new(AuditLog::new(
+                self.audit_dir(),
+                self.cipher.clone(),
+            )))));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn load_audit(&mut self) {
+        let dir = self.audit_dir();
+        if dir.exists() {
+            self.set_audit(Some(Arc::new(Mutex::new(AuditLog::new(
+                dir,
+                self.cipher.clone(),
+            )))));
+        }
+    }
+
+    fn set_audit(&mut self, audit: Option<SharedAuditLog>) {
+        for collection in self.collections.values_mut() {
+            collection.audit = audit.clone();
+        }
+        self.relation.audit = audit.clone();
+        self.audit = audit;
+    }
+
+    fn audit_dir(&self) -> PathBuf {
+        self.collections_dir.with_file_name("audit")
+    }
+
+    pub fn is_audited(&self) -> bool {
+        self.audit.is_some()
+    }
+
+    /// Sets who the following writes are made by, or `None` to record them without an actor.
+    pub fn set_audit_context(&mut self, context: Option<AuditContext>) {
+        if let Some(audit) = &self.audit {
+            audit.lock().context = context;
+        }
+    }
+
+    pub fn audit_context(&self) -> Option<AuditContext> {
+        self.audit
+            .as_ref()
+            .and_then(|audit| audit.lock().context.clone())
+    }
+
+    /// Recorded changes matching `query`, oldest first.
+    pub fn audit(&self, query: &AuditQuery) -> Vec<AuditEntry> {
+        self.audit
+            .as_ref()
+            .map_or(vec![], |audit| audit.lock().query(query))
+    }
+}
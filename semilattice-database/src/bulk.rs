@@ -0,0 +1,329 @@
+use std::{
+    fmt,
+    hash::Hash,
+    num::{NonZeroI32, NonZeroU32},
+};
+
+use futures::{Stream, StreamExt};
+use hashbrown::{HashMap, HashSet};
+use versatile_data::{Activity, FieldName, Term};
+
+use crate::{audit::AuditChange, Collection, CollectionRow, Database, WriteError};
+
+/// The row a loaded record depends on.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BulkTarget<K> {
+    /// A record of the same load, by its temporary id. It may come later in the stream.
+    Loaded(K),
+    /// A row already in the database.
+    Row(CollectionRow),
+}
+
+/// A row to insert, identified by a temporary id that relations of the same load refer to.
+#[derive(Debug, Clone)]
+pub struct BulkRecord<K> {
+    pub id: K,
+    pub collection_id: NonZeroI32,
+    pub activity: Activity,
+    pub term_begin: Term,
+    pub term_end: Term,
+    pub fields: HashMap<FieldName, Vec<u8>>,
+    /// `(relation key, depend)` for each row this one depends on.
+    pub depends: Vec<(String, BulkTarget<K>)>,
+}
+impl<K> BulkRecord<K> {
+    /// An active record with the default term.
+    pub fn new(id: K, collection_id: NonZeroI32) -> Self {
+        Self {
+            id,
+            collection_id,
+            activity: Activity::Active,
+            term_begin: Term::Default,
+            term_end: Term::Default,
+            fields: HashMap::new(),
+            depends: vec![],
+        }
+    }
+
+    pub fn field(mut self, field_name: FieldName, value: impl Into<Vec<u8>>) -> Self {
+        self.fields.insert(field_name, value.into());
+        self
+    }
+
+    pub fn depend(mut self, key: impl Into<String>, target: BulkTarget<K>) -> Self {
+        self.depends.push((key.into(), target));
+        self
+    }
+}
+
+#[derive(Debug)]
+pub enum BulkErrorKind<K> {
+    /// The record was rejected by the schema of its collection.
+    Write {
+        id: K,
+        error: WriteError,
+    },
+    UnknownCollection {
+        id: K,
+        collection_id: NonZeroI32,
+    },
+    DuplicateId(K),
+    /// Two records of the same batch have the same values for a unique constraint.
+    DuplicateKey {
+        id: K,
+        other: K,
+        field_names: Vec<FieldName>,
+    },
+    /// A relation refers to a temporary id that no record of the load had.
+    UnresolvedId(K),
+    /// A relation of the record depends on a row that is not in the database.
+    UnknownRow {
+        id: K,
+        row: CollectionRow,
+    },
+}
+impl<K: fmt::Debug> fmt::Display for BulkErrorKind<K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Write { id, error } => write!(f, "record {:?}: {}", id, error),
+            Self::UnknownCollection { id, collection_id } => {
+                write!(f, "record {:?}: no collection {}", id, collection_id)
+            }
+            Self::DuplicateId(id) => write!(f, "record {:?} appears more than once", id),
+            Self::DuplicateKey {
+                id,
+                other,
+                field_names,
+            } => write!(
+                f,
+                "records {:?} and {:?} have the same values for ({})",
+                other,
+                id,
+                field_names
+                    .iter()
+                    .map(|field_name| field_name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            Self::UnresolvedId(id) => write!(f, "no record {:?} to relate to", id),
+            Self::UnknownRow { id, row } => write!(
+                f,
+                "record {:?}: no row {} in collection {}",
+                id,
+                row.row(),
+                row.collection_id()
+            ),
+        }
+    }
+}
+
+/// A load stopped by an error. The batches before the failing one stay written, and `rows` has the row each of their temporary ids became.
+#[derive(Debug)]
+pub struct BulkError<K> {
+    pub kind: BulkErrorKind<K>,
+    pub rows: HashMap<K, CollectionRow>,
+}
+impl<K: fmt::Debug> fmt::Display for BulkError<K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({} rows written)", self.kind, self.rows.len())
+    }
+}
+impl<K: fmt::Debug> std::error::Error for BulkError<K> {}
+
+impl Collection {
+    /// Inserts rows one at a time with [Data::insert](versatile_data::Data::insert), then updates the full-text and composite indexes, the history and the audit log for them.
+    async fn insert_batch<K>(&mut self, records: Vec<BulkRecord<K>>) -> Vec<(K, NonZeroU32)> {
+        let mut ids = Vec::with_capacity(records.len());
+        let mut rows = Vec::with_capacity(records.len());
+        let mut texts = Vec::with_capacity(records.len());
+        let mut audits = Vec::new();
+        for record in records {
+            let fields = self.encrypt_fields(record.fields);
+            texts.push(self.fulltext_texts(&fields));
+            if self.audit.is_some() {
+                audits.push(fields.clone());
+            }
+            ids.push(record.id);
+            rows.push(
+                self.data
+                    .insert(record.activity, record.term_begin, record.term_end, fields)
+                    .await,
+            );
+        }
+        for (row, texts) in rows.iter().zip(texts) {
+            self.update_fulltext(*row, texts);
+        }
+        for row in rows.iter() {
+            self.update_composite(*row);
+        }
+        for row in rows.iter() {
+            self.record_history(*row);
+        }
+        let collection_id = self.id();
+        self.audit_changes(
+            rows.iter()
+                .zip(audits)
+                .map(|(row, after)| AuditChange::Insert {
+                    target: CollectionRow::new(collection_id, *row),
+                    after,
+                }),
+        );
+        ids.into_iter().zip(rows).collect()
+    }
+}
+
+impl Database {
+    /// Inserts `records` `batch_size` at a time and registers their relations, returning the row each temporary id became.
+    /// Every record of a batch is checked against the schema and unique constraints of its collection, and the existing rows it depends on are checked to exist, before any of the batch is written.
+    /// A relation to a record later in the stream is registered once that record is written.
+    /// Batches already written stay written when an error stops the load, and the error carries their rows.
+    pub async fn bulk_load<K, S>(
+        &mut self,
+        records: S,
+        batch_size: usize,
+    ) -> Result<HashMap<K, CollectionRow>, BulkError<K>>
+    where
+        K: Eq + Hash + Clone,
+        S: Stream<Item = BulkRecord<K>>,
+    {
+        let mut rows = HashMap::new();
+        match self.bulk_stream(records, batch_size, &mut rows).await {
+            Ok(()) => Ok(rows),
+            Err(kind) => Err(BulkError { kind, rows }),
+        }
+    }
+
+    async fn bulk_stream<K, S>(
+        &mut self,
+        records: S,
+        batch_size: usize,
+        rows: &mut HashMap<K, CollectionRow>,
+    ) -> Result<(), BulkErrorKind<K>>
+    where
+        K: Eq + Hash + Clone,
+        S: Stream<Item = BulkRecord<K>>,
+    {
+        let mut relations = vec![];
+        let mut batch = Vec::with_capacity(batch_size);
+        futures::pin_mut!(records);
+        while let Some(record) = records.next().await {
+            batch.push(record);
+            if batch.len() >= batch_size.max(1) {
+                self.bulk_batch(std::mem::take(&mut batch), rows, &mut relations)
+                    .await?;
+            }
+        }
+        self.bulk_batch(batch, rows, &mut relations).await?;
+        if let Some((_, BulkTarget::Loaded(id), _)) = relations.into_iter().next() {
+            return Err(BulkErrorKind::UnresolvedId(id));
+        }
+        Ok(())
+    }
+
+    async fn bulk_batch<K: Eq + Hash + Clone>(
+        &mut self,
+        batch: Vec<BulkRecord<K>>,
+        rows: &mut HashMap<K, CollectionRow>,
+        relations: &mut Vec<(String, BulkTarget<K>, K)>,
+    ) -> Result<(), BulkErrorKind<K>> {
+        let mut ids = HashSet::new();
+        let mut keys: HashMap<(NonZeroI32, usize, Vec<Vec<u8>>), K> = HashMap::new();
+        for record in batch.iter() {
+            let Some(collection) = self.collection(record.collection_id) else {
+                return Err(BulkErrorKind::UnknownCollection {
+                    id: record.id.clone(),
+                    collection_id: record.collection_id,
+                });
+            };
+            if self.read_only {
+                return Err(BulkErrorKind::Write {
+                    id: record.id.clone(),
+                    error: WriteError::ReadOnly,
+                });
+            }
+            if rows.contains_key(&record.id) || !ids.insert(record.id.clone()) {
+                return Err(BulkErrorKind::DuplicateId(record.id.clone()));
+            }
+            let write_error = |error: WriteError| BulkErrorKind::Write {
+                id: record.id.clone(),
+                error,
+            };
+            collection
+                .validate(&record.fields, true)
+                .map_err(|e| write_error(e.into()))?;
+            collection
+                .check_unique(None, &record.fields)
+                .map_err(|e| write_error(e.into()))?;
+            for (_, target) in record.depends.iter() {
+                if let BulkTarget::Row(row) = target {
+                    if self
+                        .collection(row.collection_id())
+                        .is_none_or(|collection| collection.uuid(row.row()).is_none())
+                    {
+                        return Err(BulkErrorKind::UnknownRow {
+                            id: record.id.clone(),
+                            row: row.clone(),
+                        });
+                    }
+                }
+            }
+            for (i, field_names) in collection
+                .schema()
+                .into_iter()
+                .flat_map(|schema| schema.unique.iter())
+                .enumerate()
+            {
+                if let Some(key) = collection.unique_key(field_names, None, &record.fields) {
+                    if let Some(other) =
+                        keys.insert((record.collection_id, i, key), record.id.clone())
+                    {
+                        return Err(BulkErrorKind::DuplicateKey {
+                            id: record.id.clone(),
+                            other,
+                            field_names: field_names.clone(),
+                        });
+                    }
+                }
+            }
+        }
+
+        let mut by_collection: HashMap<NonZeroI32, Vec<BulkRecord<K>>> = HashMap::new();
+        for record in batch {
+            by_collection
+                .entry(record.collection_id)
+                .or_default()
+                .push(record);
+        }
+        for (collection_id, mut records) in by_collection {
+            for record in records.iter_mut() {
+                relations.extend(
+                    std::mem::take(&mut record.depends)
+                        .into_iter()
+                        .map(|(key, target)| (key, target, record.id.clone())),
+                );
+            }
+            let collection = self.collection_mut(collection_id).unwrap();
+            for (id, row) in collection.insert_batch(records).await {
+                rows.insert(id, CollectionRow::new(collection_id, row));
+            }
+        }
+
+        let mut waiting = vec![];
+        for (key, target, pend) in std::mem::take(relations) {
+            let depend = match &target {
+                BulkTarget::Loaded(id) => rows.get(id),
+                BulkTarget::Row(row) => Some(row),
+            };
+            if let (Some(depend), Some(pend_row)) = (depend, rows.get(&pend)) {
+                self.relation
+                    .insert(&key, depend, pend_row)
+                    .await
+                    .map_err(|error| BulkErrorKind::Write { id: pend, error })?;
+            } else {
+                waiting.push((key, target, pend));
+            }
+        }
+        *relations = waiting;
+        Ok(())
+    }
+}
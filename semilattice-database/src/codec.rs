@@ -0,0 +1,212 @@
+//! Typed field values whose encoded bytes sort in the same order as the values.
+//!
+//! Field indexes compare values with [IdxBinary](crate::idx_binary::IdxBinary)'s natural order, which reads runs of ASCII digits as numbers.
+//! Fixed width values are therefore written as big-endian bytes with each nibble mapped to a letter from `a` to `p`,
+//! which contains no digits and so compares byte by byte.
+//! Strings are stored as their UTF-8 bytes with a NUL byte after each ASCII digit, so that no two digits are read as one number
+//! while `Forward`, `Partial` and `Backward` searches for text without digits keep working on them.
+//! Searches for text with digits take the encoded text.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use versatile_data::idx_binary::AvltrieeSearch;
+
+use crate::{search::Field, Collection, FieldName, FieldType};
+
+pub trait Codec: Sized {
+    /// The schema type that accepts the encoded values.
+    const FIELD_TYPE: FieldType;
+
+    fn encode(&self) -> Vec<u8>;
+    fn decode(bytes: &[u8]) -> Option<Self>;
+}
+
+const NIBBLE_BASE: u8 = b'a';
+const DIGIT_END: u8 = 0;
+
+fn encode_be(bytes: &[u8]) -> Vec<u8> {
+    bytes
+        .iter()
+        .flat_map(|b| [NIBBLE_BASE + (b >> 4), NIBBLE_BASE + (b & 0x0f)])
+        .collect()
+}
+
+fn decode_be<const N: usize>(bytes: &[u8]) -> Option<[u8; N]> {
+    if bytes.len() != N * 2 {
+        return None;
+    }
+    let mut decoded = [0u8; N];
+    for (i, pair) in bytes.chunks_exact(2).enumerate() {
+        let high = pair[0].checked_sub(NIBBLE_BASE).filter(|n| *n < 16)?;
+        let low = pair[1].checked_sub(NIBBLE_BASE).filter(|n| *n < 16)?;
+        decoded[i] = high << 4 | low;
+    }
+    Some(decoded)
+}
+
+impl Codec for u64 {
+    const FIELD_TYPE: FieldType = FieldType::U64;
+
+    fn encode(&self) -> Vec<u8> {
+        encode_be(&self.to_be_bytes())
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        decode_be(bytes).map(u64::from_be_bytes)
+    }
+}
+
+impl Codec for i64 {
+    const FIELD_TYPE: FieldType = FieldType::I64;
+
+    fn encode(&self) -> Vec<u8> {
+        ((*self as u64) ^ (1 << 63)).encode()
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        u64::decode(bytes).map(|v| (v ^ (1 << 63)) as i64)
+    }
+}
+
+impl Codec for f64 {
+    const FIELD_TYPE: FieldType = FieldType::F64;
+
+    /// NaN sorts after every other value.
+    fn encode(&self) -> Vec<u8> {
+        let bits = if self.is_nan() {
+            f64::NAN.to_bits()
+        } else {
+            self.to_bits()
+        };
+        if bits >> 63 == 1 {
+            !bits
+        } else {
+            bits | (1 << 63)
+        }
+        .encode()
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        u64::decode(bytes).map(|bits| {
+            f64::from_bits(if bits >> 63 == 1 {
+                bits & !(1 << 63)
+            } else {
+                !bits
+            })
+        })
+    }
+}
+
+impl Codec for bool {
+    const FIELD_TYPE: FieldType = FieldType::Bool;
+
+    fn encode(&self) -> Vec<u8> {
+        vec![NIBBLE_BASE + *self as u8]
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        match bytes {
+            [b] if *b == NIBBLE_BASE => Some(false),
+            [b] if *b == NIBBLE_BASE + 1 => Some(true),
+            _ => None,
+        }
+    }
+}
+
+/// Microseconds from the UNIX epoch, negative before it.
+impl Codec for SystemTime {
+    const FIELD_TYPE: FieldType = FieldType::Timestamp;
+
+    fn encode(&self) -> Vec<u8> {
+        match self.duration_since(UNIX_EPOCH) {
+            Ok(d) => d.as_micros() as i64,
+            Err(e) => -(e.duration().as_micros() as i64),
+        }
+        .encode()
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        i64::decode(bytes).map(|micros| {
+            let d = Duration::from_micros(micros.unsigned_abs());
+            if micros < 0 {
+                UNIX_EPOCH - d
+            } else {
+                UNIX_EPOCH + d
+            }
+        })
+    }
+}
+
+impl Codec for String {
+    const FIELD_TYPE: FieldType = FieldType::Utf8;
+
+    fn encode(&self) -> Vec<u8> {
+        let mut encoded = Vec::with_capacity(self.len());
+        for b in self.bytes() {
+            encoded.push(b);
+            if b.is_ascii_digit() {
+                encoded.push(DIGIT_END);
+            }
+        }
+        encoded
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        let mut decoded = Vec::with_capacity(bytes.len());
+        let mut bytes = bytes.iter();
+        while let Some(b) = bytes.next() {
+            decoded.push(*b);
+            if b.is_ascii_digit() && bytes.next() != Some(&DIGIT_END) {
+                return None;
+            }
+        }
+        String::from_utf8(decoded).ok()
+    }
+}
+
+impl Codec for Vec<u8> {
+    const FIELD_TYPE: FieldType = FieldType::Bytes;
+
+    fn encode(&self) -> Vec<u8> {
+        self.clone()
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        Some(bytes.to_vec())
+    }
+}
+
+/// A field condition on a typed value, see [Search::search_field_value](crate::Search::search_field_value).
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldValue<T: Codec> {
+    Match(T),
+    Range(T, T),
+    Min(T),
+    Max(T),
+}
+impl<T: Codec> From<FieldValue<T>> for Field {
+    fn from(value: FieldValue<T>) -> Self {
+        match value {
+            FieldValue::Match(v) => Field::Match(v.encode()),
+            FieldValue::Range(min, max) => Field::Range(min.encode(), max.encode()),
+            FieldValue::Min(min) => Field::Min(min.encode()),
+            FieldValue::Max(max) => Field::Max(max.encode()),
+        }
+    }
+}
+
+impl Collection {
+    /// Decodes the value of a field written with [Codec]. Returns None if the row has no such field or it does not decode as `T`.
+    pub fn field_value<T: Codec>(
+        &self,
+        row: std::num::NonZeroU32,
+        field_name: &FieldName,
+    ) -> Option<T> {
+        self.data()
+            .fields()
+            .get(field_name)
+            .and_then(|field| field.value(row))
+            .and_then(|bytes| self.decrypt(field_name, bytes))
+            .and_then(|bytes| T::decode(&bytes))
+    }
+}
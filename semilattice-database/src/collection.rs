@@ -1,21 +1,38 @@
+mod record;
 mod row;
 
+pub use record::CollectionRecord;
 pub use row::CollectionRow;
 
 use std::{
-    num::NonZeroI32,
-    ops::{Deref, DerefMut},
+    io,
+    num::{NonZeroI32, NonZeroU32},
+    ops::Deref,
     path::PathBuf,
 };
 
-use versatile_data::{Data, DataOption};
+use hashbrown::HashMap;
+use versatile_data::{Activity, Data, DataOption, FieldName, Term};
 
-use crate::Database;
+use crate::{
+    audit::{AuditChange, SharedAuditLog},
+    composite::CompositeIndex,
+    fulltext::FullTextIndex,
+    history::History,
+    Cipher, Database, Schema, WriteError,
+};
 
 pub struct Collection {
-    data: Data,
+    pub(crate) data: Data,
     id: NonZeroI32,
     name: String,
+    schema: Option<Schema>,
+    pub(crate) fulltext: HashMap<FieldName, FullTextIndex>,
+    pub(crate) composite: HashMap<String, CompositeIndex>,
+    pub(crate) history: Option<History>,
+    pub(crate) audit: Option<SharedAuditLog>,
+    pub(crate) read_only: bool,
+    pub(crate) cipher: Option<Cipher>,
 }
 impl Collection {
     pub fn new(data: Data, id: NonZeroI32, name: impl Into<String>) -> Self {
@@ -23,6 +40,13 @@ impl Collection {
             data,
             id,
             name: name.into(),
+            schema: None,
+            fulltext: HashMap::new(),
+            composite: HashMap::new(),
+            history: None,
+            audit: None,
+            read_only: false,
+            cipher: None,
         }
     }
 
@@ -37,6 +61,109 @@ impl Collection {
     pub fn data(&self) -> &Data {
         &self.data
     }
+
+    /// The fields any row of this collection has been written with.
+    pub fn field_names(&self) -> impl Iterator<Item = &FieldName> {
+        self.data.fields().keys()
+    }
+
+    pub fn schema(&self) -> Option<&Schema> {
+        self.schema.as_ref()
+    }
+
+    /// Mutable access to the rows, bypassing the schema, indexes, history and audit log.
+    pub fn data_mut(&mut self) -> Result<&mut Data, WriteError> {
+        self.check_writable()?;
+        Ok(&mut self.data)
+    }
+
+    pub(crate) fn check_writable(&self) -> Result<(), WriteError> {
+        if self.read_only {
+            Err(WriteError::ReadOnly)
+        } else {
+            Ok(())
+        }
+    }
+
+    pub(crate) fn set_schema(&mut self, schema: Option<Schema>) {
+        self.schema = schema;
+    }
+
+    /// Writes a new row without checking the schema. Full-text and composite indexes and the history are kept up to date.
+    pub async fn insert_unchecked(
+        &mut self,
+        activity: Activity,
+        term_begin: Term,
+        term_end: Term,
+        fields: HashMap<FieldName, Vec<u8>>,
+    ) -> Result<NonZeroU32, WriteError> {
+        self.check_writable()?;
+        let fields = self.encrypt_fields(fields);
+        let texts = self.fulltext_texts(&fields);
+        let audit = self.audit.is_some().then(|| fields.clone());
+        let row = self
+            .data
+            .insert(activity, term_begin, term_end, fields)
+            .await;
+        self.update_fulltext(row, texts);
+        self.update_composite(row);
+        self.record_history(row);
+        if let Some(after) = audit {
+            self.audit_change(AuditChange::Insert {
+                target: CollectionRow::new(self.id, row),
+                after,
+            });
+        }
+        Ok(row)
+    }
+
+    /// Updates a row without checking the schema. Full-text and composite indexes and the history are kept up to date.
+    pub async fn update_unchecked(
+        &mut self,
+        row: NonZeroU32,
+        activity: Activity,
+        term_begin: Term,
+        term_end: Term,
+        fields: HashMap<FieldName, Vec<u8>>,
+    ) -> Result<(), WriteError> {
+        self.check_writable()?;
+        let fields = self.encrypt_fields(fields);
+        let texts = self.fulltext_texts(&fields);
+        let audit = self
+            .audit
+            .is_some()
+            .then(|| (self.audit_values(row, Some(&fields)), fields.clone()));
+        self.data
+            .update(row, activity, term_begin, term_end, fields)
+            .await;
+        self.update_fulltext(row, texts);
+        self.update_composite(row);
+        self.record_history(row);
+        if let Some((before, after)) = audit {
+            self.audit_change(AuditChange::Update {
+                target: CollectionRow::new(self.id, row),
+                before,
+                after,
+            });
+        }
+        Ok(())
+    }
+
+    pub async fn delete(&mut self, row: NonZeroU32) -> Result<(), WriteError> {
+        self.check_writable()?;
+        let audit = self.audit.is_some().then(|| self.audit_values(row, None));
+        self.data.delete(row).await;
+        self.delete_fulltext(row);
+        self.delete_composite(row);
+        self.record_history_delete(row);
+        if let Some(before) = audit {
+            self.audit_change(AuditChange::Delete {
+                target: CollectionRow::new(self.id, row),
+                before,
+            });
+        }
+        Ok(())
+    }
 }
 impl Deref for Collection {
     type Target = Data;
@@ -44,20 +171,22 @@ impl Deref for Collection {
         &self.data
     }
 }
-impl DerefMut for Collection {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.data
-    }
-}
 
 impl Database {
     pub fn collections(&self) -> Vec<String> {
         self.collections
-            .iter()
-            .map(|(_, x)| x.name().to_owned())
+            .values()
+            .map(|x| x.name().to_owned())
             .collect()
     }
 
+    /// `(id, name)` of every collection in order of id.
+    pub fn collection_entries(&self) -> impl Iterator<Item = (NonZeroI32, &str)> {
+        self.collections
+            .values()
+            .map(|collection| (collection.id(), collection.name()))
+    }
+
     pub fn collection(&self, id: NonZeroI32) -> Option<&Collection> {
         self.collections.get(&id)
     }
@@ -72,24 +201,26 @@ impl Database {
             .then(|| *self.collections_map.get(name).unwrap())
     }
 
-    pub fn collection_id_or_create(&mut self, name: &str) -> NonZeroI32 {
+    /// Fails with [WriteError::ReadOnly] only if the collection has to be created.
+    pub fn collection_id_or_create(&mut self, name: &str) -> Result<NonZeroI32, WriteError> {
         if self.collections_map.contains_key(name) {
-            *self.collections_map.get(name).unwrap()
+            Ok(*self.collections_map.get(name).unwrap())
         } else {
-            self.collection_by_name_or_create(name)
+            self.check_writable()?;
+            Ok(self.collection_by_name_or_create(name))
         }
     }
 
-    pub async fn delete_collection(&mut self, name: &str) {
+    pub async fn delete_collection(&mut self, name: &str) -> Result<(), WriteError> {
+        self.check_writable()?;
         let collection_id = self.collections_map.get(name).map_or(0, |x| x.get());
         if collection_id > 0 {
             let collection_id = unsafe { NonZeroI32::new_unchecked(collection_id) };
             if let Some(collection) = self.collections.get(&collection_id) {
                 for row in collection.data.all().into_iter() {
-                    self.delete(&CollectionRow::new(collection_id, row))
-                        .await;
+                    self.delete(&CollectionRow::new(collection_id, row)).await?;
                     if let Some(collection) = self.collection_mut(collection_id) {
-                        collection.delete(row).await;
+                        collection.delete(row).await?;
                     }
                 }
             }
@@ -100,12 +231,20 @@ impl Database {
             dir.push(collection_id.to_string() + "_" + name);
             std::fs::remove_dir_all(&dir).unwrap();
         }
+        Ok(())
     }
 
-    pub(super) fn create_collection(&mut self, id: NonZeroI32, name: &str, dir: PathBuf) {
-        let collection = Collection::new(
+    /// Fails if the schema of the collection cannot be read.
+    pub(super) fn create_collection(
+        &mut self,
+        id: NonZeroI32,
+        name: &str,
+        dir: PathBuf,
+    ) -> io::Result<()> {
+        let schema = Schema::load(&dir)?;
+        let mut collection = Collection::new(
             Data::new(
-                dir,
+                dir.clone(),
                 self.collection_settings
                     .get(name)
                     .map_or(DataOption::default(), |f| f.clone()),
@@ -113,14 +252,22 @@ impl Database {
             id,
             name,
         );
+        collection.set_schema(schema);
+        collection.load_fulltext(&dir);
+        collection.load_composite(&dir);
+        collection.cipher = self.cipher.clone();
+        collection.load_history(&dir);
+        collection.audit = self.audit.clone();
+        collection.read_only = self.read_only;
         self.collections_map.insert(name.to_string(), id);
         self.collections.insert(id, collection);
+        Ok(())
     }
 
     fn collection_by_name_or_create(&mut self, name: &str) -> NonZeroI32 {
         let mut max_id = 0;
         if self.collections_dir.exists() {
-            for d in self.collections_dir.read_dir().unwrap().into_iter() {
+            for d in self.collections_dir.read_dir().unwrap() {
                 let d = d.unwrap();
                 if d.file_type().unwrap().is_dir() {
                     if let Some(fname) = d.file_name().to_str() {
@@ -131,7 +278,7 @@ impl Database {
                             }
                             if s[1] == name {
                                 let max_id = NonZeroI32::new(max_id).unwrap();
-                                self.create_collection(max_id, name, d.path());
+                                self.create_collection(max_id, name, d.path()).unwrap();
                                 return max_id;
                             }
                         }
@@ -144,7 +291,8 @@ impl Database {
             let mut collecion_dir = self.collections_dir.clone();
             collecion_dir.push(&(collection_id.to_string() + "_" + name));
             collecion_dir
-        });
+        })
+        .unwrap();
         collection_id
     }
 }
@@ -0,0 +1,37 @@
+use std::num::NonZeroU32;
+
+use hashbrown::HashMap;
+use versatile_data::{idx_binary::AvltrieeSearch, FieldName};
+
+use crate::{Collection, FieldError, Schema};
+
+/// A struct stored as the fields of a collection row. Usually derived with `#[derive(CollectionRecord)]` from the `derive` feature.
+pub trait CollectionRecord: Sized {
+    /// The fields of the struct. Fields that are not `Option` are required.
+    fn schema() -> Schema;
+
+    /// Fields that are `None` are left out, so an update keeps their stored values.
+    fn to_fields(&self) -> HashMap<FieldName, Vec<u8>>;
+
+    fn from_fields(fields: &HashMap<FieldName, Vec<u8>>) -> Result<Self, FieldError>;
+}
+
+impl Collection {
+    /// Reads a row as `R`.
+    pub fn record<R: CollectionRecord>(&self, row: NonZeroU32) -> Result<R, FieldError> {
+        R::from_fields(
+            &R::schema()
+                .fields
+                .into_keys()
+                .filter_map(|field_name| {
+                    self.data()
+                        .fields()
+                        .get(&field_name)
+                        .and_then(|field| field.value(row))
+                        .and_then(|bytes| self.decrypt(&field_name, bytes))
+                        .map(|bytes| (field_name, bytes.into_owned()))
+                })
+                .collect(),
+        )
+    }
+}
@@ -3,13 +3,26 @@ use std::{
     num::{NonZeroI32, NonZeroU32},
 };
 
-use serde::Serialize;
+use serde::{Deserialize, Deserializer, Serialize};
 
-#[derive(Clone, Debug, Serialize, Hash, Default)]
+#[derive(Clone, Debug, Serialize, Hash, Default, PartialEq, Eq)]
 pub struct CollectionRow {
     collection_id: Option<NonZeroI32>,
     row: Option<NonZeroU32>,
 }
+impl<'de> Deserialize<'de> for CollectionRow {
+    /// Both ids are required and non-zero, so a deserialized row always points at a real row.
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(rename = "CollectionRow", deny_unknown_fields)]
+        struct Ids {
+            collection_id: NonZeroI32,
+            row: NonZeroU32,
+        }
+        let ids = Ids::deserialize(deserializer)?;
+        Ok(Self::new(ids.collection_id, ids.row))
+    }
+}
 impl PartialOrd for CollectionRow {
     #[inline(always)]
     fn partial_cmp(&self, other: &CollectionRow) -> Option<Ordering> {
@@ -27,13 +40,6 @@ impl Ord for CollectionRow {
         }
     }
 }
-impl PartialEq for CollectionRow {
-    fn eq(&self, other: &CollectionRow) -> bool {
-        self.collection_id == other.collection_id && self.row == other.row
-    }
-}
-impl Eq for CollectionRow {}
-
 impl CollectionRow {
     pub fn new(collection_id: NonZeroI32, row: NonZeroU32) -> Self {
         Self {
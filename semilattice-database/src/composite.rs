@@ -0,0 +1,346 @@
+use std::{
+    num::{NonZeroI32, NonZeroU32},
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+use versatile_data::{
+    idx_binary::{AvltrieeSearch, AvltrieeUpdate, IdxBinary},
+    search::Field,
+    AvltrieeIter, CustomOrderKey, CustomSort, FieldName, Order, RowSet,
+};
+
+use crate::{Collection, Condition, Database, WriteError};
+
+const FIELDS_FILE: &str = "fields.toml";
+
+const SEPARATOR: u8 = 0;
+const ESCAPE: u8 = 1;
+/// Never appears in a key, so it sorts after every key starting with the same prefix.
+const UPPER: u8 = 0xff;
+
+#[derive(Serialize, Deserialize)]
+struct CompositeFields {
+    fields: Vec<FieldName>,
+}
+
+/// Index over the values of an ordered list of fields.
+/// Each row's key is the values joined in field order, so equal leading fields are adjacent and sorted by the next one.
+/// Rows missing any of the fields are not indexed.
+pub struct CompositeIndex {
+    fields: Vec<FieldName>,
+    index: IdxBinary,
+}
+impl CompositeIndex {
+    pub fn new(dir: &Path, fields: Vec<FieldName>) -> Self {
+        if !dir.exists() {
+            std::fs::create_dir_all(dir).unwrap();
+        }
+        Self {
+            fields,
+            index: IdxBinary::new_ext(dir.join("key"), 1),
+        }
+    }
+
+    pub fn fields(&self) -> &[FieldName] {
+        &self.fields
+    }
+
+    /// Rows in key order.
+    pub fn rows(&self, desc: bool) -> Vec<NonZeroU32> {
+        if desc {
+            self.index.as_ref().desc_iter().collect()
+        } else {
+            self.index.as_ref().iter().collect()
+        }
+    }
+
+    /// Upper bound of the indexed rows, the number of rows a walk of the index reads.
+    fn rows_bound(&self) -> usize {
+        self.index.as_ref().rows_count() as usize
+    }
+
+    /// Rows whose leading fields equal `prefix` and whose next field is within `range`.
+    pub fn search(&self, prefix: &[&[u8]], range: Option<&Field>) -> RowSet {
+        let mut start = key(prefix);
+        let mut end = start.clone();
+        match range {
+            Some(Field::Range(min, max)) => {
+                escape(&mut start, min);
+                escape(&mut end, max);
+                end.push(ESCAPE);
+            }
+            Some(Field::Min(min)) => {
+                escape(&mut start, min);
+                end.push(UPPER);
+            }
+            Some(Field::Max(max)) => {
+                escape(&mut end, max);
+                end.push(ESCAPE);
+            }
+            _ => end.push(UPPER),
+        }
+        AvltrieeIter::range_asc(&self.index, start.as_slice(), end.as_slice()).collect()
+    }
+
+    fn update(&mut self, row: NonZeroU32, key: Option<Vec<u8>>) {
+        if let Some(key) = key {
+            self.index.update(row, &key);
+        } else {
+            self.index.delete(row);
+        }
+    }
+
+    fn delete(&mut self, row: NonZeroU32) {
+        self.index.delete(row);
+    }
+}
+
+/// Separators and escapes are escaped so that values never run into the next field.
+fn escape(key: &mut Vec<u8>, value: &[u8]) {
+    for b in value {
+        match *b {
+            SEPARATOR => key.extend([ESCAPE, 1]),
+            ESCAPE => key.extend([ESCAPE, 2]),
+            UPPER => key.extend([ESCAPE, 3]),
+            b => key.push(b),
+        }
+    }
+}
+
+fn key(values: &[&[u8]]) -> Vec<u8> {
+    let mut key = vec![];
+    for value in values {
+        push_value(&mut key, value);
+    }
+    key
+}
+
+fn push_value(key: &mut Vec<u8>, value: &[u8]) {
+    escape(key, value);
+    key.push(SEPARATOR);
+}
+
+/// How a composite index answers part of a list of conditions.
+pub(crate) struct CompositePlan<'a> {
+    pub name: &'a str,
+    pub index: &'a CompositeIndex,
+    prefix: Vec<&'a [u8]>,
+    range: Option<&'a Field>,
+    /// Positions of the conditions the index answers.
+    pub covered: Vec<usize>,
+}
+impl CompositePlan<'_> {
+    pub fn rows(&self) -> RowSet {
+        self.index.search(&self.prefix, self.range)
+    }
+}
+
+impl Collection {
+    pub fn composite(&self, name: &str) -> Option<&CompositeIndex> {
+        self.composite.get(name)
+    }
+
+    /// The composite index covering the most conditions with equality on its leading fields and an optional range on the next one.
+    /// Only worth it when it covers more than one condition.
+    pub(crate) fn composite_plan<'a>(
+        &'a self,
+        conditions: &'a [Condition],
+    ) -> Option<CompositePlan<'a>> {
+        let mut best: Option<CompositePlan> = None;
+        for (name, index) in self.composite.iter() {
+            let mut plan = CompositePlan {
+                name,
+                index,
+                prefix: vec![],
+                range: None,
+                covered: vec![],
+            };
+            for field_name in index.fields() {
+                let field_condition = |f: fn(&Field) -> bool| {
+                    conditions.iter().enumerate().find_map(|(i, c)| match c {
+                        Condition::Field(name, condition) if name == field_name && f(condition) => {
+                            Some((i, condition))
+                        }
+                        _ => None,
+                    })
+                };
+                if let Some((i, Field::Match(value))) =
+                    field_condition(|c| matches!(c, Field::Match(_)))
+                {
+                    plan.prefix.push(value);
+                    plan.covered.push(i);
+                    continue;
+                }
+                if let Some((i, range)) = field_condition(|c| {
+                    matches!(c, Field::Range(_, _) | Field::Min(_) | Field::Max(_))
+                }) {
+                    plan.range = Some(range);
+                    plan.covered.push(i);
+                }
+                break;
+            }
+            if plan.covered.len() > 1
+                && best
+                    .as_ref()
+                    .is_none_or(|best| best.covered.len() < plan.covered.len())
+            {
+                best = Some(plan);
+            }
+        }
+        best
+    }
+
+    /// Sorts `rows` by walking a composite index whose leading fields are the ordered fields, all in the same direction.
+    /// Returns `None` when no index fits, some of the rows are not indexed,
+    /// or `rows` are so few that sorting them one by one reads less than walking the index.
+    pub(crate) fn composite_sort<C: CustomSort>(
+        &self,
+        rows: &RowSet,
+        orders: &[Order<C>],
+    ) -> Option<Vec<NonZeroU32>> {
+        let desc = matches!(orders.first()?, Order::Desc(_));
+        let order_fields = orders
+            .iter()
+            .map(|order| match order {
+                Order::Asc(CustomOrderKey::Field(field_name)) if !desc => Some(field_name),
+                Order::Desc(CustomOrderKey::Field(field_name)) if desc => Some(field_name),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()?;
+        let index = self.composite.values().find(|index| {
+            index.fields.len() >= order_fields.len()
+                && index
+                    .fields
+                    .iter()
+                    .zip(order_fields.iter())
+                    .all(|(a, b)| a == *b)
+        })?;
+        let comparisons = rows.len() * (rows.len().max(1).ilog2() as usize + 1);
+        if comparisons < index.rows_bound() {
+            return None;
+        }
+        let sorted: Vec<_> = index
+            .rows(desc)
+            .into_iter()
+            .filter(|row| rows.contains(row))
+            .collect();
+        (sorted.len() == rows.len()).then_some(sorted)
+    }
+
+    pub(crate) fn load_composite(&mut self, dir: &Path) {
+        let dir = dir.join("composite");
+        if let Ok(dir) = dir.read_dir() {
+            for d in dir.into_iter().flatten() {
+                if let (Some(name), Ok(fields)) = (
+                    d.file_name().to_str(),
+                    std::fs::read_to_string(d.path().join(FIELDS_FILE)),
+                ) {
+                    let fields: CompositeFields = toml::from_str(&fields).unwrap();
+                    self.composite.insert(
+                        name.to_owned(),
+                        CompositeIndex::new(&d.path(), fields.fields),
+                    );
+                }
+            }
+        }
+    }
+
+    /// Re-reads the indexed fields of `row` after a write.
+    pub(crate) fn update_composite(&mut self, row: NonZeroU32) {
+        let keys: Vec<_> = self
+            .composite
+            .iter()
+            .map(|(name, index)| {
+                let values = index
+                    .fields
+                    .iter()
+                    .map(|field_name| {
+                        self.data()
+                            .fields()
+                            .get(field_name)
+                            .and_then(|field| field.value(row))
+                    })
+                    .collect::<Option<Vec<_>>>();
+                (name.clone(), values.map(|values| key(&values)))
+            })
+            .collect();
+        for (name, key) in keys {
+            if let Some(index) = self.composite.get_mut(&name) {
+                index.update(row, key);
+            }
+        }
+    }
+
+    pub(crate) fn delete_composite(&mut self, row: NonZeroU32) {
+        for index in self.composite.values_mut() {
+            index.delete(row);
+        }
+    }
+}
+
+impl Database {
+    /// Creates an index over the values of `fields` in order and indexes the rows already in the collection.
+    /// An existing index of the same name is rebuilt.
+    /// Fails with [WriteError::Encrypted] on an encrypted database, where the index would store the values in plain text.
+    pub fn create_composite_index(
+        &mut self,
+        collection_id: NonZeroI32,
+        name: &str,
+        fields: Vec<FieldName>,
+    ) -> Result<(), WriteError> {
+        self.check_writable()?;
+        if self.is_encrypted() {
+            return Err(WriteError::Encrypted);
+        }
+        let dir = self.composite_dir(collection_id, name);
+        if let Some(collection) = self.collections.get_mut(&collection_id) {
+            if dir.exists() {
+                std::fs::remove_dir_all(&dir).unwrap();
+            }
+            std::fs::create_dir_all(&dir).unwrap();
+            std::fs::write(
+                dir.join(FIELDS_FILE),
+                toml::to_string(&CompositeFields {
+                    fields: fields.clone(),
+                })
+                .unwrap(),
+            )
+            .unwrap();
+
+            collection
+                .composite
+                .insert(name.to_owned(), CompositeIndex::new(&dir, fields));
+            for row in collection.data().all().into_iter() {
+                collection.update_composite(row);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn drop_composite_index(
+        &mut self,
+        collection_id: NonZeroI32,
+        name: &str,
+    ) -> Result<(), WriteError> {
+        self.check_writable()?;
+        let dir = self.composite_dir(collection_id, name);
+        if let Some(collection) = self.collections.get_mut(&collection_id) {
+            if collection.composite.remove(name).is_some() && dir.exists() {
+                std::fs::remove_dir_all(&dir).unwrap();
+            }
+        }
+        Ok(())
+    }
+
+    fn composite_dir(&self, collection_id: NonZeroI32, name: &str) -> std::path::PathBuf {
+        let collection_name = self
+            .collections
+            .get(&collection_id)
+            .map_or("", |collection| collection.name());
+        self.collections_dir
+            .join(collection_id.to_string() + "_" + collection_name)
+            .join("composite")
+            .join(name)
+    }
+}
@@ -0,0 +1,311 @@
+use std::{borrow::Cow, cmp::Ordering, io, num::NonZeroU32, path::Path};
+
+#[cfg(feature = "encryption")]
+use std::sync::Arc;
+
+#[cfg(feature = "encryption")]
+use aes_gcm_siv::{
+    aead::{Aead, KeyInit, Payload},
+    Aes256GcmSiv, Nonce,
+};
+use hashbrown::HashMap;
+use versatile_data::{
+    idx_binary::{AvltrieeSearch, IdxBinary},
+    search::Field,
+    CustomOrderKey, CustomSort, FieldName, Order, RowSet,
+};
+
+use crate::{Collection, Database};
+
+/// Holds a value encrypted with the key, so that opening with another key fails instead of reading garbage.
+const CHECK_FILE: &str = "encryption";
+#[cfg(feature = "encryption")]
+const CHECK_TEXT: &[u8] = b"semilattice-database";
+/// Relation keys are encrypted under the same context wherever they are stored, so that equal keys stay equal.
+const RELATION_KEY_CONTEXT: &[u8] = b"relation\0key";
+
+/// The 256-bit key of an encrypted database, see [Database::open_encrypted]. Keep it somewhere other than the database directory.
+#[cfg(feature = "encryption")]
+#[derive(Clone)]
+pub struct EncryptionKey([u8; 32]);
+#[cfg(feature = "encryption")]
+impl EncryptionKey {
+    pub fn new(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+}
+#[cfg(feature = "encryption")]
+impl From<[u8; 32]> for EncryptionKey {
+    fn from(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+}
+
+/// AES-256-GCM-SIV under the key of an encrypted database.
+/// The nonce is fixed so that a value encrypts to the same bytes every time under the same context, which is what lets fields be looked up by equality.
+/// Each value is authenticated together with its context, so it cannot be moved to another field unnoticed.
+#[derive(Clone)]
+pub struct Cipher(Inner);
+
+#[cfg(feature = "encryption")]
+type Inner = Arc<Aes256GcmSiv>;
+#[cfg(not(feature = "encryption"))]
+#[derive(Clone)]
+enum Inner {}
+
+impl Cipher {
+    #[cfg(feature = "encryption")]
+    pub(crate) fn new(key: &EncryptionKey) -> Self {
+        Self(Arc::new(Aes256GcmSiv::new_from_slice(&key.0).unwrap()))
+    }
+
+    /// 16 bytes longer than `value`.
+    #[cfg_attr(not(feature = "encryption"), allow(unused_variables))]
+    pub fn encrypt(&self, context: &[u8], value: &[u8]) -> Vec<u8> {
+        #[cfg(feature = "encryption")]
+        return self
+            .0
+            .encrypt(
+                Nonce::from_slice(&[0; 12]),
+                Payload {
+                    msg: value,
+                    aad: context,
+                },
+            )
+            .unwrap();
+        #[cfg(not(feature = "encryption"))]
+        match self.0 {}
+    }
+
+    /// None if `value` was not encrypted with this key and context or has been altered.
+    #[cfg_attr(not(feature = "encryption"), allow(unused_variables))]
+    pub fn decrypt(&self, context: &[u8], value: &[u8]) -> Option<Vec<u8>> {
+        #[cfg(feature = "encryption")]
+        return self
+            .0
+            .decrypt(
+                Nonce::from_slice(&[0; 12]),
+                Payload {
+                    msg: value,
+                    aad: context,
+                },
+            )
+            .ok();
+        #[cfg(not(feature = "encryption"))]
+        match self.0 {}
+    }
+
+    /// A relation key as it is stored in the relation index, the audit log and sessions.
+    pub fn encrypt_relation_key(&self, key: &str) -> Vec<u8> {
+        self.encrypt(RELATION_KEY_CONTEXT, key.as_bytes())
+    }
+
+    /// None if `bytes` is not a relation key encrypted with this key.
+    pub fn decrypt_relation_key(&self, bytes: &[u8]) -> Option<String> {
+        self.decrypt(RELATION_KEY_CONTEXT, bytes)
+            .and_then(|key| String::from_utf8(key).ok())
+    }
+}
+
+/// Fails unless `dir` is new or was encrypted with the key of `cipher`. A new database is marked as encrypted unless `read_only`.
+#[cfg(feature = "encryption")]
+pub(crate) fn check_key(dir: &Path, cipher: &Cipher, read_only: bool) -> io::Result<()> {
+    let path = dir.join(CHECK_FILE);
+    if path.exists() {
+        if cipher.decrypt(b"", &std::fs::read(&path)?).as_deref() != Some(CHECK_TEXT) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("wrong encryption key for {}", dir.display()),
+            ));
+        }
+    } else if read_only || dir.join("collection").exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is not encrypted", dir.display()),
+        ));
+    } else {
+        std::fs::write(path, cipher.encrypt(b"", CHECK_TEXT))?;
+    }
+    Ok(())
+}
+
+/// Fails if `dir` holds an encrypted database.
+pub(crate) fn check_unencrypted(dir: &Path) -> io::Result<()> {
+    if dir.join(CHECK_FILE).exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is encrypted and needs its key", dir.display()),
+        ));
+    }
+    Ok(())
+}
+
+impl Collection {
+    pub fn is_encrypted(&self) -> bool {
+        self.cipher.is_some()
+    }
+
+    /// Values of different collections and fields are encrypted under different contexts.
+    fn context(&self, field_name: &FieldName) -> Vec<u8> {
+        [
+            b"collection\0",
+            self.name().as_bytes(),
+            b"\0",
+            field_name.as_bytes(),
+        ]
+        .concat()
+    }
+
+    /// `value` as it is stored in the field.
+    pub(crate) fn encrypt_value<'a>(
+        &self,
+        field_name: &FieldName,
+        value: &'a [u8],
+    ) -> Cow<'a, [u8]> {
+        match &self.cipher {
+            Some(cipher) => Cow::Owned(cipher.encrypt(&self.context(field_name), value)),
+            None => Cow::Borrowed(value),
+        }
+    }
+
+    pub(crate) fn encrypt_fields(
+        &self,
+        fields: HashMap<FieldName, Vec<u8>>,
+    ) -> HashMap<FieldName, Vec<u8>> {
+        if self.cipher.is_none() {
+            return fields;
+        }
+        fields
+            .into_iter()
+            .map(|(field_name, value)| {
+                let value = self.encrypt_value(&field_name, &value).into_owned();
+                (field_name, value)
+            })
+            .collect()
+    }
+
+    /// Reads a value as stored in this collection, such as one from its history or audit entries.
+    /// Returns `bytes` as they are if the database is not encrypted and None if they fail authentication.
+    pub fn decrypt<'a>(&self, field_name: &FieldName, bytes: &'a [u8]) -> Option<Cow<'a, [u8]>> {
+        match &self.cipher {
+            Some(cipher) => cipher
+                .decrypt(&self.context(field_name), bytes)
+                .map(Cow::Owned),
+            None => Some(Cow::Borrowed(bytes)),
+        }
+    }
+
+    /// [Data::field_bytes](versatile_data::Data::field_bytes) decrypted, empty if the row has no such field. Panics if the stored value fails authentication.
+    pub fn decrypted_bytes(&self, row: NonZeroU32, field_name: &FieldName) -> Cow<'_, [u8]> {
+        let Some(bytes) = self
+            .data()
+            .fields()
+            .get(field_name)
+            .and_then(|field| field.value(row))
+        else {
+            return Cow::Borrowed(b"");
+        };
+        self.decrypt(field_name, bytes).unwrap_or_else(|| {
+            panic!(
+                "{} of row {} in {} fails authentication",
+                field_name.as_str(),
+                row,
+                self.name()
+            )
+        })
+    }
+
+    /// Sorts `rows` comparing the decrypted values of the fields in `orders`, or None if the collection is not encrypted or `orders` sort by no field.
+    /// A value that fails authentication sorts as empty.
+    pub(crate) fn decrypted_sort<C: CustomSort>(
+        &self,
+        rows: &RowSet,
+        orders: &[Order<C>],
+    ) -> Option<Vec<NonZeroU32>> {
+        if self.cipher.is_none()
+            || !orders.iter().any(|order| {
+                matches!(
+                    order,
+                    Order::Asc(CustomOrderKey::Field(_)) | Order::Desc(CustomOrderKey::Field(_))
+                )
+            })
+        {
+            return None;
+        }
+        let data = self.data();
+        let values: HashMap<&FieldName, HashMap<NonZeroU32, Cow<[u8]>>> = orders
+            .iter()
+            .filter_map(|order| match order {
+                Order::Asc(CustomOrderKey::Field(field_name))
+                | Order::Desc(CustomOrderKey::Field(field_name)) => Some(field_name),
+                _ => None,
+            })
+            .map(|field_name| {
+                let field = data.fields().get(field_name);
+                (
+                    field_name,
+                    rows.iter()
+                        .map(|row| {
+                            let value = field
+                                .and_then(|field| field.value(*row))
+                                .and_then(|bytes| self.decrypt(field_name, bytes))
+                                .unwrap_or_default();
+                            (*row, value)
+                        })
+                        .collect(),
+                )
+            })
+            .collect();
+        let compare = |key: &CustomOrderKey<C>, a: NonZeroU32, b: NonZeroU32| match key {
+            CustomOrderKey::Serial => data.serial(a).cmp(data.serial(b)),
+            CustomOrderKey::Row => a.cmp(&b),
+            CustomOrderKey::TermBegin => data.term_begin(a).cmp(&data.term_begin(b)),
+            CustomOrderKey::TermEnd => data.term_end(a).cmp(&data.term_end(b)),
+            CustomOrderKey::LastUpdated => data.last_updated(a).cmp(&data.last_updated(b)),
+            CustomOrderKey::Field(field_name) => {
+                let values = &values[field_name];
+                IdxBinary::cmp(&values[&a], &values[&b])
+            }
+            CustomOrderKey::Custom(custom_order) => custom_order.compare(a, b),
+        };
+        let mut sorted: Vec<NonZeroU32> = rows.iter().cloned().collect();
+        sorted.sort_by(|a, b| {
+            orders
+                .iter()
+                .map(|order| match order {
+                    Order::Asc(key) => compare(key, *a, *b),
+                    Order::Desc(key) => compare(key, *b, *a),
+                })
+                .find(|ord| ord.is_ne())
+                .unwrap_or(Ordering::Equal)
+        });
+        Some(sorted)
+    }
+
+    /// The condition to look up through the field index, or None if the rows have to be decrypted to check it.
+    /// Encrypted values can only be looked up by equality.
+    pub(crate) fn index_condition<'a>(
+        &self,
+        field_name: &FieldName,
+        condition: &'a Field,
+    ) -> Option<Cow<'a, Field>> {
+        match (&self.cipher, condition) {
+            (None, _) => Some(Cow::Borrowed(condition)),
+            (Some(_), Field::Match(value)) => Some(Cow::Owned(Field::Match(
+                self.encrypt_value(field_name, value).into_owned(),
+            ))),
+            (Some(_), _) => None,
+        }
+    }
+}
+
+impl Database {
+    /// The cipher field values are encrypted with, if the database is encrypted.
+    pub fn cipher(&self) -> Option<&Cipher> {
+        self.cipher.as_ref()
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        self.cipher.is_some()
+    }
+}
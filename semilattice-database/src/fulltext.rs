@@ -0,0 +1,395 @@
+mod index;
+
+pub use index::FullTextIndex;
+
+use std::{cmp::Ordering, num::NonZeroI32, num::NonZeroU32, path::Path};
+
+use hashbrown::{HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+use versatile_data::{idx_binary::AvltrieeSearch, CustomSort, FieldName, RowSet};
+
+use crate::{Collection, Database, WriteError};
+
+const OPTION_FILE: &str = "option.toml";
+
+/// How the values of an indexed field are split into tokens.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct FullTextOption {
+    /// Also split each word into character n-grams of this length, so that a query word matches inside longer words.
+    /// Words not longer than `n` are indexed whole.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ngram: Option<usize>,
+}
+impl FullTextOption {
+    pub fn grams(&self, word: &str) -> Vec<String> {
+        match self.ngram {
+            Some(n) if n > 0 && word.chars().count() > n => {
+                let chars: Vec<_> = word.chars().collect();
+                let mut grams: Vec<String> = vec![];
+                for gram in chars.windows(n).map(|w| w.iter().collect()) {
+                    if !grams.contains(&gram) {
+                        grams.push(gram);
+                    }
+                }
+                grams
+            }
+            _ => vec![word.to_owned()],
+        }
+    }
+}
+
+/// Splits text into lowercased words at every character that is not alphanumeric.
+pub fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+        .collect()
+}
+
+/// A full-text query. The text is tokenized the same way as the indexed values.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FullText {
+    /// Rows containing every word.
+    All(String),
+    /// Rows containing at least one of the words.
+    Any(String),
+    /// Rows containing the words next to each other, in order.
+    Phrase(String),
+}
+impl FullText {
+    pub fn text(&self) -> &str {
+        match self {
+            Self::All(text) | Self::Any(text) | Self::Phrase(text) => text,
+        }
+    }
+
+    /// Evaluates the query over postings of `(row, word position)` for each token.
+    pub(crate) fn evaluate(
+        &self,
+        option: &FullTextOption,
+        postings: impl Fn(&str) -> Vec<(NonZeroU32, u32)>,
+    ) -> RowSet {
+        let words: Vec<_> = tokenize(self.text())
+            .iter()
+            .map(|word| word_matches(option, word, &postings))
+            .collect();
+        let Some((first, rest)) = words.split_first() else {
+            return RowSet::default();
+        };
+        match self {
+            Self::All(_) => {
+                let mut rows: RowSet = first.iter().map(|(row, _)| *row).collect();
+                for word in rest.iter() {
+                    let word: HashSet<_> = word.iter().map(|(row, _)| *row).collect();
+                    rows.retain(|row| word.contains(row));
+                }
+                rows
+            }
+            Self::Any(_) => words.iter().flatten().map(|(row, _)| *row).collect(),
+            Self::Phrase(_) => first
+                .iter()
+                .filter(|(row, position)| {
+                    rest.iter()
+                        .enumerate()
+                        .all(|(i, word)| word.contains(&(*row, position + i as u32 + 1)))
+                })
+                .map(|(row, _)| *row)
+                .collect(),
+        }
+    }
+
+    /// Upper bound of the matching rows, from the posting counts of the index.
+    pub(crate) fn estimate(&self, index: &FullTextIndex) -> usize {
+        let words = tokenize(self.text()).into_iter().map(|word| {
+            index
+                .option()
+                .grams(&word)
+                .iter()
+                .map(|gram| index.count(gram))
+                .min()
+                .unwrap_or(0)
+        });
+        match self {
+            Self::Any(_) => words.sum(),
+            Self::All(_) | Self::Phrase(_) => words.min().unwrap_or(0),
+        }
+    }
+
+    /// Checks a single value without an index.
+    pub fn is_match_text(&self, option: &FullTextOption, text: &str) -> bool {
+        let row = NonZeroU32::MIN;
+        let postings = text_postings(option, row, text);
+        !self
+            .evaluate(option, |token| {
+                postings.get(token).cloned().unwrap_or_default()
+            })
+            .is_empty()
+    }
+
+    /// TF-IDF of the query tokens for each of `rows`, where `total` is the number of rows in the collection.
+    fn scores(
+        &self,
+        option: &FullTextOption,
+        rows: &RowSet,
+        total: usize,
+        postings: impl Fn(&str) -> Vec<(NonZeroU32, u32)>,
+    ) -> HashMap<NonZeroU32, f64> {
+        let mut scores: HashMap<_, _> = rows.iter().map(|row| (*row, 0.0)).collect();
+        for word in tokenize(self.text()).iter() {
+            for gram in option.grams(word).iter() {
+                let postings = postings(gram);
+                let df = postings
+                    .iter()
+                    .map(|(row, _)| row)
+                    .collect::<HashSet<_>>()
+                    .len();
+                if df > 0 {
+                    let idf = (1.0 + total.max(df) as f64 / df as f64).ln();
+                    for (row, _) in postings.iter() {
+                        if let Some(score) = scores.get_mut(row) {
+                            *score += idf;
+                        }
+                    }
+                }
+            }
+        }
+        scores
+    }
+}
+
+fn word_matches(
+    option: &FullTextOption,
+    word: &str,
+    postings: &impl Fn(&str) -> Vec<(NonZeroU32, u32)>,
+) -> HashSet<(NonZeroU32, u32)> {
+    let mut grams = option.grams(word).into_iter();
+    let Some(first) = grams.next() else {
+        return HashSet::new();
+    };
+    let mut matches: HashSet<_> = postings(&first).into_iter().collect();
+    for gram in grams {
+        let other: HashSet<_> = postings(&gram).into_iter().collect();
+        matches.retain(|m| other.contains(m));
+    }
+    matches
+}
+
+fn text_postings(
+    option: &FullTextOption,
+    row: NonZeroU32,
+    text: &str,
+) -> HashMap<String, Vec<(NonZeroU32, u32)>> {
+    let mut postings: HashMap<String, Vec<_>> = HashMap::new();
+    for (position, word) in tokenize(text).iter().enumerate() {
+        for gram in option.grams(word) {
+            postings
+                .entry(gram)
+                .or_default()
+                .push((row, position as u32));
+        }
+    }
+    postings
+}
+
+/// Relevance of each row of a result to a full-text query, usable as `CustomOrderKey::Custom`.
+#[derive(Debug, Clone, Default)]
+pub struct FullTextScores(HashMap<NonZeroU32, f64>);
+impl FullTextScores {
+    pub fn get(&self, row: NonZeroU32) -> f64 {
+        self.0.get(&row).cloned().unwrap_or(0.0)
+    }
+}
+impl CustomSort for FullTextScores {
+    fn compare(&self, a: NonZeroU32, b: NonZeroU32) -> Ordering {
+        self.get(a).total_cmp(&self.get(b))
+    }
+
+    fn asc(&self) -> Vec<NonZeroU32> {
+        let mut rows: Vec<_> = self.0.keys().cloned().collect();
+        rows.sort_by(|a, b| self.compare(*a, *b).then(a.cmp(b)));
+        rows
+    }
+
+    fn desc(&self) -> Vec<NonZeroU32> {
+        let mut rows: Vec<_> = self.0.keys().cloned().collect();
+        rows.sort_by(|a, b| self.compare(*b, *a).then(a.cmp(b)));
+        rows
+    }
+}
+
+impl Collection {
+    pub fn fulltext(&self, field_name: &FieldName) -> Option<&FullTextIndex> {
+        self.fulltext.get(field_name)
+    }
+
+    /// Rows matching the query, through the field's index or by reading every value when it has none.
+    pub fn fulltext_search(&self, field_name: &FieldName, query: &FullText) -> RowSet {
+        if let Some(index) = self.fulltext(field_name) {
+            query.evaluate(index.option(), |token| index.postings(token))
+        } else {
+            let option = FullTextOption::default();
+            let postings = self.scan_postings(field_name, &option, &self.data().all());
+            query.evaluate(&option, |token| {
+                postings.get(token).cloned().unwrap_or_default()
+            })
+        }
+    }
+
+    /// Scores `rows` against the query. Rows without any query token score 0.
+    pub fn fulltext_scores(
+        &self,
+        field_name: &FieldName,
+        query: &FullText,
+        rows: &RowSet,
+    ) -> FullTextScores {
+        let total = self.rows_estimate();
+        FullTextScores(if let Some(index) = self.fulltext(field_name) {
+            query.scores(index.option(), rows, total, |token| index.postings(token))
+        } else {
+            let option = FullTextOption::default();
+            let postings = self.scan_postings(field_name, &option, rows);
+            query.scores(&option, rows, rows.len(), |token| {
+                postings.get(token).cloned().unwrap_or_default()
+            })
+        })
+    }
+
+    /// The option of the field's index, or the default that unindexed fields are matched with.
+    pub(crate) fn fulltext_option(&self, field_name: &FieldName) -> FullTextOption {
+        self.fulltext(field_name)
+            .map_or_else(FullTextOption::default, |index| index.option().clone())
+    }
+
+    fn scan_postings(
+        &self,
+        field_name: &FieldName,
+        option: &FullTextOption,
+        rows: &RowSet,
+    ) -> HashMap<String, Vec<(NonZeroU32, u32)>> {
+        let mut postings: HashMap<String, Vec<_>> = HashMap::new();
+        if let Some(field) = self.data().fields().get(field_name) {
+            for row in rows.iter() {
+                if let Some(bytes) = field.value(*row) {
+                    for (token, p) in text_postings(option, *row, &String::from_utf8_lossy(bytes)) {
+                        postings.entry(token).or_default().extend(p);
+                    }
+                }
+            }
+        }
+        postings
+    }
+
+    pub(crate) fn load_fulltext(&mut self, dir: &Path) {
+        let dir = dir.join("fulltext");
+        if let Ok(dir) = dir.read_dir() {
+            for d in dir.into_iter().flatten() {
+                if let Some(field_name) = d.file_name().to_str() {
+                    let option = std::fs::read_to_string(d.path().join(OPTION_FILE)).map_or_else(
+                        |_| FullTextOption::default(),
+                        |s| toml::from_str(&s).unwrap(),
+                    );
+                    self.fulltext.insert(
+                        FieldName::new(field_name.into()),
+                        FullTextIndex::new(&d.path(), option),
+                    );
+                }
+            }
+        }
+    }
+
+    pub(crate) fn update_fulltext(&mut self, row: NonZeroU32, texts: Vec<(FieldName, String)>) {
+        for (field_name, text) in texts {
+            if let Some(index) = self.fulltext.get_mut(&field_name) {
+                index.update(row, &text);
+            }
+        }
+    }
+
+    pub(crate) fn fulltext_texts<'a>(
+        &self,
+        fields: impl IntoIterator<Item = (&'a FieldName, &'a Vec<u8>)>,
+    ) -> Vec<(FieldName, String)> {
+        fields
+            .into_iter()
+            .filter(|(field_name, _)| self.fulltext.contains_key(*field_name))
+            .map(|(field_name, value)| {
+                (
+                    FieldName::clone(field_name),
+                    String::from_utf8_lossy(value).into_owned(),
+                )
+            })
+            .collect()
+    }
+
+    pub(crate) fn delete_fulltext(&mut self, row: NonZeroU32) {
+        for index in self.fulltext.values_mut() {
+            index.delete(row);
+        }
+    }
+}
+
+impl Database {
+    /// Creates a full-text index on a field and indexes the rows already in the collection.
+    /// An existing index on the field is rebuilt with the new option.
+    /// Fails with [WriteError::Encrypted] on an encrypted database, where the index would store the words in plain text.
+    pub fn create_fulltext_index(
+        &mut self,
+        collection_id: NonZeroI32,
+        field_name: FieldName,
+        option: FullTextOption,
+    ) -> Result<(), WriteError> {
+        self.check_writable()?;
+        if self.is_encrypted() {
+            return Err(WriteError::Encrypted);
+        }
+        let dir = self.fulltext_dir(collection_id, &field_name);
+        if let Some(collection) = self.collections.get_mut(&collection_id) {
+            if dir.exists() {
+                std::fs::remove_dir_all(&dir).unwrap();
+            }
+            std::fs::create_dir_all(&dir).unwrap();
+            std::fs::write(dir.join(OPTION_FILE), toml::to_string(&option).unwrap()).unwrap();
+
+            let mut index = FullTextIndex::new(&dir, option);
+            if let Some(field) = collection.data().fields().get(&field_name) {
+                for row in collection.data().all().into_iter() {
+                    if let Some(bytes) = field.value(row) {
+                        index.update(row, &String::from_utf8_lossy(bytes));
+                    }
+                }
+            }
+            collection.fulltext.insert(field_name, index);
+        }
+        Ok(())
+    }
+
+    pub fn drop_fulltext_index(
+        &mut self,
+        collection_id: NonZeroI32,
+        field_name: &FieldName,
+    ) -> Result<(), WriteError> {
+        self.check_writable()?;
+        let dir = self.fulltext_dir(collection_id, field_name);
+        if let Some(collection) = self.collections.get_mut(&collection_id) {
+            if collection.fulltext.remove(field_name).is_some() && dir.exists() {
+                std::fs::remove_dir_all(&dir).unwrap();
+            }
+        }
+        Ok(())
+    }
+
+    fn fulltext_dir(
+        &self,
+        collection_id: NonZeroI32,
+        field_name: &FieldName,
+    ) -> std::path::PathBuf {
+        let name = self
+            .collections
+            .get(&collection_id)
+            .map_or("", |collection| collection.name());
+        self.collections_dir
+            .join(collection_id.to_string() + "_" + name)
+            .join("fulltext")
+            .join(field_name.as_str())
+    }
+}
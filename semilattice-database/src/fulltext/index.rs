@@ -0,0 +1,101 @@
+use std::{num::NonZeroU32, path::Path};
+
+use versatile_data::{
+    idx_binary::{AvltrieeSearch, AvltrieeUpdate},
+    IdxBinary, IdxFile, RowFragment,
+};
+
+use super::{tokenize, FullTextOption};
+
+const ALLOCATION_LOT: u32 = 100;
+
+struct FullTextIndexRows {
+    token: IdxFile<u32>,
+    row: IdxFile<u32>,
+    position: IdxFile<u32>,
+}
+
+/// Inverted index of one field. Each posting is a token, the collection row it occurs in and its word position there.
+pub struct FullTextIndex {
+    option: FullTextOption,
+    fragment: RowFragment,
+    tokens: IdxBinary,
+    rows: FullTextIndexRows,
+}
+impl FullTextIndex {
+    pub fn new(dir: &Path, option: FullTextOption) -> Self {
+        if !dir.exists() {
+            std::fs::create_dir_all(dir).unwrap();
+        }
+        Self {
+            option,
+            fragment: RowFragment::new(dir.join("fragment.f")),
+            tokens: IdxBinary::new_ext(dir.join("token_name"), 1),
+            rows: FullTextIndexRows {
+                token: IdxFile::new(dir.join("token.i"), ALLOCATION_LOT),
+                row: IdxFile::new(dir.join("row.i"), ALLOCATION_LOT),
+                position: IdxFile::new(dir.join("position.i"), ALLOCATION_LOT),
+            },
+        }
+    }
+
+    pub fn option(&self) -> &FullTextOption {
+        &self.option
+    }
+
+    /// Replaces the postings of `row` with the tokens of `text`.
+    pub fn update(&mut self, row: NonZeroU32, text: &str) {
+        self.delete(row);
+        for (position, word) in tokenize(text).iter().enumerate() {
+            for gram in self.option.grams(word) {
+                let token = self.tokens.row_or_insert(gram.as_bytes()).get();
+                let position = position as u32;
+                if let Some(posting) = self.fragment.pop() {
+                    self.rows.token.update(posting, &token);
+                    self.rows.row.update(posting, &row.get());
+                    self.rows.position.update(posting, &position);
+                } else {
+                    self.rows.token.insert(&token);
+                    self.rows.row.insert(&row.get());
+                    self.rows.position.insert(&position);
+                }
+            }
+        }
+    }
+
+    pub fn delete(&mut self, row: NonZeroU32) {
+        for posting in self.rows.row.iter_by(&row.get()).collect::<Vec<_>>() {
+            self.rows.token.delete(posting);
+            self.rows.row.delete(posting);
+            self.rows.position.delete(posting);
+            self.fragment.insert_blank(posting);
+        }
+    }
+
+    /// Number of postings of `token`.
+    pub fn count(&self, token: &str) -> usize {
+        self.tokens
+            .row(token.as_bytes())
+            .map_or(0, |token| self.rows.token.iter_by(&token.get()).count())
+    }
+
+    /// The rows and word positions where `token` occurs.
+    pub fn postings(&self, token: &str) -> Vec<(NonZeroU32, u32)> {
+        self.tokens.row(token.as_bytes()).map_or(vec![], |token| {
+            self.rows
+                .token
+                .iter_by(&token.get())
+                .filter_map(|posting| {
+                    if let (Some(row), Some(position)) = (
+                        self.rows.row.value(posting),
+                        self.rows.position.value(posting),
+                    ) {
+                        NonZeroU32::new(*row).map(|row| (row, *position))
+                    } else {
+                        None
+                    }
+                })
+                .collect()
+        })
+    }
+}
@@ -0,0 +1,385 @@
+use std::{
+    num::{NonZeroI32, NonZeroU32},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use hashbrown::HashMap;
+use versatile_data::{
+    idx_binary::{AvltrieeSearch, AvltrieeUpdate},
+    Activity, Field, FieldName, Fields, IdxFile,
+};
+
+use crate::{Cipher, Collection, Database, WriteError};
+
+/// Activity stored for a version that records a delete.
+const DELETED: u8 = u8::MAX;
+
+/// The state of a row right after one of its writes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RowVersion {
+    /// Milliseconds since the UNIX epoch, so that writes in the same second get different times.
+    pub at: u64,
+    /// The write was a delete. The other values are empty.
+    pub deleted: bool,
+    pub activity: Activity,
+    pub term_begin: u64,
+    pub term_end: u64,
+    pub uuid: u128,
+    pub fields: HashMap<FieldName, Vec<u8>>,
+}
+
+/// The time, activity, terms and uuid of a version.
+struct VersionHead {
+    at: u64,
+    activity: u8,
+    term_begin: u64,
+    term_end: u64,
+    uuid: u128,
+}
+impl VersionHead {
+    fn to_bytes(&self) -> Vec<u8> {
+        [
+            &self.at.to_le_bytes()[..],
+            &[self.activity],
+            &self.term_begin.to_le_bytes(),
+            &self.term_end.to_le_bytes(),
+            &self.uuid.to_le_bytes(),
+        ]
+        .concat()
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != 41 {
+            return None;
+        }
+        Some(Self {
+            at: u64::from_le_bytes(bytes[0..8].try_into().unwrap()),
+            activity: bytes[8],
+            term_begin: u64::from_le_bytes(bytes[9..17].try_into().unwrap()),
+            term_end: u64::from_le_bytes(bytes[17..25].try_into().unwrap()),
+            uuid: u128::from_le_bytes(bytes[25..41].try_into().unwrap()),
+        })
+    }
+}
+
+/// In an encrypted collection, the heads of versions are kept encrypted in one field instead of their own indexes.
+struct EncryptedHeads {
+    cipher: Cipher,
+    context: Vec<u8>,
+    heads: Field,
+}
+impl EncryptedHeads {
+    fn head(&self, version_row: NonZeroU32) -> Option<VersionHead> {
+        let bytes = self.heads.value(version_row)?;
+        let head = self
+            .cipher
+            .decrypt(&self.context, bytes)
+            .and_then(|bytes| VersionHead::from_bytes(&bytes));
+        Some(head.unwrap_or_else(|| panic!("history version {} fails authentication", version_row)))
+    }
+}
+
+/// Every version of every row of a collection, appended on each write.
+/// Versions keep all fields of the row, not only the written ones, so any of them can be read on its own.
+pub(crate) struct History {
+    dir: PathBuf,
+    row: IdxFile<u32>,
+    at: IdxFile<u64>,
+    activity: IdxFile<u8>,
+    term_begin: IdxFile<u64>,
+    term_end: IdxFile<u64>,
+    uuid: IdxFile<u128>,
+    fields: Fields,
+    encrypted: Option<EncryptedHeads>,
+}
+impl History {
+    fn new(dir: PathBuf, cipher: Option<Cipher>, collection_name: &str) -> Self {
+        let fields_dir = dir.join("fields");
+        if !fields_dir.exists() {
+            std::fs::create_dir_all(&fields_dir).unwrap();
+        }
+        let mut fields = Fields::new();
+        for d in fields_dir.read_dir().unwrap().flatten() {
+            if d.path().is_dir() {
+                if let Some(name) = d.file_name().to_str() {
+                    fields.insert(FieldName::new(name.into()), Field::new(d.path(), 1));
+                }
+            }
+        }
+        Self {
+            row: IdxFile::new(dir.join("row.i"), 1),
+            at: IdxFile::new(dir.join("at.i"), 1),
+            activity: IdxFile::new(dir.join("activity.i"), 1),
+            term_begin: IdxFile::new(dir.join("term_begin.i"), 1),
+            term_end: IdxFile::new(dir.join("term_end.i"), 1),
+            uuid: IdxFile::new(dir.join("uuid.i"), 1),
+            fields,
+            encrypted: cipher.map(|cipher| {
+                let heads_dir = dir.join("head");
+                std::fs::create_dir_all(&heads_dir).unwrap();
+                EncryptedHeads {
+                    cipher,
+                    context: [b"history\0", collection_name.as_bytes()].concat(),
+                    heads: Field::new(heads_dir, 1),
+                }
+            }),
+            dir,
+        }
+    }
+
+    fn push(&mut self, row: NonZeroU32, version: &RowVersion) {
+        let version_row = self.row.insert(&row.get());
+        let head = VersionHead {
+            at: version.at,
+            activity: if version.deleted {
+                DELETED
+            } else {
+                version.activity as u8
+            },
+            term_begin: version.term_begin,
+            term_end: version.term_end,
+            uuid: version.uuid,
+        };
+        if let Some(encrypted) = &mut self.encrypted {
+            let bytes = encrypted
+                .cipher
+                .encrypt(&encrypted.context, &head.to_bytes());
+            encrypted.heads.update(version_row, &bytes);
+        } else {
+            self.at.update(version_row, &head.at);
+            self.activity.update(version_row, &head.activity);
+            self.term_begin.update(version_row, &head.term_begin);
+            self.term_end.update(version_row, &head.term_end);
+            self.uuid.update(version_row, &head.uuid);
+        }
+        for (name, value) in version.fields.iter() {
+            if !self.fields.contains_key(name) {
+                let dir = self.dir.join("fields").join(name.as_str());
+                std::fs::create_dir_all(&dir).unwrap();
+                self.fields.insert(name.clone(), Field::new(dir, 1));
+            }
+            self.fields
+                .get_mut(name)
+                .unwrap()
+                .update(version_row, value);
+        }
+    }
+
+    fn head(&self, version_row: NonZeroU32) -> Option<VersionHead> {
+        if let Some(encrypted) = &self.encrypted {
+            return encrypted.head(version_row);
+        }
+        Some(VersionHead {
+            at: *self.at.value(version_row)?,
+            activity: *self.activity.value(version_row)?,
+            term_begin: *self.term_begin.value(version_row)?,
+            term_end: *self.term_end.value(version_row)?,
+            uuid: *self.uuid.value(version_row)?,
+        })
+    }
+
+    fn version(&self, version_row: NonZeroU32) -> Option<RowVersion> {
+        let head = self.head(version_row)?;
+        Some(RowVersion {
+            at: head.at,
+            deleted: head.activity == DELETED,
+            activity: if head.activity == Activity::Active as u8 {
+                Activity::Active
+            } else {
+                Activity::Inactive
+            },
+            term_begin: head.term_begin,
+            term_end: head.term_end,
+            uuid: head.uuid,
+            fields: self
+                .fields
+                .iter()
+                .filter_map(|(name, field)| {
+                    field
+                        .value(version_row)
+                        .map(|value| (name.clone(), value.to_vec()))
+                })
+                .collect(),
+        })
+    }
+
+    /// Versions of `row` in the order they were written.
+    fn version_rows(&self, row: NonZeroU32) -> Vec<NonZeroU32> {
+        let mut version_rows: Vec<_> = self.row.iter_by(&row.get()).collect();
+        version_rows.sort();
+        version_rows
+    }
+
+    /// The latest version of every row written at or before `at`.
+    fn version_rows_at(&self, at: u64) -> HashMap<NonZeroU32, NonZeroU32> {
+        let mut latest = HashMap::new();
+        // Encrypted times cannot be looked up, so every version is read.
+        let version_rows: Box<dyn Iterator<Item = NonZeroU32>> = if self.encrypted.is_some() {
+            Box::new(self.row.iter().filter(move |version_row| {
+                self.head(*version_row).is_some_and(|head| head.at <= at)
+            }))
+        } else {
+            Box::new(self.at.iter_to(&at))
+        };
+        for version_row in version_rows {
+            if let Some(row) = self
+                .row
+                .value(version_row)
+                .and_then(|row| NonZeroU32::new(*row))
+            {
+                let v = latest.entry(row).or_insert(version_row);
+                if *v < version_row {
+                    *v = version_row;
+                }
+            }
+        }
+        latest
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
+
+impl Collection {
+    pub fn has_history(&self) -> bool {
+        self.history.is_some()
+    }
+
+    /// Every version of `row` kept since history was enabled, oldest first.
+    /// A row number reused after a delete continues the history of the deleted row.
+    pub fn history(&self, row: NonZeroU32) -> Vec<RowVersion> {
+        self.history.as_ref().map_or(vec![], |history| {
+            history
+                .version_rows(row)
+                .into_iter()
+                .filter_map(|version_row| history.version(version_row))
+                .collect()
+        })
+    }
+
+    /// The row as it was at `at`, or `None` if it did not exist then or history does not reach back that far.
+    pub fn row_at(&self, row: NonZeroU32, at: u64) -> Option<RowVersion> {
+        let history = self.history.as_ref()?;
+        history
+            .version_rows(row)
+            .into_iter()
+            .rev()
+            .filter_map(|version_row| history.version(version_row))
+            .find(|version| version.at <= at)
+            .filter(|version| !version.deleted)
+    }
+
+    /// Every row that existed at `at` with its state then.
+    pub fn rows_at(&self, at: u64) -> HashMap<NonZeroU32, RowVersion> {
+        self.history.as_ref().map_or(HashMap::new(), |history| {
+            history
+                .version_rows_at(at)
+                .into_iter()
+                .filter_map(|(row, version_row)| {
+                    history
+                        .version(version_row)
+                        .filter(|version| !version.deleted)
+                        .map(|version| (row, version))
+                })
+                .collect()
+        })
+    }
+
+    pub(crate) fn load_history(&mut self, dir: &Path) {
+        let dir = dir.join("history");
+        if dir.exists() {
+            self.history = Some(History::new(dir, self.cipher.clone(), self.name()));
+        }
+    }
+
+    /// Appends the current state of `row` after a write.
+    pub(crate) fn record_history(&mut self, row: NonZeroU32) {
+        if self.history.is_none() {
+            return;
+        }
+        let data = self.data();
+        let version = RowVersion {
+            at: now(),
+            deleted: false,
+            activity: data.activity(row).unwrap_or(Activity::Active),
+            term_begin: data.term_begin(row).cloned().unwrap_or(0),
+            term_end: data.term_end(row).cloned().unwrap_or(0),
+            uuid: data.uuid(row).cloned().unwrap_or(0),
+            fields: data
+                .fields()
+                .iter()
+                .filter_map(|(name, field)| {
+                    field.value(row).map(|value| (name.clone(), value.to_vec()))
+                })
+                .collect(),
+        };
+        if let Some(history) = &mut self.history {
+            history.push(row, &version);
+        }
+    }
+
+    pub(crate) fn record_history_delete(&mut self, row: NonZeroU32) {
+        if let Some(history) = &mut self.history {
+            history.push(
+                row,
+                &RowVersion {
+                    at: now(),
+                    deleted: true,
+                    activity: Activity::Inactive,
+                    term_begin: 0,
+                    term_end: 0,
+                    uuid: 0,
+                    fields: HashMap::new(),
+                },
+            );
+        }
+    }
+}
+
+impl Database {
+    /// Starts keeping every version of the rows of the collection. The rows already there are recorded as of now.
+    pub fn enable_history(&mut self, collection_id: NonZeroI32) -> Result<(), WriteError> {
+        self.check_writable()?;
+        let dir = self.history_dir(collection_id);
+        if let Some(collection) = self.collections.get_mut(&collection_id) {
+            if collection.history.is_none() {
+                collection.history = Some(History::new(
+                    dir,
+                    collection.cipher.clone(),
+                    collection.name(),
+                ));
+                for row in collection.data().all().into_iter() {
+                    collection.record_history(row);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Stops keeping versions and removes the ones kept so far.
+    pub fn disable_history(&mut self, collection_id: NonZeroI32) -> Result<(), WriteError> {
+        self.check_writable()?;
+        let dir = self.history_dir(collection_id);
+        if let Some(collection) = self.collections.get_mut(&collection_id) {
+            if collection.history.take().is_some() && dir.exists() {
+                std::fs::remove_dir_all(&dir).unwrap();
+            }
+        }
+        Ok(())
+    }
+
+    fn history_dir(&self, collection_id: NonZeroI32) -> PathBuf {
+        let collection_name = self
+            .collections
+            .get(&collection_id)
+            .map_or("", |collection| collection.name());
+        self.collections_dir
+            .join(collection_id.to_string() + "_" + collection_name)
+            .join("history")
+    }
+}
@@ -1,19 +1,50 @@
+pub mod audit;
+pub mod bulk;
+pub mod codec;
+pub mod composite;
+pub mod fulltext;
+pub mod history;
+pub mod migration;
 pub mod search;
+pub mod transaction;
 
 mod collection;
+mod encryption;
+mod purge;
 mod relation;
+mod schema;
+mod stats;
+mod temporary;
 
 use async_recursion::async_recursion;
-pub use collection::{Collection, CollectionRow};
-pub use relation::{Depend, RelationIndex};
+pub use collection::{Collection, CollectionRecord, CollectionRow};
+pub use encryption::Cipher;
+#[cfg(feature = "encryption")]
+pub use encryption::EncryptionKey;
+pub use hashbrown;
+pub use purge::{PurgePolicy, PurgeReport};
+pub use relation::{
+    Depend, MemoryRelationStorage, MmapRelationStorage, RelationIndex, RelationStorage,
+};
+pub use schema::{
+    FieldError, FieldSchema, FieldType, Schema, UniqueConflict, ValidationError, WriteError,
+};
 pub use search::{Condition, Search, SearchJoin, SearchResult};
-use versatile_data::idx_binary::AvltrieeSearch;
+#[cfg(feature = "derive")]
+pub use semilattice_database_derive::CollectionRecord;
+pub use stats::{CollectionStats, DatabaseStats, RelationStats};
 pub use versatile_data::{
-    create_uuid, idx_binary, uuid_string, Activity, CustomOrderKey, CustomSort, DataOption, Field,
-    FieldName, Fields, FileMmap, IdxFile, Order, OrderKey, RowSet, Term, Uuid,
+    create_uuid, idx_binary, uuid_string, Activity, CustomOrderKey, CustomSort, Data, DataOption,
+    Field, FieldName, Fields, FileMmap, IdxFile, Order, OrderKey, RowSet, Term, Uuid,
 };
 
-use std::{collections::BTreeMap, num::NonZeroI32, path::PathBuf};
+use std::{
+    collections::BTreeMap,
+    fs::{File, TryLockError},
+    io,
+    num::NonZeroI32,
+    path::{Path, PathBuf},
+};
 
 use hashbrown::HashMap;
 
@@ -23,22 +54,169 @@ pub struct Database {
     collections: BTreeMap<NonZeroI32, Collection>,
     relation: RelationIndex,
     collection_settings: std::collections::HashMap<String, DataOption>,
+    audit: Option<audit::SharedAuditLog>,
+    read_only: bool,
+    /// Held while open so that no other process writes to the directory.
+    lock: Option<File>,
+    cipher: Option<Cipher>,
+    temporary: Option<temporary::TemporaryDir>,
 }
 impl Database {
+    /// [Database::open] that panics if the directory is locked by another writer.
     pub fn new(
         dir: PathBuf,
         collection_settings: Option<std::collections::HashMap<String, DataOption>>,
         relation_allocation_lot: u32,
     ) -> Self {
+        Self::open(dir, collection_settings, relation_allocation_lot)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Opens the database in `dir`, creating it if needed, and locks the directory until the database is dropped.
+    /// The lock is advisory, on the file `lock`, and fails with [io::ErrorKind::WouldBlock] while another writer holds it.
+    /// Read-only opens do not take it, so they can run alongside one writer.
+    pub fn open(
+        dir: PathBuf,
+        collection_settings: Option<std::collections::HashMap<String, DataOption>>,
+        relation_allocation_lot: u32,
+    ) -> io::Result<Self> {
+        let lock = Self::lock(&dir)?;
+        encryption::check_unencrypted(&dir)?;
+        let relation = RelationIndex::new(&dir, relation_allocation_lot);
+        let mut database = Self::load(dir, collection_settings, relation, false, None)?;
+        database.lock = Some(lock);
+        Ok(database)
+    }
+
+    /// [Database::open] with field values, relation keys and the heads of history versions and session rows encrypted under `key`.
+    /// A new database is created encrypted. Fails with [io::ErrorKind::InvalidData] if `key` is not the one the database was created with
+    /// and with [io::ErrorKind::InvalidInput] if the database was created without encryption.
+    #[cfg(feature = "encryption")]
+    pub fn open_encrypted(
+        dir: PathBuf,
+        collection_settings: Option<std::collections::HashMap<String, DataOption>>,
+        relation_allocation_lot: u32,
+        key: &EncryptionKey,
+    ) -> io::Result<Self> {
+        let lock = Self::lock(&dir)?;
+        let cipher = Cipher::new(key);
+        encryption::check_key(&dir, &cipher, false)?;
+        let relation = RelationIndex::with_storage(Box::new(MmapRelationStorage::with_cipher(
+            &dir,
+            relation_allocation_lot,
+            Some(cipher.clone()),
+        )));
+        let mut database = Self::load(dir, collection_settings, relation, false, Some(cipher))?;
+        database.lock = Some(lock);
+        Ok(database)
+    }
+
+    /// [Database::open] with the relations kept in `relation_storage` instead of the `relation` directory.
+    /// Collections are always stored in the directory.
+    pub fn open_with(
+        dir: PathBuf,
+        collection_settings: Option<std::collections::HashMap<String, DataOption>>,
+        relation_storage: Box<dyn RelationStorage>,
+    ) -> io::Result<Self> {
+        let lock = Self::lock(&dir)?;
+        encryption::check_unencrypted(&dir)?;
+        let relation = RelationIndex::with_storage(relation_storage);
+        let mut database = Self::load(dir, collection_settings, relation, false, None)?;
+        database.lock = Some(lock);
+        Ok(database)
+    }
+
+    fn lock(dir: &Path) -> io::Result<File> {
+        std::fs::create_dir_all(dir)?;
+        let lock = File::options()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(dir.join("lock"))?;
+        match lock.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::WouldBlock,
+                    format!("{} is opened by another process", dir.display()),
+                ))
+            }
+            Err(TryLockError::Error(e)) => return Err(e),
+        }
+        Ok(lock)
+    }
+
+    /// Opens an existing database without creating anything in its directory. Every write fails with [WriteError::ReadOnly].
+    /// The files are opened and mapped read-only, and a file that is missing, such as the relation files of a database that never had a relation, is kept in memory instead.
+    pub fn open_read_only(
+        dir: PathBuf,
+        collection_settings: Option<std::collections::HashMap<String, DataOption>>,
+    ) -> io::Result<Self> {
+        Self::check_exists(&dir)?;
+        encryption::check_unencrypted(&dir)?;
+        Self::load_read_only(dir, collection_settings, None)
+    }
+
+    /// [Database::open_read_only] for a database created with [Database::open_encrypted] under `key`.
+    #[cfg(feature = "encryption")]
+    pub fn open_read_only_encrypted(
+        dir: PathBuf,
+        collection_settings: Option<std::collections::HashMap<String, DataOption>>,
+        key: &EncryptionKey,
+    ) -> io::Result<Self> {
+        Self::check_exists(&dir)?;
+        let cipher = Cipher::new(key);
+        encryption::check_key(&dir, &cipher, true)?;
+        Self::load_read_only(dir, collection_settings, Some(cipher))
+    }
+
+    fn check_exists(dir: &Path) -> io::Result<()> {
+        if !dir.join("relation").is_dir() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no database in {}", dir.display()),
+            ));
+        }
+        Ok(())
+    }
+
+    fn load_read_only(
+        dir: PathBuf,
+        collection_settings: Option<std::collections::HashMap<String, DataOption>>,
+        cipher: Option<Cipher>,
+    ) -> io::Result<Self> {
+        file_mmap::read_only(|| {
+            let relation = match MmapRelationStorage::open_existing(&dir, cipher.clone())? {
+                Some(storage) => RelationIndex::with_storage(Box::new(storage)),
+                None => RelationIndex::with_storage(Box::new(MemoryRelationStorage::new())),
+            };
+            Self::load(dir, collection_settings, relation, true, cipher)
+        })
+    }
+
+    fn load(
+        dir: PathBuf,
+        collection_settings: Option<std::collections::HashMap<String, DataOption>>,
+        mut relation: RelationIndex,
+        read_only: bool,
+        cipher: Option<Cipher>,
+    ) -> io::Result<Self> {
         let mut collections_dir = dir.to_path_buf();
         collections_dir.push("collection");
 
+        relation.read_only = read_only;
         let mut db = Self {
             collections_dir,
             collections: BTreeMap::new(),
             collections_map: HashMap::new(),
-            relation: RelationIndex::new(&dir, relation_allocation_lot),
-            collection_settings: collection_settings.unwrap_or(std::collections::HashMap::new()),
+            relation,
+            collection_settings: collection_settings.unwrap_or_default(),
+            audit: None,
+            read_only,
+            lock: None,
+            cipher,
+            temporary: None,
         };
         if db.collections_dir.exists() {
             let dir = db.collections_dir.read_dir().unwrap();
@@ -47,37 +225,57 @@ impl Database {
                 if d.file_type().unwrap().is_dir() {
                     if let Some(fname) = d.file_name().to_str() {
                         if let Some(pos) = fname.find("_") {
-                            if let Ok(collection_id) = (&fname[..pos]).parse::<NonZeroI32>() {
+                            if let Ok(collection_id) = fname[..pos].parse::<NonZeroI32>() {
                                 let name = &fname[(pos + 1)..];
-                                db.create_collection(collection_id, name, d.path());
+                                db.create_collection(collection_id, name, d.path())?;
                             }
                         }
                     }
                 }
             }
         }
-        db
+        db.load_audit();
+        Ok(db)
+    }
+
+    /// The directory the database is stored in.
+    pub fn dir(&self) -> &Path {
+        self.collections_dir.parent().unwrap()
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    pub(crate) fn check_writable(&self) -> Result<(), WriteError> {
+        if self.read_only {
+            Err(WriteError::ReadOnly)
+        } else {
+            Ok(())
+        }
     }
 
+    /// Deletes the row along with the rows depending on it and the relations from it.
     #[async_recursion(?Send)]
-    pub async fn delete(&mut self, target: &CollectionRow) {
-        let rows: Vec<_> = self.relation.index_depend().iter_by(target).collect();
+    pub async fn delete(&mut self, target: &CollectionRow) -> Result<(), WriteError> {
+        self.check_writable()?;
+        let rows: Vec<_> = self.relation.rows_by_depend(target).collect();
         for relation_row in rows.into_iter() {
-            if let Some(collection_row) = self.relation.index_pend().value(relation_row).cloned() {
-                self.delete(&collection_row).await;
+            if let Some(collection_row) = self.relation.pend(relation_row).cloned() {
+                self.delete(&collection_row).await?;
             }
         }
         for relation_row in self
             .relation
-            .index_pend()
-            .iter_by(target)
+            .rows_by_pend(target)
             .collect::<Vec<_>>()
             .into_iter()
         {
-            self.relation.delete(relation_row).await;
+            self.relation.delete(relation_row).await?;
         }
         if let Some(collection) = self.collection_mut(target.collection_id()) {
-            collection.delete(target.row()).await;
+            collection.delete(target.row()).await?;
         }
+        Ok(())
     }
 }
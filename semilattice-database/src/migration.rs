@@ -0,0 +1,457 @@
+use std::{fmt, num::NonZeroI32, path::PathBuf};
+
+use futures::future::LocalBoxFuture;
+use hashbrown::HashMap;
+use versatile_data::{idx_binary::AvltrieeSearch, Activity, FieldName, Term};
+
+use crate::{CollectionRow, Database, WriteError};
+
+const VERSION_FILE: &str = "schema_version";
+
+/// One versioned step of a [Migrator].
+pub trait MigrationStep {
+    /// Steps run in ascending order of version, once each.
+    fn version(&self) -> u32;
+
+    fn description(&self) -> &str {
+        ""
+    }
+
+    fn run<'a>(
+        &'a self,
+        migration: &'a mut Migration<'_>,
+    ) -> LocalBoxFuture<'a, Result<(), WriteError>>;
+}
+
+/// A row a step wrote or, in a dry run, would have written.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MigrationChange {
+    /// In a dry run, the id is the one the collection would get.
+    CreateCollection {
+        name: String,
+        collection_id: NonZeroI32,
+    },
+    Insert(NonZeroI32),
+    Update(CollectionRow),
+    Delete(CollectionRow),
+    RenameRelationKey {
+        from: String,
+        to: String,
+        rows: usize,
+    },
+    Relate {
+        key: String,
+        depend: CollectionRow,
+        pend: CollectionRow,
+    },
+    MoveRelations {
+        from: CollectionRow,
+        to: CollectionRow,
+        rows: usize,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StepReport {
+    pub version: u32,
+    pub description: String,
+    pub changes: Vec<MigrationChange>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MigrationReport {
+    /// The version before the run.
+    pub from_version: u32,
+    pub steps: Vec<StepReport>,
+}
+
+/// A write that failed in a step. The steps before it stay applied and the stored version is that of the last one.
+#[derive(Debug)]
+pub struct MigrationError {
+    pub version: u32,
+    pub error: WriteError,
+}
+impl fmt::Display for MigrationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "migration {} failed: {}", self.version, self.error)
+    }
+}
+impl std::error::Error for MigrationError {}
+
+enum Target<'a> {
+    Write(&'a mut Database),
+    /// A dry run only borrows the database, so it cannot write to it.
+    DryRun(&'a Database),
+}
+
+/// The database as seen by a step. Writes are recorded, and in a dry run they are checked but not applied.
+pub struct Migration<'a> {
+    database: Target<'a>,
+    /// Collections a dry run would have created, by name.
+    planned: HashMap<String, NonZeroI32>,
+    changes: Vec<MigrationChange>,
+}
+impl Migration<'_> {
+    pub fn database(&self) -> &Database {
+        match &self.database {
+            Target::Write(database) => database,
+            Target::DryRun(database) => database,
+        }
+    }
+
+    pub fn is_dry_run(&self) -> bool {
+        matches!(self.database, Target::DryRun(_))
+    }
+
+    /// In a dry run a missing collection is not created. It gets the id it would have, and writes to it are reported without being checked.
+    pub fn collection_id_or_create(&mut self, name: &str) -> Result<NonZeroI32, WriteError> {
+        if let Some(collection_id) = self
+            .database()
+            .collection_id(name)
+            .or_else(|| self.planned.get(name).copied())
+        {
+            return Ok(collection_id);
+        }
+        let collection_id = match &mut self.database {
+            Target::Write(database) => database.collection_id_or_create(name)?,
+            Target::DryRun(database) => {
+                let max_id = database
+                    .collections
+                    .keys()
+                    .chain(self.planned.values())
+                    .map(|collection_id| collection_id.get())
+                    .max()
+                    .unwrap_or(0);
+                let collection_id = NonZeroI32::new(max_id + 1).unwrap();
+                self.planned.insert(name.to_owned(), collection_id);
+                collection_id
+            }
+        };
+        self.changes.push(MigrationChange::CreateCollection {
+            name: name.to_owned(),
+            collection_id,
+        });
+        Ok(collection_id)
+    }
+
+    fn is_planned(&self, collection_id: NonZeroI32) -> bool {
+        self.planned
+            .values()
+            .any(|planned| *planned == collection_id)
+    }
+
+    /// The inserted row, or None in a dry run.
+    pub async fn insert(
+        &mut self,
+        collection_id: NonZeroI32,
+        activity: Activity,
+        term_begin: Term,
+        term_end: Term,
+        fields: HashMap<FieldName, Vec<u8>>,
+    ) -> Result<Option<CollectionRow>, WriteError> {
+        let row = match &mut self.database {
+            Target::Write(database) => {
+                let Some(collection) = database.collection_mut(collection_id) else {
+                    return Ok(None);
+                };
+                let row = collection
+                    .insert(activity, term_begin, term_end, fields)
+                    .await?;
+                Some(CollectionRow::new(collection_id, row))
+            }
+            Target::DryRun(database) => {
+                if let Some(collection) = database.collection(collection_id) {
+                    collection.validate(&fields, true)?;
+                    collection.check_unique(None, &fields)?;
+                } else if !self.is_planned(collection_id) {
+                    return Ok(None);
+                }
+                None
+            }
+        };
+        self.changes.push(MigrationChange::Insert(collection_id));
+        Ok(row)
+    }
+
+    /// Overwrites `fields` of a row, keeping its activity and term.
+    pub async fn update(
+        &mut self,
+        target: &CollectionRow,
+        fields: HashMap<FieldName, Vec<u8>>,
+    ) -> Result<(), WriteError> {
+        let row = target.row();
+        match &mut self.database {
+            Target::Write(database) => {
+                let Some(collection) = database.collection_mut(target.collection_id()) else {
+                    return Ok(());
+                };
+                let (Some(activity), Some(term_begin), Some(term_end)) = (
+                    collection.activity(row),
+                    collection.term_begin(row).cloned(),
+                    collection.term_end(row).cloned(),
+                ) else {
+                    return Ok(());
+                };
+                collection
+                    .update(
+                        row,
+                        activity,
+                        Term::Overwrite(term_begin),
+                        Term::Overwrite(term_end),
+                        fields,
+                    )
+                    .await?;
+            }
+            Target::DryRun(database) => {
+                let Some(collection) = database.collection(target.collection_id()) else {
+                    return Ok(());
+                };
+                collection.validate(&fields, false)?;
+                collection.check_unique(Some(row), &fields)?;
+            }
+        }
+        self.changes.push(MigrationChange::Update(target.clone()));
+        Ok(())
+    }
+
+    /// Deletes a row and the rows depending on it.
+    pub async fn delete(&mut self, target: &CollectionRow) -> Result<(), WriteError> {
+        if let Target::Write(database) = &mut self.database {
+            database.delete(target).await?;
+        }
+        self.changes.push(MigrationChange::Delete(target.clone()));
+        Ok(())
+    }
+
+    /// Gives `value` to every row of the collection that does not have the field.
+    pub async fn set_default(
+        &mut self,
+        collection_id: NonZeroI32,
+        field_name: &FieldName,
+        value: &[u8],
+    ) -> Result<(), WriteError> {
+        self.transform(collection_id, field_name, |current| {
+            current.is_none().then(|| value.to_vec())
+        })
+        .await
+    }
+
+    /// Rewrites a field of every row of the collection. `f` gets the current value, if any, and returns the new one or None to leave the row as it is.
+    pub async fn transform(
+        &mut self,
+        collection_id: NonZeroI32,
+        field_name: &FieldName,
+        f: impl Fn(Option<&[u8]>) -> Option<Vec<u8>>,
+    ) -> Result<(), WriteError> {
+        let Some(collection) = self.database().collection(collection_id) else {
+            return Ok(());
+        };
+        let field = collection.data().fields().get(field_name);
+        let values: Vec<_> = collection
+            .data()
+            .all()
+            .into_iter()
+            .filter_map(|row| {
+                let value = field
+                    .and_then(|field| field.value(row))
+                    .and_then(|bytes| collection.decrypt(field_name, bytes));
+                f(value.as_deref()).map(|value| (row, value))
+            })
+            .collect();
+        for (row, value) in values {
+            self.update(
+                &CollectionRow::new(collection_id, row),
+                [(field_name.clone(), value)].into(),
+            )
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// Renames the key of every relation registered with `from`.
+    pub async fn rename_relation_key(&mut self, from: &str, to: &str) -> Result<(), WriteError> {
+        let rows = match &mut self.database {
+            Target::Write(database) => database.relation_mut().rename_key(from, to).await?.len(),
+            Target::DryRun(database) => database.relation().rows_by_key(from).len(),
+        };
+        self.changes.push(MigrationChange::RenameRelationKey {
+            from: from.to_owned(),
+            to: to.to_owned(),
+            rows,
+        });
+        Ok(())
+    }
+
+    pub async fn register_relation(
+        &mut self,
+        key: &str,
+        depend: &CollectionRow,
+        pend: &CollectionRow,
+    ) -> Result<(), WriteError> {
+        if let Target::Write(database) = &mut self.database {
+            database.register_relation(key, depend, pend).await?;
+        }
+        self.changes.push(MigrationChange::Relate {
+            key: key.to_owned(),
+            depend: depend.clone(),
+            pend: pend.clone(),
+        });
+        Ok(())
+    }
+
+    /// Registers every relation of `from` again with `to` in its place, under the same key, and removes the old ones.
+    /// Used when rows move to another collection, such as when a collection is split.
+    pub async fn move_relations(
+        &mut self,
+        from: &CollectionRow,
+        to: &CollectionRow,
+    ) -> Result<(), WriteError> {
+        let relation = self.database().relation();
+        let mut rows: Vec<_> = relation
+            .rows_by_depend(from)
+            .chain(relation.rows_by_pend(from))
+            .collect();
+        rows.sort();
+        rows.dedup();
+        if let Target::Write(database) = &mut self.database {
+            let relations: Vec<_> = rows
+                .iter()
+                .filter_map(|row| {
+                    let relation = database.relation();
+                    let replace = |collection_row: &CollectionRow| {
+                        if collection_row == from {
+                            to.clone()
+                        } else {
+                            collection_row.clone()
+                        }
+                    };
+                    Some((
+                        relation.key(*row).to_owned(),
+                        replace(relation.depend(*row)?),
+                        replace(relation.pend(*row)?),
+                    ))
+                })
+                .collect();
+            for row in rows.iter() {
+                database.relation_mut().delete(*row).await?;
+            }
+            for (key, depend, pend) in relations {
+                database.register_relation(&key, &depend, &pend).await?;
+            }
+        }
+        self.changes.push(MigrationChange::MoveRelations {
+            from: from.clone(),
+            to: to.clone(),
+            rows: rows.len(),
+        });
+        Ok(())
+    }
+}
+
+/// Runs registered steps whose version is above the one stored in the database directory.
+#[derive(Default)]
+pub struct Migrator {
+    steps: Vec<Box<dyn MigrationStep>>,
+}
+impl Migrator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn step(mut self, step: impl MigrationStep + 'static) -> Self {
+        self.steps.push(Box::new(step));
+        self.steps.sort_by_key(|step| step.version());
+        self
+    }
+
+    /// The steps that have not been applied yet.
+    pub fn pending(&self, database: &Database) -> Vec<&dyn MigrationStep> {
+        let version = database.schema_version();
+        self.steps
+            .iter()
+            .filter(|step| step.version() > version)
+            .map(|step| step.as_ref())
+            .collect()
+    }
+
+    pub async fn run(&self, database: &mut Database) -> Result<MigrationReport, MigrationError> {
+        let mut report = MigrationReport {
+            from_version: database.schema_version(),
+            steps: vec![],
+        };
+        for step in self.pending(database) {
+            if database.is_read_only() {
+                return Err(MigrationError {
+                    version: step.version(),
+                    error: WriteError::ReadOnly,
+                });
+            }
+            let step_report = Self::run_step(step, Target::Write(database)).await?;
+            database.set_schema_version(step.version());
+            report.steps.push(step_report);
+        }
+        Ok(report)
+    }
+
+    /// Runs the pending steps without writing anything and reports the rows they would touch.
+    /// Each step sees the data as it is, not as the steps before it would have left it.
+    pub async fn dry_run(&self, database: &Database) -> Result<MigrationReport, MigrationError> {
+        let mut report = MigrationReport {
+            from_version: database.schema_version(),
+            steps: vec![],
+        };
+        for step in self.pending(database) {
+            report
+                .steps
+                .push(Self::run_step(step, Target::DryRun(database)).await?);
+        }
+        Ok(report)
+    }
+
+    async fn run_step(
+        step: &dyn MigrationStep,
+        database: Target<'_>,
+    ) -> Result<StepReport, MigrationError> {
+        let mut migration = Migration {
+            database,
+            planned: HashMap::new(),
+            changes: vec![],
+        };
+        step.run(&mut migration)
+            .await
+            .map_err(|error| MigrationError {
+                version: step.version(),
+                error,
+            })?;
+        Ok(StepReport {
+            version: step.version(),
+            description: step.description().to_owned(),
+            changes: migration.changes,
+        })
+    }
+}
+
+impl Database {
+    /// The version of the last migration step applied, 0 if none.
+    pub fn schema_version(&self) -> u32 {
+        std::fs::read_to_string(self.schema_version_path())
+            .ok()
+            .and_then(|s| s.trim().parse().ok())
+            .unwrap_or(0)
+    }
+
+    fn set_schema_version(&self, version: u32) {
+        let path = self.schema_version_path();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).unwrap();
+        }
+        std::fs::write(path, version.to_string()).unwrap();
+    }
+
+    fn schema_version_path(&self) -> PathBuf {
+        self.collections_dir
+            .parent()
+            .map_or_else(PathBuf::new, |dir| dir.to_path_buf())
+            .join(VERSION_FILE)
+    }
+}
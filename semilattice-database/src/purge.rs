@@ -0,0 +1,92 @@
+use versatile_data::{search::Term as TermCondition, Activity, Condition, Term};
+
+use crate::{CollectionRow, Database, WriteError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PurgePolicy {
+    /// Deletes the rows through [Database::delete], so the rows depending on them go too.
+    Delete,
+    /// Keeps the rows but makes them inactive.
+    Deactivate,
+}
+
+/// What [Database::purge_expired] did.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct PurgeReport {
+    /// Expired rows that were deleted or deactivated.
+    pub rows: Vec<CollectionRow>,
+    /// Rows deleted because they depended on a deleted row, expired or not.
+    pub cascaded: Vec<CollectionRow>,
+}
+
+impl Database {
+    /// Deletes or deactivates the rows of every collection whose `term_end` is set and at or before `before`.
+    /// Each row is handled on its own and rows already handled no longer qualify, so an interrupted purge is finished by running it again.
+    pub async fn purge_expired(
+        &mut self,
+        before: u64,
+        policy: PurgePolicy,
+    ) -> Result<PurgeReport, WriteError> {
+        self.check_writable()?;
+        let mut report = PurgeReport::default();
+        let collection_ids: Vec<_> = self.collections.keys().cloned().collect();
+        for collection_id in collection_ids {
+            let Some(collection) = self.collection(collection_id) else {
+                continue;
+            };
+            let mut rows = collection
+                .data()
+                .result_condition(&Condition::Term(TermCondition::Past(before)))
+                .await;
+            if policy == PurgePolicy::Deactivate {
+                rows.retain(|row| collection.activity(*row) == Some(Activity::Active));
+            }
+            for row in rows {
+                let target = CollectionRow::new(collection_id, row);
+                match policy {
+                    PurgePolicy::Delete => {
+                        let Some(collection) = self.collection(collection_id) else {
+                            break;
+                        };
+                        // Gone already as a dependant of a row purged before it.
+                        if collection.term_end(row).is_none() {
+                            continue;
+                        }
+                        self.dependants(&target, &mut report.cascaded);
+                        self.delete(&target).await?;
+                    }
+                    PurgePolicy::Deactivate => {
+                        let Some(collection) = self.collection_mut(collection_id) else {
+                            break;
+                        };
+                        let term_begin = *collection.term_begin(row).unwrap_or(&0);
+                        let term_end = *collection.term_end(row).unwrap_or(&0);
+                        collection
+                            .update_unchecked(
+                                row,
+                                Activity::Inactive,
+                                Term::Overwrite(term_begin),
+                                Term::Overwrite(term_end),
+                                Default::default(),
+                            )
+                            .await?;
+                    }
+                }
+                report.rows.push(target);
+            }
+        }
+        Ok(report)
+    }
+
+    /// The rows [Database::delete] removes along with `target`.
+    fn dependants(&self, target: &CollectionRow, rows: &mut Vec<CollectionRow>) {
+        for relation_row in self.relation.rows_by_depend(target) {
+            if let Some(pend) = self.relation.pend(relation_row) {
+                if !rows.contains(pend) {
+                    rows.push(pend.clone());
+                    self.dependants(pend, rows);
+                }
+            }
+        }
+    }
+}
@@ -1,6 +1,8 @@
 mod index;
+mod storage;
 
 pub use index::RelationIndex;
+pub use storage::{MemoryRelationStorage, MmapRelationStorage, RelationStorage};
 
 use std::{
     num::{NonZeroI32, NonZeroU32},
@@ -10,7 +12,7 @@ use std::{
 
 use serde::{ser::SerializeStruct, Serialize};
 
-use crate::{collection::CollectionRow, Database};
+use crate::{collection::CollectionRow, Database, WriteError};
 
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub struct Depend {
@@ -62,7 +64,7 @@ impl Database {
         key_name: &str,
         depend: &CollectionRow,
         pend: &CollectionRow,
-    ) {
+    ) -> Result<(), WriteError> {
         self.relation.insert(key_name, depend, pend).await
     }
 
@@ -70,11 +72,12 @@ impl Database {
         &mut self,
         depend: &CollectionRow,
         pends: Vec<(Arc<String>, CollectionRow)>,
-    ) {
+    ) -> Result<(), WriteError> {
         for (key_name, pend) in pends.iter() {
             self.register_relation(key_name.as_str(), depend, pend)
-                .await;
+                .await?;
         }
+        Ok(())
     }
 
     pub fn depends(
@@ -1,73 +1,35 @@
 use std::{
+    collections::BTreeMap,
     num::{NonZeroI32, NonZeroU32},
     path::Path,
     sync::Arc,
 };
 
-use versatile_data::{
-    idx_binary::{AvltrieeSearch, AvltrieeUpdate},
-    IdxBinary, IdxFile, RowFragment,
+use versatile_data::IdxFile;
+
+use crate::{
+    audit::{AuditChange, SharedAuditLog},
+    CollectionRow, Depend, RelationStats, WriteError,
 };
 
-use crate::{CollectionRow, Depend};
+use super::storage::{MmapRelationStorage, RelationStorage};
 
-struct RelationIndexRows {
-    key: IdxFile<u32>,
-    depend: IdxFile<CollectionRow>,
-    pend: IdxFile<CollectionRow>,
-}
 pub struct RelationIndex {
-    fragment: RowFragment,
-    key_names: IdxBinary,
-    rows: RelationIndexRows,
+    storage: Box<dyn RelationStorage>,
+    pub(crate) audit: Option<SharedAuditLog>,
+    pub(crate) read_only: bool,
 }
 impl RelationIndex {
+    /// An index stored in the `relation` directory under `root_dir`.
     pub fn new(root_dir: &Path, allocation_lot: u32) -> Self {
-        let mut dir = root_dir.to_path_buf();
-        dir.push("relation");
-        if !dir.exists() {
-            std::fs::create_dir_all(&dir).unwrap();
-        }
+        Self::with_storage(Box::new(MmapRelationStorage::new(root_dir, allocation_lot)))
+    }
+
+    pub fn with_storage(storage: Box<dyn RelationStorage>) -> Self {
         Self {
-            key_names: IdxBinary::new_ext(
-                {
-                    let mut path = dir.clone();
-                    path.push("key_name");
-                    path
-                },
-                1,
-            ),
-            fragment: RowFragment::new({
-                let mut path = dir.clone();
-                path.push("fragment.f");
-                path
-            }),
-            rows: RelationIndexRows {
-                key: IdxFile::new(
-                    {
-                        let mut path = dir.clone();
-                        path.push("key.i");
-                        path
-                    },
-                    allocation_lot,
-                ),
-                depend: IdxFile::new(
-                    {
-                        let mut path = dir.clone();
-                        path.push("depend.i");
-                        path
-                    },
-                    allocation_lot,
-                ),
-                pend: IdxFile::new(
-                    {
-                        let mut path = dir.clone();
-                        path.push("pend.i");
-                        path
-                    },
-                    allocation_lot,
-                ),
-            },
+            storage,
+            audit: None,
+            read_only: false,
         }
     }
 
@@ -76,50 +38,103 @@ impl RelationIndex {
         relation_key: &str,
         depend: &CollectionRow,
         pend: &CollectionRow,
-    ) {
-        let key_id = self.key_names.row_or_insert(relation_key.as_bytes()).get();
-        if let Some(row) = self.fragment.pop() {
-            futures::join!(
-                async { self.rows.key.update(row, &key_id) },
-                async { self.rows.depend.update(row, depend) },
-                async { self.rows.pend.update(row, pend) }
-            );
+    ) -> Result<(), WriteError> {
+        self.check_writable()?;
+        self.storage.insert(relation_key, depend, pend);
+        if let Some(audit) = &self.audit {
+            audit.lock().push(AuditChange::Relate {
+                key: relation_key.to_owned(),
+                depend: depend.clone(),
+                pend: pend.clone(),
+            });
+        }
+        Ok(())
+    }
+
+    fn check_writable(&self) -> Result<(), WriteError> {
+        if self.read_only {
+            Err(WriteError::ReadOnly)
         } else {
-            futures::join!(
-                async { self.rows.key.insert(&key_id) },
-                async { self.rows.depend.insert(depend) },
-                async { self.rows.pend.insert(pend) }
-            );
+            Ok(())
         }
     }
 
-    pub async fn delete(&mut self, row: NonZeroU32) {
-        futures::join!(
-            async {
-                self.rows.key.delete(row);
-            },
-            async {
-                self.rows.depend.delete(row);
-            },
-            async {
-                self.rows.pend.delete(row);
-            },
-            async {
-                self.fragment.insert_blank(row);
-            },
-        );
+    fn audit_unrelate(&self, row: NonZeroU32) {
+        if let (Some(audit), Some(depend), Some(pend)) =
+            (&self.audit, self.depend(row), self.pend(row))
+        {
+            audit.lock().push(AuditChange::Unrelate {
+                key: self.key(row).to_owned(),
+                depend: depend.clone(),
+                pend: pend.clone(),
+            });
+        }
+    }
+
+    pub async fn delete(&mut self, row: NonZeroU32) -> Result<(), WriteError> {
+        self.check_writable()?;
+        self.audit_unrelate(row);
+        self.storage.delete(row);
+        Ok(())
     }
 
-    pub async fn delete_pends_by_collection_row(&mut self, collection_row: &CollectionRow) {
+    pub async fn delete_pends_by_collection_row(
+        &mut self,
+        collection_row: &CollectionRow,
+    ) -> Result<(), WriteError> {
         for row in self
-            .rows
-            .pend
-            .iter_by(collection_row)
+            .rows_by_pend(collection_row)
             .collect::<Vec<_>>()
             .into_iter()
         {
-            self.delete(row).await;
+            self.delete(row).await?;
+        }
+        Ok(())
+    }
+
+    /// The relation rows registered with `key`.
+    pub fn rows_by_key(&self, key: &str) -> Vec<NonZeroU32> {
+        self.storage.rows_by_key(key).collect()
+    }
+
+    /// The relation rows whose depend is `depend`.
+    pub fn rows_by_depend<'a>(
+        &'a self,
+        depend: &CollectionRow,
+    ) -> impl Iterator<Item = NonZeroU32> + 'a {
+        self.storage.rows_by_depend(depend)
+    }
+
+    /// The relation rows whose pend is `pend`.
+    pub fn rows_by_pend<'a>(
+        &'a self,
+        pend: &CollectionRow,
+    ) -> impl Iterator<Item = NonZeroU32> + 'a {
+        self.storage.rows_by_pend(pend)
+    }
+
+    /// Changes the key of the relations registered with `from` to `to` and returns their rows.
+    pub async fn rename_key(
+        &mut self,
+        from: &str,
+        to: &str,
+    ) -> Result<Vec<NonZeroU32>, WriteError> {
+        self.check_writable()?;
+        let rows = self.rows_by_key(from);
+        for row in rows.iter() {
+            self.audit_unrelate(*row);
+            self.storage.set_key(*row, to);
+            if let (Some(audit), Some(depend), Some(pend)) =
+                (&self.audit, self.depend(*row), self.pend(*row))
+            {
+                audit.lock().push(AuditChange::Relate {
+                    key: to.to_owned(),
+                    depend: depend.clone(),
+                    pend: pend.clone(),
+                });
+            }
         }
+        Ok(rows)
     }
 
     pub fn pends(
@@ -128,146 +143,76 @@ impl RelationIndex {
         depend: &CollectionRow,
         pend_collection_id: Option<NonZeroI32>,
     ) -> Vec<&CollectionRow> {
-        if let Some(pend_collection_id) = pend_collection_id {
-            key.as_ref().map_or_else(
-                || {
-                    self.rows
-                        .depend
-                        .iter_by(depend)
-                        .filter_map(|row| {
-                            if let Some(v) = self.rows.pend.value(row) {
-                                if v.collection_id() == pend_collection_id {
-                                    return Some(v);
-                                }
-                            }
-                            None
-                        })
-                        .collect()
-                },
-                |key| {
-                    self.key_names.row(key.as_bytes()).map_or(vec![], |key| {
-                        self.rows
-                            .depend
-                            .iter_by(depend)
-                            .filter_map(|row| {
-                                if let (Some(key_row), Some(collection_row)) =
-                                    (self.rows.key.value(row), self.rows.pend.value(row))
-                                {
-                                    if *key_row == key.get() {
-                                        if collection_row.collection_id() == pend_collection_id {
-                                            return Some(collection_row);
-                                        }
-                                    }
-                                }
-                                None
-                            })
-                            .collect()
-                    })
-                },
-            )
-        } else {
-            key.as_ref().map_or_else(
-                || {
-                    self.rows
-                        .depend
-                        .iter_by(depend)
-                        .filter_map(|row| self.rows.pend.value(row))
-                        .collect()
-                },
-                |key| {
-                    self.key_names.row(key.as_bytes()).map_or(vec![], |key| {
-                        self.rows
-                            .depend
-                            .iter_by(depend)
-                            .filter_map(|row| {
-                                if let (Some(key_row), Some(collection_row)) =
-                                    (self.rows.key.value(row), self.rows.pend.value(row))
-                                {
-                                    (*key_row == key.get()).then_some(collection_row)
-                                } else {
-                                    None
-                                }
-                            })
-                            .collect()
-                    })
-                },
-            )
-        }
+        self.rows_by_depend(depend)
+            .filter(|row| {
+                key.as_ref()
+                    .is_none_or(|key| self.storage.key(*row) == Some(key.as_str()))
+            })
+            .filter_map(|row| self.pend(row))
+            .filter(|pend| pend_collection_id.is_none_or(|id| pend.collection_id() == id))
+            .collect()
     }
 
     pub fn depends(&self, key: Option<Arc<String>>, pend: &CollectionRow) -> Vec<Depend> {
-        key.map_or_else(
-            || {
-                self.rows
-                    .pend
-                    .iter_by(pend)
-                    .filter_map(|row| {
-                        if let (Some(key), Some(collection_row)) =
-                            (self.rows.key.value(row), self.rows.depend.value(row))
-                        {
-                            Some(Depend::new(
-                                Arc::new(
-                                    unsafe {
-                                        std::str::from_utf8_unchecked(
-                                            self.key_names
-                                                .value(NonZeroU32::new(*key).unwrap())
-                                                .unwrap(),
-                                        )
-                                    }
-                                    .into(),
-                                ),
-                                collection_row.clone(),
-                            ))
-                        } else {
-                            None
-                        }
-                    })
-                    .collect()
-            },
-            |key_name| {
-                self.key_names
-                    .row(key_name.as_bytes())
-                    .map_or(vec![], |key| {
-                        self.rows
-                            .pend
-                            .iter_by(pend)
-                            .filter_map(|row| {
-                                if let (Some(key_row), Some(collection_row)) =
-                                    (self.rows.key.value(row), self.rows.depend.value(row))
-                                {
-                                    (*key_row == key.get()).then_some(Depend::new(
-                                        Arc::clone(&key_name),
-                                        collection_row.clone(),
-                                    ))
-                                } else {
-                                    None
-                                }
-                            })
-                            .collect()
-                    })
-            },
-        )
+        self.rows_by_pend(pend)
+            .filter_map(|row| {
+                let row_key = self.storage.key(row)?;
+                let depend = self.depend(row)?;
+                match &key {
+                    Some(key) => (key.as_str() == row_key)
+                        .then(|| Depend::new(Arc::clone(key), depend.clone())),
+                    None => Some(Depend::new(Arc::new(row_key.into()), depend.clone())),
+                }
+            })
+            .collect()
     }
 
+    /// Panics if the storage does not keep its depends in an [IdxFile].
+    #[deprecated(note = "use rows_by_depend and depend, which work with any RelationStorage")]
     pub fn index_depend(&self) -> &IdxFile<CollectionRow> {
-        &self.rows.depend
+        self.storage
+            .depend_index()
+            .expect("the relation storage has no depend IdxFile")
     }
 
+    /// Panics if the storage does not keep its pends in an [IdxFile].
+    #[deprecated(note = "use rows_by_pend and pend, which work with any RelationStorage")]
     pub fn index_pend(&self) -> &IdxFile<CollectionRow> {
-        &self.rows.pend
+        self.storage
+            .pend_index()
+            .expect("the relation storage has no pend IdxFile")
     }
 
     pub fn depend(&self, row: NonZeroU32) -> Option<&CollectionRow> {
-        self.rows.depend.value(row)
+        self.storage.depend(row)
+    }
+
+    pub fn pend(&self, row: NonZeroU32) -> Option<&CollectionRow> {
+        self.storage.pend(row)
+    }
+
+    /// Every relation key ever registered, with the number of relations using it now.
+    pub fn keys(&self) -> impl Iterator<Item = (&str, usize)> {
+        self.storage
+            .keys()
+            .map(|key| (key, self.storage.rows_by_key(key).count()))
+    }
+
+    pub fn stats(&self) -> RelationStats {
+        let keys: BTreeMap<_, _> = self
+            .keys()
+            .filter(|(_, count)| *count > 0)
+            .map(|(key, count)| (key.to_owned(), count))
+            .collect();
+        RelationStats {
+            relations: keys.values().sum(),
+            keys,
+            free_rows: self.storage.free_rows(),
+            bytes: self.storage.bytes(),
+        }
     }
 
     pub fn key(&self, row: NonZeroU32) -> &str {
-        self.rows.key.value(row).map_or("", |key_row| unsafe {
-            std::str::from_utf8_unchecked(
-                self.key_names
-                    .value(NonZeroU32::new(*key_row).unwrap())
-                    .unwrap(),
-            )
-        })
+        self.storage.key(row).unwrap_or("")
     }
 }
@@ -0,0 +1,409 @@
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet},
+    io,
+    num::NonZeroU32,
+    path::{Path, PathBuf},
+};
+
+use hashbrown::HashMap;
+use versatile_data::{
+    idx_binary::{AvltrieeSearch, AvltrieeUpdate},
+    IdxBinary, IdxFile, RowFragment,
+};
+
+use crate::{stats::dir_bytes, Cipher, CollectionRow};
+
+/// Where a [crate::RelationIndex] keeps its relations. Each relation is a row holding a key, a depend and a pend.
+/// Rows freed by `delete` may be handed out again by `insert`.
+pub trait RelationStorage: Send {
+    fn insert(&mut self, key: &str, depend: &CollectionRow, pend: &CollectionRow) -> NonZeroU32;
+    fn delete(&mut self, row: NonZeroU32);
+    fn set_key(&mut self, row: NonZeroU32, key: &str);
+
+    fn key(&self, row: NonZeroU32) -> Option<&str>;
+    fn depend(&self, row: NonZeroU32) -> Option<&CollectionRow>;
+    fn pend(&self, row: NonZeroU32) -> Option<&CollectionRow>;
+
+    fn rows_by_key<'a>(&'a self, key: &str) -> Box<dyn Iterator<Item = NonZeroU32> + 'a>;
+    fn rows_by_depend<'a>(
+        &'a self,
+        depend: &CollectionRow,
+    ) -> Box<dyn Iterator<Item = NonZeroU32> + 'a>;
+    fn rows_by_pend<'a>(
+        &'a self,
+        pend: &CollectionRow,
+    ) -> Box<dyn Iterator<Item = NonZeroU32> + 'a>;
+
+    /// Every key ever stored, including those no relation uses any more.
+    fn keys(&self) -> Box<dyn Iterator<Item = &str> + '_>;
+
+    /// Rows freed and not yet reused.
+    fn free_rows(&self) -> usize;
+
+    /// The size of the storage on disk.
+    fn bytes(&self) -> u64;
+
+    /// The depend index, for storages that keep it in an [IdxFile].
+    fn depend_index(&self) -> Option<&IdxFile<CollectionRow>> {
+        None
+    }
+
+    /// The pend index, for storages that keep it in an [IdxFile].
+    fn pend_index(&self) -> Option<&IdxFile<CollectionRow>> {
+        None
+    }
+}
+
+/// The files [MmapRelationStorage] keeps in the `relation` directory.
+const FILES: [&str; 7] = [
+    "key_name.i",
+    "key_name.d",
+    "key_name.d.f",
+    "fragment.f",
+    "key.i",
+    "depend.i",
+    "pend.i",
+];
+
+/// The default storage: memory-mapped files in the `relation` directory of the database.
+/// Under a [Cipher], key names are stored encrypted and kept decrypted in memory.
+pub struct MmapRelationStorage {
+    dir: PathBuf,
+    fragment: RowFragment,
+    free: usize,
+    key_names: IdxBinary,
+    key: IdxFile<u32>,
+    depend: IdxFile<CollectionRow>,
+    pend: IdxFile<CollectionRow>,
+    cipher: Option<Cipher>,
+    decrypted_key_names: HashMap<u32, String>,
+}
+impl MmapRelationStorage {
+    pub fn new(root_dir: &Path, allocation_lot: u32) -> Self {
+        Self::with_cipher(root_dir, allocation_lot, None)
+    }
+
+    pub(crate) fn with_cipher(
+        root_dir: &Path,
+        allocation_lot: u32,
+        cipher: Option<Cipher>,
+    ) -> Self {
+        Self::open(root_dir, allocation_lot, cipher, false)
+    }
+
+    fn open(root_dir: &Path, allocation_lot: u32, cipher: Option<Cipher>, read_only: bool) -> Self {
+        let dir = root_dir.join("relation");
+        if !dir.exists() {
+            std::fs::create_dir_all(&dir).unwrap();
+        }
+        let mut fragment = RowFragment::new(dir.join("fragment.f"));
+        let free = if read_only {
+            Self::count_free_read_only(&dir)
+        } else {
+            Self::count_free(&mut fragment)
+        };
+        let key_names = IdxBinary::new_ext(dir.join("key_name"), 1);
+        let decrypted_key_names = cipher.as_ref().map_or(HashMap::new(), |cipher| {
+            key_names
+                .as_ref()
+                .iter()
+                .map(|key_id| {
+                    let key = key_names
+                        .value(key_id)
+                        .and_then(|bytes| cipher.decrypt_relation_key(bytes))
+                        .unwrap_or_else(|| panic!("relation key {} fails authentication", key_id));
+                    (key_id.get(), key)
+                })
+                .collect()
+        });
+        Self {
+            key_names,
+            fragment,
+            free,
+            key: IdxFile::new(dir.join("key.i"), allocation_lot),
+            depend: IdxFile::new(dir.join("depend.i"), allocation_lot),
+            pend: IdxFile::new(dir.join("pend.i"), allocation_lot),
+            dir,
+            cipher,
+            decrypted_key_names,
+        }
+    }
+
+    /// Opens the storage without creating any file. None if the `relation` directory has none of them,
+    /// and fails with [io::ErrorKind::InvalidData] if it has only some.
+    pub(crate) fn open_existing(
+        root_dir: &Path,
+        cipher: Option<Cipher>,
+    ) -> io::Result<Option<Self>> {
+        let dir = root_dir.join("relation");
+        let existing = FILES.iter().filter(|file| dir.join(file).is_file()).count();
+        if existing == 0 {
+            Ok(None)
+        } else if existing < FILES.len() {
+            Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("the relation index in {} is incomplete", dir.display()),
+            ))
+        } else {
+            Ok(Some(Self::open(root_dir, 1, cipher, true)))
+        }
+    }
+
+    /// RowFragment has no count, so pop every free row and put them back in the same order.
+    fn count_free(fragment: &mut RowFragment) -> usize {
+        let mut rows = vec![];
+        while let Some(row) = fragment.pop() {
+            rows.push(row);
+        }
+        let free = rows.len();
+        for row in rows.into_iter().rev() {
+            fragment.insert_blank(row);
+        }
+        free
+    }
+
+    /// A fragment mapped read-only cannot be popped, so count the rows it holds after its head, 4 bytes each.
+    fn count_free_read_only(dir: &Path) -> usize {
+        let len = std::fs::metadata(dir.join("fragment.f")).map_or(0, |metadata| metadata.len());
+        (len / 4).saturating_sub(1) as usize
+    }
+
+    fn key_name(&self, key_id: u32) -> Option<&str> {
+        if self.cipher.is_some() {
+            return self.decrypted_key_names.get(&key_id).map(String::as_str);
+        }
+        NonZeroU32::new(key_id)
+            .and_then(|key_id| self.key_names.value(key_id))
+            .map(|bytes| unsafe { std::str::from_utf8_unchecked(bytes) })
+    }
+
+    /// `key` as it is stored in `key_name`.
+    fn stored_key<'a>(&self, key: &'a str) -> Cow<'a, [u8]> {
+        match &self.cipher {
+            Some(cipher) => Cow::Owned(cipher.encrypt_relation_key(key)),
+            None => Cow::Borrowed(key.as_bytes()),
+        }
+    }
+
+    fn key_id_or_insert(&mut self, key: &str) -> u32 {
+        let key_id = self.key_names.row_or_insert(&self.stored_key(key)).get();
+        if self.cipher.is_some() {
+            self.decrypted_key_names
+                .entry(key_id)
+                .or_insert_with(|| key.to_owned());
+        }
+        key_id
+    }
+}
+impl RelationStorage for MmapRelationStorage {
+    fn insert(&mut self, key: &str, depend: &CollectionRow, pend: &CollectionRow) -> NonZeroU32 {
+        let key_id = self.key_id_or_insert(key);
+        if let Some(row) = self.fragment.pop() {
+            self.free -= 1;
+            self.key.update(row, &key_id);
+            self.depend.update(row, depend);
+            self.pend.update(row, pend);
+            row
+        } else {
+            let row = self.key.insert(&key_id);
+            self.depend.insert(depend);
+            self.pend.insert(pend);
+            row
+        }
+    }
+
+    fn delete(&mut self, row: NonZeroU32) {
+        self.key.delete(row);
+        self.depend.delete(row);
+        self.pend.delete(row);
+        self.fragment.insert_blank(row);
+        self.free += 1;
+    }
+
+    fn set_key(&mut self, row: NonZeroU32, key: &str) {
+        let key_id = self.key_id_or_insert(key);
+        self.key.update(row, &key_id);
+    }
+
+    fn key(&self, row: NonZeroU32) -> Option<&str> {
+        self.key
+            .value(row)
+            .and_then(|key_id| self.key_name(*key_id))
+    }
+
+    fn depend(&self, row: NonZeroU32) -> Option<&CollectionRow> {
+        self.depend.value(row)
+    }
+
+    fn pend(&self, row: NonZeroU32) -> Option<&CollectionRow> {
+        self.pend.value(row)
+    }
+
+    fn rows_by_key<'a>(&'a self, key: &str) -> Box<dyn Iterator<Item = NonZeroU32> + 'a> {
+        match self.key_names.row(&self.stored_key(key)) {
+            Some(key_id) => Box::new(self.key.iter_by(&key_id.get())),
+            None => Box::new(std::iter::empty()),
+        }
+    }
+
+    fn rows_by_depend<'a>(
+        &'a self,
+        depend: &CollectionRow,
+    ) -> Box<dyn Iterator<Item = NonZeroU32> + 'a> {
+        Box::new(self.depend.iter_by(depend))
+    }
+
+    fn rows_by_pend<'a>(
+        &'a self,
+        pend: &CollectionRow,
+    ) -> Box<dyn Iterator<Item = NonZeroU32> + 'a> {
+        Box::new(self.pend.iter_by(pend))
+    }
+
+    fn keys(&self) -> Box<dyn Iterator<Item = &str> + '_> {
+        Box::new(
+            self.key_names
+                .as_ref()
+                .iter()
+                .filter_map(|key_id| self.key_name(key_id.get())),
+        )
+    }
+
+    fn free_rows(&self) -> usize {
+        self.free
+    }
+
+    fn bytes(&self) -> u64 {
+        dir_bytes(&self.dir)
+    }
+
+    fn depend_index(&self) -> Option<&IdxFile<CollectionRow>> {
+        Some(&self.depend)
+    }
+
+    fn pend_index(&self) -> Option<&IdxFile<CollectionRow>> {
+        Some(&self.pend)
+    }
+}
+
+/// Relations kept in memory only and lost on drop.
+#[derive(Default)]
+pub struct MemoryRelationStorage {
+    key_names: Vec<String>,
+    key_ids: HashMap<String, usize>,
+    rows: BTreeMap<NonZeroU32, (usize, CollectionRow, CollectionRow)>,
+    by_key: HashMap<usize, BTreeSet<NonZeroU32>>,
+    by_depend: HashMap<CollectionRow, BTreeSet<NonZeroU32>>,
+    by_pend: HashMap<CollectionRow, BTreeSet<NonZeroU32>>,
+    free: Vec<NonZeroU32>,
+}
+impl MemoryRelationStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn key_id(&mut self, key: &str) -> usize {
+        if let Some(key_id) = self.key_ids.get(key) {
+            *key_id
+        } else {
+            self.key_names.push(key.to_owned());
+            self.key_ids
+                .insert(key.to_owned(), self.key_names.len() - 1);
+            self.key_names.len() - 1
+        }
+    }
+
+    fn rows_of<'a, K: std::hash::Hash + Eq>(
+        index: &'a HashMap<K, BTreeSet<NonZeroU32>>,
+        value: &K,
+    ) -> Box<dyn Iterator<Item = NonZeroU32> + 'a> {
+        match index.get(value) {
+            // Highest row first, as the mmap indexes give rows inserted in order.
+            Some(rows) => Box::new(rows.iter().rev().cloned()),
+            None => Box::new(std::iter::empty()),
+        }
+    }
+}
+impl RelationStorage for MemoryRelationStorage {
+    fn insert(&mut self, key: &str, depend: &CollectionRow, pend: &CollectionRow) -> NonZeroU32 {
+        let key_id = self.key_id(key);
+        let row = self.free.pop().unwrap_or_else(|| {
+            NonZeroU32::new(self.rows.last_key_value().map_or(0, |(row, _)| row.get()) + 1).unwrap()
+        });
+        self.rows
+            .insert(row, (key_id, depend.clone(), pend.clone()));
+        self.by_key.entry(key_id).or_default().insert(row);
+        self.by_depend
+            .entry(depend.clone())
+            .or_default()
+            .insert(row);
+        self.by_pend.entry(pend.clone()).or_default().insert(row);
+        row
+    }
+
+    fn delete(&mut self, row: NonZeroU32) {
+        if let Some((key_id, depend, pend)) = self.rows.remove(&row) {
+            self.by_key.entry(key_id).or_default().remove(&row);
+            self.by_depend.entry(depend).or_default().remove(&row);
+            self.by_pend.entry(pend).or_default().remove(&row);
+            self.free.push(row);
+        }
+    }
+
+    fn set_key(&mut self, row: NonZeroU32, key: &str) {
+        let key_id = self.key_id(key);
+        if let Some((old, _, _)) = self.rows.get_mut(&row) {
+            let old = std::mem::replace(old, key_id);
+            self.by_key.entry(old).or_default().remove(&row);
+            self.by_key.entry(key_id).or_default().insert(row);
+        }
+    }
+
+    fn key(&self, row: NonZeroU32) -> Option<&str> {
+        self.rows
+            .get(&row)
+            .map(|(key_id, _, _)| self.key_names[*key_id].as_str())
+    }
+
+    fn depend(&self, row: NonZeroU32) -> Option<&CollectionRow> {
+        self.rows.get(&row).map(|(_, depend, _)| depend)
+    }
+
+    fn pend(&self, row: NonZeroU32) -> Option<&CollectionRow> {
+        self.rows.get(&row).map(|(_, _, pend)| pend)
+    }
+
+    fn rows_by_key<'a>(&'a self, key: &str) -> Box<dyn Iterator<Item = NonZeroU32> + 'a> {
+        match self.key_ids.get(key) {
+            Some(key_id) => Self::rows_of(&self.by_key, key_id),
+            None => Box::new(std::iter::empty()),
+        }
+    }
+
+    fn rows_by_depend<'a>(
+        &'a self,
+        depend: &CollectionRow,
+    ) -> Box<dyn Iterator<Item = NonZeroU32> + 'a> {
+        Self::rows_of(&self.by_depend, depend)
+    }
+
+    fn rows_by_pend<'a>(
+        &'a self,
+        pend: &CollectionRow,
+    ) -> Box<dyn Iterator<Item = NonZeroU32> + 'a> {
+        Self::rows_of(&self.by_pend, pend)
+    }
+
+    fn keys(&self) -> Box<dyn Iterator<Item = &str> + '_> {
+        Box::new(self.key_names.iter().map(|key| key.as_str()))
+    }
+
+    fn free_rows(&self) -> usize {
+        self.free.len()
+    }
+
+    fn bytes(&self) -> u64 {
+        0
+    }
+}
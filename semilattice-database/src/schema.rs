@@ -0,0 +1,424 @@
+use std::{
+    collections::BTreeMap,
+    fmt, io,
+    num::{NonZeroI32, NonZeroU32},
+    path::Path,
+    time::SystemTime,
+};
+
+use hashbrown::HashMap;
+use serde::{Deserialize, Serialize};
+use versatile_data::{idx_binary::AvltrieeSearch, Activity, AvltrieeIter, FieldName, Term};
+
+use crate::{codec::Codec, Collection, CollectionRow, Database};
+
+const SCHEMA_FILE: &str = "schema.toml";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldType {
+    Bytes,
+    Utf8,
+    I64,
+    U64,
+    F64,
+    Bool,
+    Timestamp,
+}
+impl FieldType {
+    /// Whether `bytes` is a value of this type as written by [Codec].
+    pub fn accepts(&self, bytes: &[u8]) -> bool {
+        match self {
+            Self::Bytes => true,
+            Self::Utf8 => String::decode(bytes).is_some(),
+            Self::I64 => i64::decode(bytes).is_some(),
+            Self::U64 => u64::decode(bytes).is_some(),
+            Self::F64 => f64::decode(bytes).is_some(),
+            Self::Bool => bool::decode(bytes).is_some(),
+            Self::Timestamp => SystemTime::decode(bytes).is_some(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FieldSchema {
+    pub field_type: FieldType,
+    #[serde(default)]
+    pub required: bool,
+    /// Maximum length in bytes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_length: Option<usize>,
+}
+impl FieldSchema {
+    pub fn new(field_type: FieldType) -> Self {
+        Self {
+            field_type,
+            required: false,
+            max_length: None,
+        }
+    }
+
+    pub fn required(mut self) -> Self {
+        self.required = true;
+        self
+    }
+
+    pub fn max_length(mut self, max_length: usize) -> Self {
+        self.max_length = Some(max_length);
+        self
+    }
+}
+
+/// The fields a collection accepts. Fields not declared here are rejected.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct Schema {
+    /// Field combinations no two rows may share. A row missing any of the fields is not constrained.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub unique: Vec<Vec<FieldName>>,
+    pub fields: BTreeMap<FieldName, FieldSchema>,
+}
+impl Schema {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn field(mut self, field_name: FieldName, field: FieldSchema) -> Self {
+        self.fields.insert(field_name, field);
+        self
+    }
+
+    pub fn unique(mut self, field_names: Vec<FieldName>) -> Self {
+        self.unique.push(field_names);
+        self
+    }
+
+    /// Checks the fields of a write. Required fields are only checked for new rows, since an update keeps the fields it does not set.
+    pub fn validate(&self, fields: &HashMap<FieldName, Vec<u8>>, new_row: bool) -> Vec<FieldError> {
+        let mut errors: Vec<_> = fields
+            .iter()
+            .filter_map(|(field_name, value)| {
+                let Some(field) = self.fields.get(field_name) else {
+                    return Some(FieldError::Unknown(field_name.clone()));
+                };
+                if !field.field_type.accepts(value) {
+                    return Some(FieldError::Type {
+                        field_name: field_name.clone(),
+                        expected: field.field_type,
+                    });
+                }
+                field
+                    .max_length
+                    .filter(|max_length| value.len() > *max_length)
+                    .map(|max_length| FieldError::TooLong {
+                        field_name: field_name.clone(),
+                        max_length,
+                        length: value.len(),
+                    })
+            })
+            .collect();
+        if new_row {
+            errors.extend(
+                self.fields
+                    .iter()
+                    .filter(|(field_name, field)| {
+                        field.required && !fields.contains_key(*field_name)
+                    })
+                    .map(|(field_name, _)| FieldError::Missing(field_name.clone())),
+            );
+        }
+        errors.sort_by(|a, b| a.field_name().cmp(b.field_name()));
+        errors
+    }
+
+    /// None if the collection has no schema. Fails with [io::ErrorKind::InvalidData] if the file does not parse.
+    pub(crate) fn load(dir: &Path) -> io::Result<Option<Self>> {
+        let path = dir.join(SCHEMA_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+        toml::from_str(&std::fs::read_to_string(&path)?)
+            .map(Some)
+            .map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{}: {}", path.display(), e),
+                )
+            })
+    }
+
+    pub(crate) fn save(schema: Option<&Self>, dir: &Path) {
+        let path = dir.join(SCHEMA_FILE);
+        if let Some(schema) = schema {
+            std::fs::create_dir_all(dir).unwrap();
+            std::fs::write(path, toml::to_string(schema).unwrap()).unwrap();
+        } else if path.exists() {
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FieldError {
+    Unknown(FieldName),
+    Missing(FieldName),
+    Type {
+        field_name: FieldName,
+        expected: FieldType,
+    },
+    TooLong {
+        field_name: FieldName,
+        max_length: usize,
+        length: usize,
+    },
+}
+impl FieldError {
+    pub fn field_name(&self) -> &FieldName {
+        match self {
+            Self::Unknown(field_name) | Self::Missing(field_name) => field_name,
+            Self::Type { field_name, .. } | Self::TooLong { field_name, .. } => field_name,
+        }
+    }
+}
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unknown(field_name) => write!(f, "unknown field {}", field_name),
+            Self::Missing(field_name) => write!(f, "missing required field {}", field_name),
+            Self::Type {
+                field_name,
+                expected,
+            } => write!(f, "field {} is not {:?}", field_name, expected),
+            Self::TooLong {
+                field_name,
+                max_length,
+                length,
+            } => write!(
+                f,
+                "field {} is {} bytes, longer than {}",
+                field_name, length, max_length
+            ),
+        }
+    }
+}
+
+/// A write rejected by the schema of its collection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationError {
+    pub collection_id: NonZeroI32,
+    pub errors: Vec<FieldError>,
+}
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "collection {}: ", self.collection_id)?;
+        for (i, error) in self.errors.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", error)?;
+        }
+        Ok(())
+    }
+}
+impl std::error::Error for ValidationError {}
+
+/// A write that would give a row the same values as `existing` for a unique constraint.
+/// Rows held only by a session have a negative collection id.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UniqueConflict {
+    pub collection_id: NonZeroI32,
+    pub field_names: Vec<FieldName>,
+    pub existing: CollectionRow,
+}
+impl fmt::Display for UniqueConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "collection {}: ({}) already used by row {} of collection {}",
+            self.collection_id,
+            self.field_names
+                .iter()
+                .map(|field_name| field_name.as_str())
+                .collect::<Vec<_>>()
+                .join(", "),
+            self.existing.row(),
+            self.existing.collection_id()
+        )
+    }
+}
+impl std::error::Error for UniqueConflict {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WriteError {
+    Validation(ValidationError),
+    Conflict(UniqueConflict),
+    /// The database was opened with [crate::Database::open_read_only].
+    ReadOnly,
+    /// Full-text and composite indexes would store field values in plain text, so they cannot be created on an encrypted database.
+    Encrypted,
+}
+impl From<ValidationError> for WriteError {
+    fn from(e: ValidationError) -> Self {
+        Self::Validation(e)
+    }
+}
+impl From<UniqueConflict> for WriteError {
+    fn from(e: UniqueConflict) -> Self {
+        Self::Conflict(e)
+    }
+}
+impl fmt::Display for WriteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Validation(e) => e.fmt(f),
+            Self::Conflict(e) => e.fmt(f),
+            Self::ReadOnly => write!(f, "the database is opened read-only"),
+            Self::Encrypted => write!(f, "the index cannot be created on an encrypted database"),
+        }
+    }
+}
+impl std::error::Error for WriteError {}
+
+impl Collection {
+    /// Checks a write against the schema of this collection, if it has one.
+    pub fn validate(
+        &self,
+        fields: &HashMap<FieldName, Vec<u8>>,
+        new_row: bool,
+    ) -> Result<(), ValidationError> {
+        let Some(schema) = self.schema() else {
+            return Ok(());
+        };
+        let errors = schema.validate(fields, new_row);
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ValidationError {
+                collection_id: self.id(),
+                errors,
+            })
+        }
+    }
+
+    /// The values a write gives to `field_names`, taking those it does not set from `row`, as they are stored.
+    /// None if any of them would be missing.
+    pub fn unique_key(
+        &self,
+        field_names: &[FieldName],
+        row: Option<NonZeroU32>,
+        fields: &HashMap<FieldName, Vec<u8>>,
+    ) -> Option<Vec<Vec<u8>>> {
+        field_names
+            .iter()
+            .map(|field_name| {
+                fields
+                    .get(field_name)
+                    .map(|value| self.encrypt_value(field_name, value).into_owned())
+                    .or_else(|| {
+                        row.and_then(|row| {
+                            self.data()
+                                .fields()
+                                .get(field_name)
+                                .and_then(|field| field.value(row))
+                                .map(|bytes| bytes.to_vec())
+                        })
+                    })
+            })
+            .collect()
+    }
+
+    /// The rows whose values of `field_names` are exactly `key`.
+    pub fn unique_rows(&self, field_names: &[FieldName], key: &[Vec<u8>]) -> Vec<NonZeroU32> {
+        let fields: Option<Vec<_>> = field_names
+            .iter()
+            .map(|field_name| self.data().fields().get(field_name))
+            .collect();
+        let Some(fields) = fields.filter(|fields| !fields.is_empty()) else {
+            return vec![];
+        };
+        AvltrieeIter::by(fields[0], &key[0])
+            .filter(|row| {
+                fields
+                    .iter()
+                    .zip(key.iter())
+                    .all(|(field, value)| field.value(*row) == Some(value.as_slice()))
+            })
+            .collect()
+    }
+
+    /// Checks a write of `row`, or of a new row, against the unique constraints of the schema.
+    pub fn check_unique(
+        &self,
+        row: Option<NonZeroU32>,
+        fields: &HashMap<FieldName, Vec<u8>>,
+    ) -> Result<(), UniqueConflict> {
+        let Some(schema) = self.schema() else {
+            return Ok(());
+        };
+        for field_names in schema.unique.iter() {
+            if let Some(key) = self.unique_key(field_names, row, fields) {
+                if let Some(existing) = self
+                    .unique_rows(field_names, &key)
+                    .into_iter()
+                    .find(|r| Some(*r) != row)
+                {
+                    return Err(UniqueConflict {
+                        collection_id: self.id(),
+                        field_names: field_names.clone(),
+                        existing: CollectionRow::new(self.id(), existing),
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn insert(
+        &mut self,
+        activity: Activity,
+        term_begin: Term,
+        term_end: Term,
+        fields: HashMap<FieldName, Vec<u8>>,
+    ) -> Result<NonZeroU32, WriteError> {
+        self.check_writable()?;
+        self.validate(&fields, true)?;
+        self.check_unique(None, &fields)?;
+        self.insert_unchecked(activity, term_begin, term_end, fields)
+            .await
+    }
+
+    pub async fn update(
+        &mut self,
+        row: NonZeroU32,
+        activity: Activity,
+        term_begin: Term,
+        term_end: Term,
+        fields: HashMap<FieldName, Vec<u8>>,
+    ) -> Result<(), WriteError> {
+        self.check_writable()?;
+        self.validate(&fields, false)?;
+        self.check_unique(Some(row), &fields)?;
+        self.update_unchecked(row, activity, term_begin, term_end, fields)
+            .await
+    }
+}
+
+impl Database {
+    /// Sets or removes the schema of a collection. It is stored in the collection's directory and applies to writes from then on.
+    pub fn set_schema(
+        &mut self,
+        collection_id: NonZeroI32,
+        schema: Option<Schema>,
+    ) -> Result<(), WriteError> {
+        self.check_writable()?;
+        if let Some(collection) = self.collections.get_mut(&collection_id) {
+            Schema::save(
+                schema.as_ref(),
+                &self
+                    .collections_dir
+                    .join(collection_id.to_string() + "_" + collection.name()),
+            );
+            collection.set_schema(schema);
+        }
+        Ok(())
+    }
+}
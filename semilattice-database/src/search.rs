@@ -1,26 +1,40 @@
 mod condition;
+mod explain;
 mod join;
+mod plan;
+mod record;
+mod remote;
 mod result;
 
 pub use self::join::SearchJoin;
 pub use condition::Condition;
+pub use explain::{Explain, ExplainTarget, Method};
+pub use plan::{Access, Cost};
+pub use record::{BytesDecoder, FieldDecoder, Record, RecordSelect, Utf8Decoder};
 pub use result::SearchResult;
 
 pub use versatile_data::search::{Field, Number, Term};
 
-use crate::Database;
+use crate::{
+    codec::{Codec, FieldValue},
+    fulltext::FullText,
+    Database,
+};
 
 use std::{num::NonZeroI32, sync::Arc};
 
 use hashbrown::HashMap;
+use serde::{Deserialize, Serialize};
 
 use versatile_data::{Activity, FieldName};
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Search {
     collection_id: NonZeroI32,
     conditions: Vec<Condition>,
     join: HashMap<Arc<String>, SearchJoin>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    as_of: Option<u64>,
 }
 
 impl Search {
@@ -33,6 +47,7 @@ impl Search {
             collection_id,
             conditions,
             join,
+            as_of: None,
         }
     }
 
@@ -44,6 +59,21 @@ impl Search {
         &self.conditions
     }
 
+    pub fn join(&self) -> &HashMap<Arc<String>, SearchJoin> {
+        &self.join
+    }
+
+    pub fn as_of(&self) -> Option<u64> {
+        self.as_of
+    }
+
+    /// Evaluates the conditions against the rows as they were at `at`, in milliseconds since the UNIX epoch, read from the collection's history.
+    /// Collections without history have nothing to match. Relations and joins are always the current ones.
+    pub fn at(mut self, at: u64) -> Self {
+        self.as_of = Some(at);
+        self
+    }
+
     pub fn search(mut self, condition: Condition) -> Self {
         self.conditions.push(condition);
         self
@@ -59,6 +89,19 @@ impl Search {
         self.search(Condition::Field(field_name, condition))
     }
 
+    /// Searches a field written with [Codec](crate::codec::Codec).
+    pub fn search_field_value<T: Codec>(
+        self,
+        field_name: FieldName,
+        condition: FieldValue<T>,
+    ) -> Self {
+        self.search_field(field_name, condition.into())
+    }
+
+    pub fn search_fulltext(self, field_name: FieldName, query: FullText) -> Self {
+        self.search(Condition::FullText(field_name, query))
+    }
+
     pub fn search_row(self, condition: Number) -> Self {
         self.search(Condition::Row(condition))
     }
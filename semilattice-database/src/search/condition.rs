@@ -2,22 +2,26 @@ use std::sync::Arc;
 
 use async_recursion::async_recursion;
 use futures::future;
+use serde::{Deserialize, Serialize};
 
 use versatile_data::{
     search::{Field, Number, Term},
     Activity, Condition as VersatileDataCondition, FieldName, RowSet,
 };
 
-use crate::{Collection, CollectionRow, RelationIndex, Search};
+use crate::{fulltext::FullText, Collection, CollectionRow, RelationIndex, Search};
 
-#[derive(Debug, Clone, PartialEq)]
+use super::remote::{uuids, ActivityDef, FieldDef, NumberDef, TermDef};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Condition {
-    Activity(Activity),
-    Term(Term),
-    Row(Number),
-    Uuid(Vec<u128>),
-    LastUpdated(Number),
-    Field(FieldName, Field),
+    Activity(#[serde(with = "ActivityDef")] Activity),
+    Term(#[serde(with = "TermDef")] Term),
+    Row(#[serde(with = "NumberDef")] Number),
+    Uuid(#[serde(with = "uuids")] Vec<u128>),
+    LastUpdated(#[serde(with = "NumberDef")] Number),
+    Field(FieldName, #[serde(with = "FieldDef")] Field),
+    FullText(FieldName, FullText),
     Narrow(Vec<Condition>),
     Wide(Vec<Condition>),
     Depend(Option<Arc<String>>, CollectionRow),
@@ -56,12 +60,24 @@ impl Condition {
                     .result_condition(&VersatileDataCondition::LastUpdated(c))
                     .await
             }
-            Self::Field(name, condition) => {
-                collection
+            Self::Field(name, condition) => match collection.index_condition(name, condition) {
+                Some(condition) => {
+                    collection
+                        .data()
+                        .result_condition(&VersatileDataCondition::Field(
+                            Arc::clone(name),
+                            &condition,
+                        ))
+                        .await
+                }
+                None => collection
                     .data()
-                    .result_condition(&VersatileDataCondition::Field(Arc::clone(name), condition))
-                    .await
-            }
+                    .all()
+                    .into_iter()
+                    .filter(|row| self.is_match(collection, relation, *row))
+                    .collect(),
+            },
+            Self::FullText(name, query) => collection.fulltext_search(name, query),
             Self::Depend(key, collection_row) => {
                 let collection_id = collection.id();
                 relation
@@ -73,15 +89,13 @@ impl Condition {
             Self::Narrow(conditions) => {
                 Search::result_conditions(collection, conditions, relation).await
             }
-            Self::Wide(conditions) => future::join_all(
-                conditions
+            Self::Wide(conditions) => {
+                future::join_all(conditions.iter().map(|c| c.result(collection, relation)))
+                    .await
                     .into_iter()
-                    .map(|c| c.result(collection, relation)),
-            )
-            .await
-            .into_iter()
-            .flatten()
-            .collect(),
+                    .flatten()
+                    .collect()
+            }
         }
     }
 }
@@ -0,0 +1,165 @@
+use std::{
+    fmt,
+    num::NonZeroI32,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use hashbrown::HashMap;
+
+use crate::{Condition, Database, Search};
+
+use super::{Access, SearchResult};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Method {
+    Index,
+    Scan,
+    /// Checked row by row against the candidates left by the preceding conditions.
+    Filter,
+}
+impl From<Access> for Method {
+    fn from(access: Access) -> Self {
+        match access {
+            Access::Index => Self::Index,
+            Access::Scan => Self::Scan,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExplainTarget {
+    Search(NonZeroI32),
+    Condition(Condition),
+    /// Conditions checked against the versions of the rows at a past instant.
+    History {
+        at: u64,
+        conditions: Vec<Condition>,
+    },
+    /// Conditions answered together by a composite index.
+    Composite {
+        name: String,
+        conditions: Vec<Condition>,
+    },
+    Join {
+        name: Arc<String>,
+        collection_id: NonZeroI32,
+        relation_key: Option<Arc<String>>,
+        parent_rows: usize,
+    },
+}
+
+/// A node of the tree returned by [Search::explain].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Explain {
+    pub target: ExplainTarget,
+    pub method: Option<Method>,
+    pub estimated_rows: Option<usize>,
+    pub rows: usize,
+    pub elapsed: Duration,
+    pub children: Vec<Explain>,
+}
+impl Explain {
+    fn fmt_indent(&self, f: &mut fmt::Formatter<'_>, depth: usize) -> fmt::Result {
+        write!(f, "{:width$}", "", width = depth * 2)?;
+        match &self.target {
+            ExplainTarget::Search(collection_id) => {
+                write!(f, "Search collection={}", collection_id)?
+            }
+            ExplainTarget::Condition(condition) => write!(f, "{:?}", condition)?,
+            ExplainTarget::History { at, conditions } => {
+                write!(f, "History at={} {:?}", at, conditions)?
+            }
+            ExplainTarget::Composite { name, conditions } => {
+                write!(f, "Composite {} {:?}", name, conditions)?
+            }
+            ExplainTarget::Join {
+                name,
+                collection_id,
+                relation_key,
+                parent_rows,
+            } => write!(
+                f,
+                "Join {} collection={} relation_key={:?} parent_rows={}",
+                name, collection_id, relation_key, parent_rows
+            )?,
+        }
+        if let Some(method) = self.method {
+            write!(f, " {:?}", method)?;
+        }
+        if let Some(estimated_rows) = self.estimated_rows {
+            write!(f, " estimated={}", estimated_rows)?;
+        }
+        writeln!(f, " rows={} elapsed={:?}", self.rows, self.elapsed)?;
+        for child in self.children.iter() {
+            child.fmt_indent(f, depth + 1)?;
+        }
+        Ok(())
+    }
+}
+impl fmt::Display for Explain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_indent(f, 0)
+    }
+}
+
+impl Search {
+    /// Runs the search and reports the rows, time and access method of every condition and join.
+    pub async fn explain(&self, database: &Database) -> Explain {
+        self.clone().result_explain(database).await.1
+    }
+
+    pub async fn result_explain(self, database: &Database) -> (SearchResult, Explain) {
+        let started = Instant::now();
+        let collection_id = self.collection_id;
+        let mut children = vec![];
+        let mut join = HashMap::new();
+        let rows = if let Some(collection) = database.collection(collection_id) {
+            let rows = if let Some(at) = self.as_of {
+                let started = Instant::now();
+                let rows =
+                    Self::version_conditions(collection, &self.conditions, &database.relation, at);
+                children.push(Explain {
+                    target: ExplainTarget::History {
+                        at,
+                        conditions: self.conditions.clone(),
+                    },
+                    method: Some(Method::Filter),
+                    estimated_rows: None,
+                    rows: rows.len(),
+                    elapsed: started.elapsed(),
+                    children: vec![],
+                });
+                rows
+            } else {
+                Self::narrow_conditions(
+                    collection,
+                    &self.conditions,
+                    &database.relation,
+                    None,
+                    Some(&mut children),
+                )
+                .await
+            };
+            for (name, search_join) in self.join.iter() {
+                let (result, explain) = search_join
+                    .join_result_explain(database, name, collection_id, &rows)
+                    .await;
+                join.insert(Arc::clone(name), result);
+                children.push(explain);
+            }
+            rows
+        } else {
+            Default::default()
+        };
+        let explain = Explain {
+            target: ExplainTarget::Search(collection_id),
+            method: None,
+            estimated_rows: None,
+            rows: rows.len(),
+            elapsed: started.elapsed(),
+            children,
+        };
+        (SearchResult::new(Some(self), rows, join), explain)
+    }
+}
@@ -1,18 +1,20 @@
 use std::{
     num::{NonZeroI32, NonZeroU32},
     sync::Arc,
+    time::Instant,
 };
 
 use async_recursion::async_recursion;
-use futures::{future, FutureExt};
+use futures::future;
 use hashbrown::HashMap;
+use serde::{Deserialize, Serialize};
 use versatile_data::RowSet;
 
 use crate::{CollectionRow, Condition, Database, Search};
 
-use super::SearchResult;
+use super::{Explain, ExplainTarget, SearchResult};
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct SearchJoin {
     collection_id: NonZeroI32,
     relation_key: Option<Arc<String>>,
@@ -35,47 +37,72 @@ impl SearchJoin {
         }
     }
 
+    pub fn collection_id(&self) -> NonZeroI32 {
+        self.collection_id
+    }
+
+    pub fn relation_key(&self) -> Option<&Arc<String>> {
+        self.relation_key.as_ref()
+    }
+
+    pub fn conditions(&self) -> &Vec<Condition> {
+        &self.conditions
+    }
+
+    pub fn join(&self) -> &HashMap<Arc<String>, SearchJoin> {
+        &self.join
+    }
+
+    /// The search that this join runs on its own collection, kept in each joined [SearchResult].
+    pub fn search(&self) -> Search {
+        Search::new(
+            self.collection_id,
+            self.conditions.clone(),
+            self.join.clone(),
+        )
+    }
+
+    async fn rows(
+        &self,
+        database: &Database,
+        parent_collection_id: NonZeroI32,
+        parent_row: NonZeroU32,
+    ) -> RowSet {
+        if let Some(collection) = database.collection(self.collection_id) {
+            let candidates = self.relation_key.as_ref().map(|key| {
+                database
+                    .relation
+                    .pends(
+                        Some(Arc::clone(key)),
+                        &CollectionRow::new(parent_collection_id, parent_row),
+                        Some(self.collection_id),
+                    )
+                    .into_iter()
+                    .map(|r| r.row())
+                    .collect()
+            });
+            Search::narrow_conditions(
+                collection,
+                &self.conditions,
+                &database.relation,
+                candidates,
+                None,
+            )
+            .await
+        } else {
+            RowSet::default()
+        }
+    }
+
     #[async_recursion(?Send)]
     async fn join_result_row(
         &self,
         database: &Database,
+        search: &Arc<Search>,
         parent_collection_id: NonZeroI32,
         parent_row: NonZeroU32,
     ) -> SearchResult {
-        let mut futs = vec![];
-        if let Some(key) = &self.relation_key {
-            futs.push(
-                async {
-                    database
-                        .relation
-                        .pends(
-                            Some(Arc::clone(key)),
-                            &CollectionRow::new(parent_collection_id, parent_row),
-                            Some(self.collection_id),
-                        )
-                        .into_iter()
-                        .map(|r| r.row())
-                        .collect()
-                }
-                .boxed_local(),
-            );
-        }
-        if self.conditions.len() > 0 {
-            if let Some(collection) = database.collection(self.collection_id) {
-                futs.push(
-                    async {
-                        Search::result_conditions(collection, &self.conditions, &database.relation)
-                            .await
-                    }
-                    .boxed_local(),
-                );
-            }
-        }
-
-        let (mut rows, _index, futs) = future::select_all(futs).await;
-        for r in future::join_all(futs).await.into_iter() {
-            rows.retain(|v| r.contains(v));
-        }
+        let rows = self.rows(database, parent_collection_id, parent_row).await;
 
         let join_nest = future::join_all(self.join.iter().map(|(key, join)| async {
             (
@@ -87,7 +114,7 @@ impl SearchJoin {
         .into_iter()
         .collect();
 
-        SearchResult::new(None, rows, join_nest)
+        SearchResult::shared(Some(Arc::clone(search)), rows, join_nest)
     }
 
     pub async fn join_result(
@@ -96,10 +123,11 @@ impl SearchJoin {
         parent_collection_id: NonZeroI32,
         parent_rows: &RowSet,
     ) -> HashMap<NonZeroU32, SearchResult> {
-        future::join_all(parent_rows.into_iter().map(|parent_row| async {
+        let search = Arc::new(self.search());
+        future::join_all(parent_rows.iter().map(|parent_row| async {
             (
                 *parent_row,
-                self.join_result_row(database, parent_collection_id, *parent_row)
+                self.join_result_row(database, &search, parent_collection_id, *parent_row)
                     .await,
             )
         }))
@@ -107,4 +135,74 @@ impl SearchJoin {
         .into_iter()
         .collect()
     }
+
+    #[async_recursion(?Send)]
+    pub(super) async fn join_result_explain(
+        &self,
+        database: &Database,
+        name: &Arc<String>,
+        parent_collection_id: NonZeroI32,
+        parent_rows: &RowSet,
+    ) -> (HashMap<NonZeroU32, SearchResult>, Explain) {
+        let started = Instant::now();
+        let mut rows_by_parent = vec![];
+        let mut all_rows = RowSet::default();
+        for parent_row in parent_rows.iter() {
+            let rows = self.rows(database, parent_collection_id, *parent_row).await;
+            all_rows.extend(rows.iter());
+            rows_by_parent.push((*parent_row, rows));
+        }
+
+        let mut children = vec![];
+        let mut nests = vec![];
+        for (key, join) in self.join.iter() {
+            let (result, explain) = join
+                .join_result_explain(database, key, self.collection_id, &all_rows)
+                .await;
+            nests.push((key, result));
+            children.push(explain);
+        }
+
+        let search = Arc::new(self.search());
+        let mut joined_rows = 0;
+        let result = rows_by_parent
+            .into_iter()
+            .map(|(parent_row, rows)| {
+                joined_rows += rows.len();
+                let join_nest = nests
+                    .iter()
+                    .map(|(key, result)| {
+                        (
+                            Arc::clone(key),
+                            rows.iter()
+                                .filter_map(|row| {
+                                    result.get(row).map(|result| (*row, result.clone()))
+                                })
+                                .collect(),
+                        )
+                    })
+                    .collect();
+                (
+                    parent_row,
+                    SearchResult::shared(Some(Arc::clone(&search)), rows, join_nest),
+                )
+            })
+            .collect();
+        (
+            result,
+            Explain {
+                target: ExplainTarget::Join {
+                    name: Arc::clone(name),
+                    collection_id: self.collection_id,
+                    relation_key: self.relation_key.clone(),
+                    parent_rows: parent_rows.len(),
+                },
+                method: None,
+                estimated_rows: None,
+                rows: joined_rows,
+                elapsed: started.elapsed(),
+                children,
+            },
+        )
+    }
 }
@@ -0,0 +1,401 @@
+use std::{cmp::Ordering, num::NonZeroU32, time::Instant};
+
+use async_recursion::async_recursion;
+
+use versatile_data::{
+    idx_binary::{AvltrieeSearch, IdxBinary},
+    search::{Field, Number, Term},
+    AvltrieeIter, RowSet,
+};
+
+use crate::{history::RowVersion, Collection, CollectionRow, Condition, RelationIndex, Search};
+
+use super::{Explain, ExplainTarget, Method};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Access {
+    Index,
+    Scan,
+}
+
+/// Estimated number of rows a condition produces and how they are found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cost {
+    pub rows: usize,
+    pub access: Access,
+}
+impl PartialOrd for Cost {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for Cost {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Scans are always planned after index lookups, whatever their estimate.
+        self.access
+            .cmp(&other.access)
+            .then(self.rows.cmp(&other.rows))
+    }
+}
+
+impl Collection {
+    /// Upper bound of the rows in this collection, read from the field index headers.
+    pub fn rows_estimate(&self) -> usize {
+        self.data()
+            .fields()
+            .values()
+            .map(|field| field.as_ref().rows_count() as usize)
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+impl Condition {
+    pub fn cost(&self, collection: &Collection, relation: &RelationIndex) -> Cost {
+        let total = collection.rows_estimate();
+        let index = |rows: usize| Cost {
+            rows: rows.min(total),
+            access: Access::Index,
+        };
+        let scan = Cost {
+            rows: total,
+            access: Access::Scan,
+        };
+        match self {
+            Self::Row(Number::In(rows)) => index(rows.len()),
+            Self::Row(Number::Range(range)) => index(
+                range
+                    .end()
+                    .saturating_sub(*range.start())
+                    .saturating_add(1)
+                    .max(0) as usize,
+            ),
+            Self::Row(_) => scan,
+            Self::Uuid(uuids) => index(uuids.len()),
+            Self::Depend(_, collection_row) => {
+                index(relation.rows_by_depend(collection_row).count())
+            }
+            Self::Activity(_) | Self::Term(_) => index(total),
+            Self::LastUpdated(Number::In(values)) => index(values.len()),
+            Self::LastUpdated(_) => index(total / 2),
+            Self::Field(name, condition) => match collection.index_condition(name, condition) {
+                Some(condition) => collection
+                    .data()
+                    .fields()
+                    .get(name)
+                    .map_or(index(0), |field| match condition.as_ref() {
+                        Field::Match(v) => index(AvltrieeIter::by(field, v).count()),
+                        Field::Range(_, _) => index(total / 3),
+                        Field::Min(_) | Field::Max(_) => index(total / 2),
+                        _ => scan,
+                    }),
+                None => scan,
+            },
+            Self::FullText(name, query) => collection
+                .fulltext(name)
+                .map_or(scan, |fulltext| index(query.estimate(fulltext))),
+            Self::Narrow(conditions) => conditions
+                .iter()
+                .map(|c| c.cost(collection, relation))
+                .min()
+                .unwrap_or(scan),
+            Self::Wide(conditions) => conditions.iter().fold(index(0), |sum, c| {
+                let cost = c.cost(collection, relation);
+                Cost {
+                    rows: (sum.rows + cost.rows).min(total),
+                    access: sum.access.max(cost.access),
+                }
+            }),
+        }
+    }
+
+    /// Checks a single row without touching the indexes of other rows.
+    pub(crate) fn is_match(
+        &self,
+        collection: &Collection,
+        relation: &RelationIndex,
+        row: NonZeroU32,
+    ) -> bool {
+        let data = collection.data();
+        match self {
+            Self::Activity(activity) => data.activity(row) == Some(*activity),
+            Self::Term(condition) => {
+                if let (Some(begin), Some(end)) = (data.term_begin(row), data.term_end(row)) {
+                    match condition {
+                        Term::In(base) => begin <= base && (*end == 0 || end > base),
+                        Term::Future(base) => begin >= base,
+                        Term::Past(base) => *end > 0 && end <= base,
+                    }
+                } else {
+                    false
+                }
+            }
+            Self::Row(condition) => number_match(condition, row.get() as isize),
+            Self::Uuid(uuids) => data.uuid(row).is_some_and(|uuid| uuids.contains(uuid)),
+            Self::LastUpdated(condition) => data
+                .last_updated(row)
+                .is_some_and(|v| number_match(condition, *v as isize)),
+            Self::Field(name, condition) => data
+                .fields()
+                .get(name)
+                .and_then(|field| field.value(row))
+                .and_then(|bytes| collection.decrypt(name, bytes))
+                .is_some_and(|bytes| field_match(condition, &bytes)),
+            Self::FullText(name, query) => data
+                .fields()
+                .get(name)
+                .and_then(|field| field.value(row))
+                .is_some_and(|bytes| {
+                    query.is_match_text(
+                        &collection.fulltext_option(name),
+                        &String::from_utf8_lossy(bytes),
+                    )
+                }),
+            Self::Depend(key, depend) => {
+                let key = key.as_ref().map(|key| key.as_str());
+                relation
+                    .rows_by_pend(&CollectionRow::new(collection.id(), row))
+                    .any(|relation_row| {
+                        relation.depend(relation_row) == Some(depend)
+                            && key.is_none_or(|key| relation.key(relation_row) == key)
+                    })
+            }
+            Self::Narrow(conditions) => conditions
+                .iter()
+                .all(|c| c.is_match(collection, relation, row)),
+            Self::Wide(conditions) => conditions
+                .iter()
+                .any(|c| c.is_match(collection, relation, row)),
+        }
+    }
+
+    /// Checks a row as it was in `version`, one of its past versions.
+    pub(crate) fn is_match_version(
+        &self,
+        collection: &Collection,
+        relation: &RelationIndex,
+        row: NonZeroU32,
+        version: &RowVersion,
+    ) -> bool {
+        match self {
+            Self::Activity(activity) => version.activity == *activity,
+            Self::Term(condition) => {
+                let (begin, end) = (version.term_begin, version.term_end);
+                match condition {
+                    Term::In(base) => begin <= *base && (end == 0 || end > *base),
+                    Term::Future(base) => begin >= *base,
+                    Term::Past(base) => end > 0 && end <= *base,
+                }
+            }
+            Self::Row(condition) => number_match(condition, row.get() as isize),
+            Self::Uuid(uuids) => uuids.contains(&version.uuid),
+            // Last update times are in seconds, history times in milliseconds.
+            Self::LastUpdated(condition) => number_match(condition, (version.at / 1000) as isize),
+            Self::Field(name, condition) => version
+                .fields
+                .get(name)
+                .and_then(|bytes| collection.decrypt(name, bytes))
+                .is_some_and(|bytes| field_match(condition, &bytes)),
+            Self::FullText(name, query) => version.fields.get(name).is_some_and(|bytes| {
+                query.is_match_text(
+                    &collection.fulltext_option(name),
+                    &String::from_utf8_lossy(bytes),
+                )
+            }),
+            Self::Depend(..) => self.is_match(collection, relation, row),
+            Self::Narrow(conditions) => conditions
+                .iter()
+                .all(|c| c.is_match_version(collection, relation, row, version)),
+            Self::Wide(conditions) => conditions
+                .iter()
+                .any(|c| c.is_match_version(collection, relation, row, version)),
+        }
+    }
+}
+
+impl Search {
+    /// Evaluates the conditions from the most selective one.
+    /// Conditions answered by a composite index are looked up through it first.
+    /// Once the candidates are fewer than a condition would produce, the condition is checked row by row instead of through its index.
+    /// Each step is recorded to `trace` when it is given.
+    #[async_recursion(?Send)]
+    pub(crate) async fn narrow_conditions(
+        collection: &Collection,
+        conditions: &[Condition],
+        relation: &RelationIndex,
+        candidates: Option<RowSet>,
+        mut trace: Option<&'async_recursion mut Vec<Explain>>,
+    ) -> RowSet {
+        let mut rows = candidates;
+        let composite = collection.composite_plan(conditions);
+        if let Some(ref plan) = composite {
+            let started = Instant::now();
+            let mut r = plan.rows();
+            // Key order can differ from the field comparison for escaped bytes, so the covered conditions are checked again.
+            r.retain(|row| {
+                plan.covered
+                    .iter()
+                    .all(|i| conditions[*i].is_match(collection, relation, *row))
+            });
+            let estimated_rows = r.len();
+            if let Some(ref mut rows) = rows {
+                rows.retain(|row| r.contains(row));
+            } else {
+                rows = Some(r);
+            }
+            if let Some(ref mut trace) = trace {
+                trace.push(Explain {
+                    target: ExplainTarget::Composite {
+                        name: plan.name.to_owned(),
+                        conditions: plan
+                            .covered
+                            .iter()
+                            .map(|i| conditions[*i].clone())
+                            .collect(),
+                    },
+                    method: Some(Method::Index),
+                    estimated_rows: Some(estimated_rows),
+                    rows: rows.as_ref().map_or(0, |rows| rows.len()),
+                    elapsed: started.elapsed(),
+                    children: vec![],
+                });
+            }
+        }
+
+        let mut planned: Vec<_> = conditions
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| {
+                composite
+                    .as_ref()
+                    .is_none_or(|plan| !plan.covered.contains(i))
+            })
+            .map(|(_, c)| (c.cost(collection, relation), c))
+            .collect();
+        planned.sort_by_key(|(cost, _)| *cost);
+
+        for (cost, condition) in planned.into_iter() {
+            if rows.as_ref().is_some_and(|rows| rows.is_empty()) {
+                break;
+            }
+            let started = Instant::now();
+            let (method, children) = match rows {
+                Some(ref mut rows) if cost.access == Access::Scan || rows.len() <= cost.rows => {
+                    rows.retain(|row| condition.is_match(collection, relation, *row));
+                    (Method::Filter, vec![])
+                }
+                _ => {
+                    let (r, children) =
+                        Self::evaluate(collection, condition, relation, trace.is_some()).await;
+                    if let Some(ref mut rows) = rows {
+                        rows.retain(|row| r.contains(row));
+                    } else {
+                        rows = Some(r);
+                    }
+                    (cost.access.into(), children)
+                }
+            };
+            if let Some(ref mut trace) = trace {
+                trace.push(Explain {
+                    target: ExplainTarget::Condition(condition.clone()),
+                    method: Some(method),
+                    estimated_rows: Some(cost.rows),
+                    rows: rows.as_ref().map_or(0, |rows| rows.len()),
+                    elapsed: started.elapsed(),
+                    children,
+                });
+            }
+        }
+        rows.unwrap_or_else(|| collection.data().all())
+    }
+
+    /// Checks every row that existed at `at` in its version of that time.
+    pub(crate) fn version_conditions(
+        collection: &Collection,
+        conditions: &[Condition],
+        relation: &RelationIndex,
+        at: u64,
+    ) -> RowSet {
+        collection
+            .rows_at(at)
+            .into_iter()
+            .filter(|(row, version)| {
+                conditions
+                    .iter()
+                    .all(|c| c.is_match_version(collection, relation, *row, version))
+            })
+            .map(|(row, _)| row)
+            .collect()
+    }
+
+    #[async_recursion(?Send)]
+    async fn evaluate(
+        collection: &Collection,
+        condition: &Condition,
+        relation: &RelationIndex,
+        trace: bool,
+    ) -> (RowSet, Vec<Explain>) {
+        let mut children = vec![];
+        let rows = match condition {
+            Condition::Narrow(conditions) if trace => {
+                Self::narrow_conditions(collection, conditions, relation, None, Some(&mut children))
+                    .await
+            }
+            Condition::Wide(conditions) if trace => {
+                let mut rows = RowSet::default();
+                for condition in conditions.iter() {
+                    let started = Instant::now();
+                    let cost = condition.cost(collection, relation);
+                    let (r, grandchildren) =
+                        Self::evaluate(collection, condition, relation, trace).await;
+                    children.push(Explain {
+                        target: ExplainTarget::Condition(condition.clone()),
+                        method: Some(cost.access.into()),
+                        estimated_rows: Some(cost.rows),
+                        rows: r.len(),
+                        elapsed: started.elapsed(),
+                        children: grandchildren,
+                    });
+                    rows.extend(r);
+                }
+                rows
+            }
+            _ => condition.result(collection, relation).await,
+        };
+        (rows, children)
+    }
+}
+
+fn number_match(condition: &Number, value: isize) -> bool {
+    match condition {
+        Number::Min(min) => value >= *min,
+        Number::Max(max) => value <= *max,
+        Number::Range(range) => range.contains(&value),
+        Number::In(values) => values.contains(&value),
+    }
+}
+
+fn field_match(condition: &Field, bytes: &[u8]) -> bool {
+    match condition {
+        Field::Match(v) => IdxBinary::cmp(bytes, v) == Ordering::Equal,
+        Field::Range(min, max) => {
+            IdxBinary::cmp(bytes, min) != Ordering::Less
+                && IdxBinary::cmp(bytes, max) != Ordering::Greater
+        }
+        Field::Min(min) => IdxBinary::cmp(bytes, min) != Ordering::Less,
+        Field::Max(max) => IdxBinary::cmp(bytes, max) != Ordering::Greater,
+        Field::Forward(cont) => bytes.starts_with(cont.as_bytes()),
+        Field::Partial(cont) => contains(bytes, cont.as_bytes()),
+        Field::Backward(cont) => bytes.ends_with(cont.as_bytes()),
+        Field::ValueForward(cont) => cont.as_bytes().starts_with(bytes),
+        Field::ValuePartial(cont) => contains(cont.as_bytes(), bytes),
+        Field::ValueBackward(cont) => cont.as_bytes().ends_with(bytes),
+    }
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    needle.is_empty()
+        || haystack
+            .windows(needle.len())
+            .any(|window| window == needle)
+}
@@ -0,0 +1,166 @@
+use std::{
+    collections::BTreeMap,
+    num::{NonZeroI32, NonZeroI64, NonZeroU32},
+    sync::Arc,
+};
+
+use hashbrown::HashMap;
+use serde::Serialize;
+use versatile_data::{Activity, FieldName};
+
+use crate::{Collection, CollectionRow, Database, Depend};
+
+use super::{remote::ActivityDef, SearchResult};
+
+/// Turns the bytes of a field into a value that can be serialized.
+pub trait FieldDecoder {
+    type Value: Serialize;
+
+    fn decode(
+        &self,
+        collection_id: NonZeroI32,
+        field_name: &FieldName,
+        bytes: &[u8],
+    ) -> Self::Value;
+}
+
+/// Decodes every field as a UTF-8 string, replacing invalid sequences.
+pub struct Utf8Decoder;
+impl FieldDecoder for Utf8Decoder {
+    type Value = String;
+
+    fn decode(&self, _: NonZeroI32, _: &FieldName, bytes: &[u8]) -> String {
+        String::from_utf8_lossy(bytes).into_owned()
+    }
+}
+
+/// Keeps every field as raw bytes.
+pub struct BytesDecoder;
+impl FieldDecoder for BytesDecoder {
+    type Value = Vec<u8>;
+
+    fn decode(&self, _: NonZeroI32, _: &FieldName, bytes: &[u8]) -> Vec<u8> {
+        bytes.to_vec()
+    }
+}
+
+impl<V: Serialize, F: Fn(NonZeroI32, &FieldName, &[u8]) -> V> FieldDecoder for F {
+    type Value = V;
+
+    fn decode(&self, collection_id: NonZeroI32, field_name: &FieldName, bytes: &[u8]) -> V {
+        self(collection_id, field_name, bytes)
+    }
+}
+
+/// What to read for each row of a result.
+/// Joins without their own entry in `join` get every field and no depends.
+#[derive(Debug, Clone, Default)]
+pub struct RecordSelect {
+    /// The fields to read. `None` reads all fields of the collection.
+    pub fields: Option<Vec<FieldName>>,
+    pub depends: bool,
+    pub join: HashMap<Arc<String>, RecordSelect>,
+}
+
+/// A row of a search result with its values and the rows joined to it.
+/// Rows held only by a session are negative.
+#[derive(Debug, Clone, Serialize)]
+pub struct Record<V> {
+    pub collection_id: NonZeroI32,
+    pub row: NonZeroI64,
+    pub uuid: String,
+    #[serde(with = "ActivityDef")]
+    pub activity: Activity,
+    pub term_begin: u64,
+    pub term_end: u64,
+    pub last_updated: Option<u64>,
+    pub fields: BTreeMap<FieldName, V>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub depends: Option<Vec<Depend>>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub join: BTreeMap<Arc<String>, Vec<Record<V>>>,
+}
+
+impl<V> Record<V> {
+    /// Reads a committed row of the collection.
+    pub fn read<D: FieldDecoder<Value = V>>(
+        database: &Database,
+        collection: &Collection,
+        row: NonZeroU32,
+        select: &RecordSelect,
+        decoder: &D,
+    ) -> Self {
+        let collection_id = collection.id();
+        let read = |field_name: &FieldName| {
+            let bytes = collection.decrypted_bytes(row, field_name);
+            (
+                Arc::clone(field_name),
+                decoder.decode(collection_id, field_name, &bytes),
+            )
+        };
+        let fields = if let Some(field_names) = &select.fields {
+            field_names.iter().map(read).collect()
+        } else {
+            collection.data().fields().keys().map(read).collect()
+        };
+        Self {
+            collection_id,
+            row: row.into(),
+            uuid: collection.uuid_string(row).unwrap_or_default(),
+            activity: collection.activity(row).unwrap_or(Activity::Active),
+            term_begin: *collection.term_begin(row).unwrap_or(&0),
+            term_end: *collection.term_end(row).unwrap_or(&0),
+            last_updated: collection.last_updated(row).cloned(),
+            fields,
+            depends: select.depends.then(|| {
+                database
+                    .relation()
+                    .depends(None, &CollectionRow::new(collection_id, row))
+            }),
+            join: BTreeMap::new(),
+        }
+    }
+}
+
+impl SearchResult {
+    /// Reads the rows of this result in ascending order, with the rows joined to each of them.
+    pub fn records<D: FieldDecoder>(
+        &self,
+        database: &Database,
+        select: &RecordSelect,
+        decoder: &D,
+    ) -> Vec<Record<D::Value>> {
+        self.records_in(database, self.rows().iter().cloned(), select, decoder)
+    }
+
+    /// Reads the given rows in the given order, for example the output of [SearchResult::sort].
+    pub fn records_in<D: FieldDecoder>(
+        &self,
+        database: &Database,
+        rows: impl IntoIterator<Item = NonZeroU32>,
+        select: &RecordSelect,
+        decoder: &D,
+    ) -> Vec<Record<D::Value>> {
+        let Some(collection) = self
+            .search()
+            .and_then(|search| database.collection(search.collection_id()))
+        else {
+            return vec![];
+        };
+        let default_select = RecordSelect::default();
+        rows.into_iter()
+            .map(|row| {
+                let mut record = Record::read(database, collection, row, select, decoder);
+                for (name, join) in self.join().iter() {
+                    if let Some(result) = join.get(&row) {
+                        let select = select.join.get(name).unwrap_or(&default_select);
+                        record
+                            .join
+                            .insert(Arc::clone(name), result.records(database, select, decoder));
+                    }
+                }
+                record
+            })
+            .collect()
+    }
+}
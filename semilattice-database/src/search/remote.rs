@@ -0,0 +1,68 @@
+//! Serde definitions of the search types re-exported from versatile_data.
+
+use std::{ops::RangeInclusive, sync::Arc};
+
+use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+use versatile_data::{
+    search::{Field, Number, Term},
+    Activity, Uuid,
+};
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "Activity")]
+pub(crate) enum ActivityDef {
+    Inactive = 0,
+    Active = 1,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "Term")]
+pub(crate) enum TermDef {
+    In(u64),
+    Past(u64),
+    Future(u64),
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "Number")]
+pub(crate) enum NumberDef {
+    Min(isize),
+    Max(isize),
+    Range(RangeInclusive<isize>),
+    In(Vec<isize>),
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "Field")]
+pub(crate) enum FieldDef {
+    Match(Vec<u8>),
+    Range(Vec<u8>, Vec<u8>),
+    Min(Vec<u8>),
+    Max(Vec<u8>),
+    Forward(Arc<String>),
+    Partial(Arc<String>),
+    Backward(Arc<String>),
+    ValueForward(Arc<String>),
+    ValueBackward(Arc<String>),
+    ValuePartial(Arc<String>),
+}
+
+/// Uuids as hyphenated strings, since JSON numbers cannot hold 128 bits exactly.
+pub(crate) mod uuids {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(uuids: &[u128], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(uuids.iter().map(|uuid| Uuid::from_u128(*uuid).to_string()))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u128>, D::Error> {
+        Vec::<String>::deserialize(deserializer)?
+            .iter()
+            .map(|uuid| {
+                Uuid::parse_str(uuid)
+                    .map(|uuid| uuid.as_u128())
+                    .map_err(D::Error::custom)
+            })
+            .collect()
+    }
+}
@@ -8,7 +8,7 @@ use crate::{Collection, Condition, Database, RelationIndex, Search};
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct SearchResult {
-    search: Option<Search>,
+    search: Option<Arc<Search>>,
     rows: RowSet,
     join: HashMap<Arc<String>, HashMap<NonZeroU32, SearchResult>>,
 }
@@ -18,12 +18,21 @@ impl SearchResult {
         search: Option<Search>,
         rows: RowSet,
         join: HashMap<Arc<String>, HashMap<NonZeroU32, SearchResult>>,
+    ) -> Self {
+        Self::shared(search.map(Arc::new), rows, join)
+    }
+
+    /// Joined results of every parent row share the search of their join.
+    pub(crate) fn shared(
+        search: Option<Arc<Search>>,
+        rows: RowSet,
+        join: HashMap<Arc<String>, HashMap<NonZeroU32, SearchResult>>,
     ) -> Self {
         Self { search, rows, join }
     }
 
     pub fn search(&self) -> Option<&Search> {
-        self.search.as_ref()
+        self.search.as_deref()
     }
 
     pub fn rows(&self) -> &RowSet {
@@ -34,11 +43,16 @@ impl SearchResult {
         &self.join
     }
 
+    /// Orders on the leading fields of a composite index are read from the index in one pass.
+    /// Orders by a field of an encrypted collection compare the decrypted values.
     pub fn sort<C: CustomSort>(&self, database: &Database, orders: &[Order<C>]) -> Vec<NonZeroU32> {
         if let Some(search) = self.search() {
             if let Some(collection) = database.collection(search.collection_id) {
-                return if orders.len() > 0 {
-                    collection.data().sort(&self.rows, &orders)
+                return if !orders.is_empty() {
+                    collection
+                        .decrypted_sort(&self.rows, orders)
+                        .or_else(|| collection.composite_sort(&self.rows, orders))
+                        .unwrap_or_else(|| collection.data().sort(&self.rows, orders))
                 } else {
                     self.rows.iter().cloned().collect()
                 };
@@ -51,25 +65,18 @@ impl SearchResult {
 impl Search {
     pub(crate) async fn result_conditions(
         collection: &Collection,
-        conditions: &Vec<Condition>,
+        conditions: &[Condition],
         relation: &RelationIndex,
     ) -> RowSet {
-        let (mut rows, _index, fs) = future::select_all(
-            conditions
-                .into_iter()
-                .map(|c| c.result(collection, relation)),
-        )
-        .await;
-        for r in future::join_all(fs).await.into_iter() {
-            rows.retain(|v| r.contains(v));
-        }
-        rows
+        Self::narrow_conditions(collection, conditions, relation, None, None).await
     }
 
     pub async fn result(self, database: &Database) -> SearchResult {
         let collection_id = self.collection_id;
         if let Some(collection) = database.collection(collection_id) {
-            let rows = if self.conditions.len() > 0 {
+            let rows = if let Some(at) = self.as_of {
+                Self::version_conditions(collection, &self.conditions, &database.relation, at)
+            } else if !self.conditions.is_empty() {
                 Self::result_conditions(collection, &self.conditions, &database.relation).await
             } else {
                 collection.data().all()
@@ -86,13 +93,13 @@ impl Search {
             .collect();
 
             SearchResult {
-                search: Some(self),
+                search: Some(Arc::new(self)),
                 rows,
                 join,
             }
         } else {
             SearchResult {
-                search: Some(self),
+                search: Some(Arc::new(self)),
                 rows: Default::default(),
                 join: Default::default(),
             }
@@ -0,0 +1,115 @@
+use std::{collections::BTreeMap, num::NonZeroI32, path::Path};
+
+use versatile_data::{Activity, FieldName};
+
+use crate::{Collection, Database};
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DatabaseStats {
+    pub collections: Vec<CollectionStats>,
+    pub relation: RelationStats,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CollectionStats {
+    pub collection_id: NonZeroI32,
+    pub name: String,
+    pub rows: usize,
+    pub active_rows: usize,
+    pub inactive_rows: usize,
+    /// On-disk bytes of each field's values and index.
+    pub fields: BTreeMap<FieldName, u64>,
+    /// On-disk bytes of every other index, by file name, `fulltext/<field>` or `composite/<name>`.
+    pub indexes: BTreeMap<String, u64>,
+}
+impl CollectionStats {
+    /// On-disk bytes of the whole collection.
+    pub fn bytes(&self) -> u64 {
+        self.fields.values().sum::<u64>() + self.indexes.values().sum::<u64>()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct RelationStats {
+    pub relations: usize,
+    pub keys: BTreeMap<String, usize>,
+    /// Deleted relation rows waiting to be reused.
+    pub free_rows: usize,
+    pub bytes: u64,
+}
+
+impl Collection {
+    pub(crate) fn stats(&self, dir: &Path) -> CollectionStats {
+        let rows = self.data().all();
+        let active_rows = rows
+            .iter()
+            .filter(|row| self.data().activity(**row) == Some(Activity::Active))
+            .count();
+        let mut fields = BTreeMap::new();
+        let mut indexes = BTreeMap::new();
+        for d in dir.read_dir().into_iter().flatten().flatten() {
+            let name = d.file_name().to_string_lossy().into_owned();
+            let path = d.path();
+            if path.is_dir() {
+                let entries = path.read_dir().into_iter().flatten().flatten();
+                for e in entries.filter(|e| e.path().is_dir()) {
+                    let sub = e.file_name().to_string_lossy().into_owned();
+                    if name == "fields" {
+                        fields.insert(FieldName::new(sub), dir_bytes(&e.path()));
+                    } else {
+                        indexes.insert(name.clone() + "/" + &sub, dir_bytes(&e.path()));
+                    }
+                }
+            } else {
+                indexes.insert(name, d.metadata().map_or(0, |m| m.len()));
+            }
+        }
+        CollectionStats {
+            collection_id: self.id(),
+            name: self.name().to_owned(),
+            rows: rows.len(),
+            active_rows,
+            inactive_rows: rows.len() - active_rows,
+            fields,
+            indexes,
+        }
+    }
+}
+
+impl Database {
+    /// Row counts and on-disk sizes of every collection and of the relations.
+    /// Sizes are file lengths, which include the space files reserve in advance.
+    pub fn stats(&self) -> DatabaseStats {
+        DatabaseStats {
+            collections: self
+                .collections
+                .values()
+                .map(|collection| {
+                    collection.stats(
+                        &self
+                            .collections_dir
+                            .join(collection.id().to_string() + "_" + collection.name()),
+                    )
+                })
+                .collect(),
+            relation: self.relation.stats(),
+        }
+    }
+}
+
+/// Total length of the files under `dir`.
+pub(crate) fn dir_bytes(dir: &Path) -> u64 {
+    dir.read_dir()
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|d| {
+            let path = d.path();
+            if path.is_dir() {
+                dir_bytes(&path)
+            } else {
+                d.metadata().map_or(0, |m| m.len())
+            }
+        })
+        .sum()
+}
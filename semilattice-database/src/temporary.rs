@@ -0,0 +1,69 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use versatile_data::DataOption;
+
+use crate::{Database, MemoryRelationStorage, RelationIndex};
+
+static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// A directory of its own under `/dev/shm` where that exists and the system temporary directory otherwise, removed on drop.
+pub(crate) struct TemporaryDir(PathBuf);
+impl TemporaryDir {
+    fn new() -> Self {
+        let shm = Path::new("/dev/shm");
+        let base = if shm.is_dir() {
+            shm.to_path_buf()
+        } else {
+            std::env::temp_dir()
+        };
+        let dir = base.join(format!(
+            "semilattice-{}-{}",
+            std::process::id(),
+            COUNT.fetch_add(1, Ordering::Relaxed)
+        ));
+        if dir.exists() {
+            std::fs::remove_dir_all(&dir).unwrap();
+        }
+        std::fs::create_dir_all(&dir).unwrap();
+        Self(dir)
+    }
+}
+impl Drop for TemporaryDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+impl Database {
+    /// A database of its own in a temporary directory, removed when the database is dropped.
+    /// It is stored in files like any other: the directory is under `/dev/shm` where that exists, which is memory-backed,
+    /// and under [std::env::temp_dir] otherwise, which is usually on disk.
+    pub fn temporary(
+        collection_settings: Option<std::collections::HashMap<String, DataOption>>,
+        relation_allocation_lot: u32,
+    ) -> Self {
+        let temporary = TemporaryDir::new();
+        let mut database = Self::new(
+            temporary.0.clone(),
+            collection_settings,
+            relation_allocation_lot,
+        );
+        database.temporary = Some(temporary);
+        database
+    }
+
+    /// Whether the database was created by [Database::temporary], and so is removed when dropped.
+    pub fn is_temporary(&self) -> bool {
+        self.temporary.is_some()
+    }
+}
+
+impl RelationIndex {
+    /// A relation index in [MemoryRelationStorage], gone when dropped.
+    pub fn temporary() -> Self {
+        Self::with_storage(Box::new(MemoryRelationStorage::new()))
+    }
+}
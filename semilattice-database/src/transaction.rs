@@ -0,0 +1,466 @@
+use std::{fmt, num::NonZeroI32};
+
+use hashbrown::{HashMap, HashSet};
+use versatile_data::{Activity, FieldName, Term};
+
+use crate::{CollectionRow, Database, UniqueConflict, WriteError};
+
+/// A row a transaction writes to: one already in the database, or one it inserts.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TransactionRow {
+    Row(CollectionRow),
+    /// The n-th insert of the transaction, counting from 0.
+    Inserted(usize),
+}
+impl From<CollectionRow> for TransactionRow {
+    fn from(row: CollectionRow) -> Self {
+        Self::Row(row)
+    }
+}
+
+/// The fields each row ends up with after the transaction and the operation that last wrote it.
+type Writes = HashMap<TransactionRow, (HashMap<FieldName, Vec<u8>>, usize)>;
+
+enum Operation {
+    Insert {
+        collection_id: NonZeroI32,
+        activity: Activity,
+        term_begin: Term,
+        term_end: Term,
+        fields: HashMap<FieldName, Vec<u8>>,
+    },
+    Update {
+        row: TransactionRow,
+        activity: Activity,
+        term_begin: Term,
+        term_end: Term,
+        fields: HashMap<FieldName, Vec<u8>>,
+    },
+    Delete(TransactionRow),
+    Relate {
+        key: String,
+        depend: TransactionRow,
+        pend: TransactionRow,
+    },
+    Unrelate {
+        key: String,
+        depend: TransactionRow,
+        pend: TransactionRow,
+    },
+}
+
+/// Why a transaction was not committed. `operation` counts the calls made on the transaction from 0.
+#[derive(Debug)]
+pub enum TransactionError {
+    Write {
+        operation: usize,
+        error: WriteError,
+    },
+    /// Two rows written by the transaction would have the same values for a unique constraint.
+    DuplicateKey {
+        operation: usize,
+        other: usize,
+        field_names: Vec<FieldName>,
+    },
+    UnknownCollection {
+        operation: usize,
+        collection_id: NonZeroI32,
+    },
+    /// An insert the operation comes before or that the transaction never made, or a row to update or relate that does not exist or that the transaction deleted.
+    UnknownRow {
+        operation: usize,
+        row: TransactionRow,
+    },
+    /// `error` stopped the commit after the checks passed, while applying the writes. The operations before the failing one are written and stay written.
+    /// `inserted` has the rows of the inserts among them, in order.
+    PartlyApplied {
+        error: Box<TransactionError>,
+        inserted: Vec<CollectionRow>,
+    },
+}
+impl fmt::Display for TransactionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Write { operation, error } => write!(f, "operation {}: {}", operation, error),
+            Self::DuplicateKey {
+                operation,
+                other,
+                field_names,
+            } => write!(
+                f,
+                "operations {} and {} give the same values to ({})",
+                other,
+                operation,
+                field_names
+                    .iter()
+                    .map(|field_name| field_name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            Self::UnknownCollection {
+                operation,
+                collection_id,
+            } => write!(
+                f,
+                "operation {}: no collection {}",
+                operation, collection_id
+            ),
+            Self::UnknownRow { operation, row } => {
+                write!(f, "operation {}: no row {:?}", operation, row)
+            }
+            Self::PartlyApplied { error, .. } => {
+                write!(f, "{}, after the operations before it were applied", error)
+            }
+        }
+    }
+}
+impl std::error::Error for TransactionError {}
+
+/// Writes collected in memory and applied together by [Transaction::commit].
+/// Nothing reaches the database before the commit, so dropping the transaction or a commit that fails its checks leaves it untouched.
+pub struct Transaction<'a> {
+    database: &'a mut Database,
+    operations: Vec<Operation>,
+    inserts: usize,
+}
+impl<'a> Transaction<'a> {
+    pub fn insert(
+        &mut self,
+        collection_id: NonZeroI32,
+        activity: Activity,
+        term_begin: Term,
+        term_end: Term,
+        fields: HashMap<FieldName, Vec<u8>>,
+    ) -> TransactionRow {
+        self.operations.push(Operation::Insert {
+            collection_id,
+            activity,
+            term_begin,
+            term_end,
+            fields,
+        });
+        self.inserts += 1;
+        TransactionRow::Inserted(self.inserts - 1)
+    }
+
+    pub fn update(
+        &mut self,
+        row: impl Into<TransactionRow>,
+        activity: Activity,
+        term_begin: Term,
+        term_end: Term,
+        fields: HashMap<FieldName, Vec<u8>>,
+    ) {
+        self.operations.push(Operation::Update {
+            row: row.into(),
+            activity,
+            term_begin,
+            term_end,
+            fields,
+        });
+    }
+
+    /// Deletes through [Database::delete], so the rows depending on it go too.
+    pub fn delete(&mut self, row: impl Into<TransactionRow>) {
+        self.operations.push(Operation::Delete(row.into()));
+    }
+
+    pub fn register_relation(
+        &mut self,
+        key: impl Into<String>,
+        depend: impl Into<TransactionRow>,
+        pend: impl Into<TransactionRow>,
+    ) {
+        self.operations.push(Operation::Relate {
+            key: key.into(),
+            depend: depend.into(),
+            pend: pend.into(),
+        });
+    }
+
+    /// Removes the relations `key` from `pend` to `depend`.
+    pub fn unregister_relation(
+        &mut self,
+        key: impl Into<String>,
+        depend: impl Into<TransactionRow>,
+        pend: impl Into<TransactionRow>,
+    ) {
+        self.operations.push(Operation::Unrelate {
+            key: key.into(),
+            depend: depend.into(),
+            pend: pend.into(),
+        });
+    }
+
+    /// Discards the transaction. The same as dropping it.
+    pub fn rollback(self) {}
+
+    /// Checks every write against the schemas and unique constraints as the database would be after the whole transaction, then applies them in order.
+    /// Returns the row of each insert in order.
+    /// Nothing is written if a check fails. The writes applied before a failure while applying are not undone, see [TransactionError::PartlyApplied].
+    /// A process that dies while applying may also leave part of the transaction written.
+    pub async fn commit(self) -> Result<Vec<CollectionRow>, TransactionError> {
+        self.check()?;
+
+        let mut inserted = Vec::with_capacity(self.inserts);
+        match Self::apply(self.database, self.operations, &mut inserted).await {
+            Ok(()) => Ok(inserted),
+            Err(error) => Err(TransactionError::PartlyApplied {
+                error: Box::new(error),
+                inserted,
+            }),
+        }
+    }
+
+    async fn apply(
+        database: &mut Database,
+        operations: Vec<Operation>,
+        inserted: &mut Vec<CollectionRow>,
+    ) -> Result<(), TransactionError> {
+        let resolve = |inserted: &[CollectionRow], row: TransactionRow| match row {
+            TransactionRow::Row(row) => row,
+            TransactionRow::Inserted(i) => inserted[i].clone(),
+        };
+        for (i, operation) in operations.into_iter().enumerate() {
+            let write_error = |error| TransactionError::Write {
+                operation: i,
+                error,
+            };
+            // A row checked to exist may since have been deleted along with a row it depends on.
+            let exists = |database: &Database, row: &CollectionRow| {
+                database
+                    .collection(row.collection_id())
+                    .is_some_and(|collection| collection.uuid(row.row()).is_some())
+            };
+            let unknown_row = |row: CollectionRow| TransactionError::UnknownRow {
+                operation: i,
+                row: TransactionRow::Row(row),
+            };
+            match operation {
+                Operation::Insert {
+                    collection_id,
+                    activity,
+                    term_begin,
+                    term_end,
+                    fields,
+                } => {
+                    let collection = database.collection_mut(collection_id).unwrap();
+                    let row = collection
+                        .insert_unchecked(activity, term_begin, term_end, fields)
+                        .await
+                        .map_err(write_error)?;
+                    inserted.push(CollectionRow::new(collection_id, row));
+                }
+                Operation::Update {
+                    row,
+                    activity,
+                    term_begin,
+                    term_end,
+                    fields,
+                } => {
+                    let row = resolve(inserted, row);
+                    if !exists(database, &row) {
+                        return Err(unknown_row(row));
+                    }
+                    database
+                        .collection_mut(row.collection_id())
+                        .unwrap()
+                        .update_unchecked(row.row(), activity, term_begin, term_end, fields)
+                        .await
+                        .map_err(write_error)?;
+                }
+                Operation::Delete(row) => {
+                    database
+                        .delete(&resolve(inserted, row))
+                        .await
+                        .map_err(write_error)?;
+                }
+                Operation::Relate { key, depend, pend } => {
+                    let (depend, pend) = (resolve(inserted, depend), resolve(inserted, pend));
+                    for row in [&depend, &pend] {
+                        if !exists(database, row) {
+                            return Err(unknown_row(row.clone()));
+                        }
+                    }
+                    database
+                        .register_relation(&key, &depend, &pend)
+                        .await
+                        .map_err(write_error)?;
+                }
+                Operation::Unrelate { key, depend, pend } => {
+                    let (depend, pend) = (resolve(inserted, depend), resolve(inserted, pend));
+                    for row in [&depend, &pend] {
+                        if !exists(database, row) {
+                            return Err(unknown_row(row.clone()));
+                        }
+                    }
+                    let relation_rows: Vec<_> = database
+                        .relation
+                        .rows_by_pend(&pend)
+                        .filter(|relation_row| {
+                            database.relation.depend(*relation_row) == Some(&depend)
+                                && database.relation.key(*relation_row) == key
+                        })
+                        .collect();
+                    for relation_row in relation_rows {
+                        database
+                            .relation
+                            .delete(relation_row)
+                            .await
+                            .map_err(write_error)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn check(&self) -> Result<(), TransactionError> {
+        if self.database.is_read_only() && !self.operations.is_empty() {
+            return Err(TransactionError::Write {
+                operation: 0,
+                error: WriteError::ReadOnly,
+            });
+        }
+        let mut writes: HashMap<NonZeroI32, Writes> = HashMap::new();
+        let mut deleted: HashSet<TransactionRow> = HashSet::new();
+        let mut collections = vec![];
+        for (i, operation) in self.operations.iter().enumerate() {
+            let unknown_row = |row: &TransactionRow| TransactionError::UnknownRow {
+                operation: i,
+                row: row.clone(),
+            };
+            let collection_of = |row: &TransactionRow| match row {
+                TransactionRow::Row(row) => Some(row.collection_id()),
+                TransactionRow::Inserted(n) => collections.get(*n).cloned(),
+            };
+            let check_exists = |row: &TransactionRow| {
+                let exists = !deleted.contains(row)
+                    && match row {
+                        TransactionRow::Row(row) => self
+                            .database
+                            .collection(row.collection_id())
+                            .is_some_and(|collection| collection.uuid(row.row()).is_some()),
+                        TransactionRow::Inserted(n) => *n < collections.len(),
+                    };
+                if exists {
+                    Ok(())
+                } else {
+                    Err(unknown_row(row))
+                }
+            };
+            let (collection_id, target, fields, new_row) = match operation {
+                Operation::Insert {
+                    collection_id,
+                    fields,
+                    ..
+                } => {
+                    collections.push(*collection_id);
+                    (
+                        *collection_id,
+                        TransactionRow::Inserted(collections.len() - 1),
+                        fields,
+                        true,
+                    )
+                }
+                Operation::Update { row, fields, .. } => {
+                    check_exists(row)?;
+                    let collection_id = collection_of(row).ok_or_else(|| unknown_row(row))?;
+                    (collection_id, row.clone(), fields, false)
+                }
+                Operation::Delete(row) => {
+                    let collection_id = collection_of(row).ok_or_else(|| unknown_row(row))?;
+                    if let Some(writes) = writes.get_mut(&collection_id) {
+                        writes.remove(row);
+                    }
+                    deleted.insert(row.clone());
+                    continue;
+                }
+                Operation::Relate { depend, pend, .. }
+                | Operation::Unrelate { depend, pend, .. } => {
+                    check_exists(depend)?;
+                    check_exists(pend)?;
+                    continue;
+                }
+            };
+            let Some(collection) = self.database.collection(collection_id) else {
+                return Err(TransactionError::UnknownCollection {
+                    operation: i,
+                    collection_id,
+                });
+            };
+            collection
+                .validate(fields, new_row)
+                .map_err(|e| TransactionError::Write {
+                    operation: i,
+                    error: e.into(),
+                })?;
+            let (written, operation) = writes
+                .entry(collection_id)
+                .or_default()
+                .entry(target)
+                .or_default();
+            written.extend(fields.iter().map(|(k, v)| (k.clone(), v.clone())));
+            *operation = i;
+        }
+
+        for (collection_id, writes) in writes.iter() {
+            let Some(collection) = self.database.collection(*collection_id) else {
+                continue;
+            };
+            let Some(schema) = collection.schema() else {
+                continue;
+            };
+            let mut keys: HashMap<(usize, Vec<Vec<u8>>), usize> = HashMap::new();
+            for (target, (fields, operation)) in writes.iter() {
+                let row = match target {
+                    TransactionRow::Row(row) => Some(row.row()),
+                    TransactionRow::Inserted(_) => None,
+                };
+                for (i, field_names) in schema.unique.iter().enumerate() {
+                    let Some(key) = collection.unique_key(field_names, row, fields) else {
+                        continue;
+                    };
+                    let existing = collection
+                        .unique_rows(field_names, &key)
+                        .into_iter()
+                        .map(|row| CollectionRow::new(*collection_id, row))
+                        .find(|existing| {
+                            Some(existing.row()) != row
+                                && !deleted.contains(&TransactionRow::Row(existing.clone()))
+                                && !writes.contains_key(&TransactionRow::Row(existing.clone()))
+                        });
+                    if let Some(existing) = existing {
+                        return Err(TransactionError::Write {
+                            operation: *operation,
+                            error: WriteError::Conflict(UniqueConflict {
+                                collection_id: *collection_id,
+                                field_names: field_names.clone(),
+                                existing,
+                            }),
+                        });
+                    }
+                    if let Some(other) = keys.insert((i, key), *operation) {
+                        return Err(TransactionError::DuplicateKey {
+                            operation: other.max(*operation),
+                            other: other.min(*operation),
+                            field_names: field_names.clone(),
+                        });
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Database {
+    /// Starts collecting writes to apply together, see [Transaction].
+    pub fn transaction(&mut self) -> Transaction<'_> {
+        Transaction {
+            database: self,
+            operations: vec![],
+            inserts: 0,
+        }
+    }
+}
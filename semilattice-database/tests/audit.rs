@@ -0,0 +1,102 @@
+#[cfg(test)]
+#[test]
+fn audit() {
+    use semilattice_database::{
+        audit::{AuditChange, AuditContext, AuditQuery},
+        *,
+    };
+
+    let dir = "./sl-test-audit/";
+
+    if std::path::Path::new(dir).exists() {
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+    std::fs::create_dir_all(dir).unwrap();
+
+    let field_name = FieldName::new("name".into());
+
+    futures::executor::block_on(async {
+        let mut database = Database::new(dir.into(), None, 10);
+        database.enable_audit().unwrap();
+        let collection_id = database.collection_id_or_create("person").unwrap();
+
+        database.set_audit_context(Some(AuditContext::new("alice")));
+        let mut rows = vec![];
+        for name in ["Joe", "Ann"] {
+            rows.push(CollectionRow::new(
+                collection_id,
+                database
+                    .collection_mut(collection_id)
+                    .unwrap()
+                    .insert(
+                        Activity::Active,
+                        Term::Default,
+                        Term::Default,
+                        [(field_name.clone(), name.into())].into(),
+                    )
+                    .await
+                    .unwrap(),
+            ));
+        }
+        database
+            .register_relation("friend", &rows[0], &rows[1])
+            .await
+            .unwrap();
+
+        database.set_audit_context(Some(AuditContext::new("bob").session("web")));
+        database
+            .collection_mut(collection_id)
+            .unwrap()
+            .update(
+                rows[1].row(),
+                Activity::Active,
+                Term::Default,
+                Term::Default,
+                [(field_name.clone(), "Anne".into())].into(),
+            )
+            .await
+            .unwrap();
+        database.delete(&rows[1]).await.unwrap();
+
+        let entries = database.audit(&AuditQuery::new().row(rows[1].clone()));
+        assert_eq!(
+            entries
+                .iter()
+                .map(|entry| entry.actor.as_str())
+                .collect::<Vec<_>>(),
+            ["alice", "alice", "bob", "bob", "bob"]
+        );
+        assert_eq!(
+            entries[2].change,
+            AuditChange::Update {
+                target: rows[1].clone(),
+                before: [(field_name.clone(), b"Ann".to_vec())].into(),
+                after: [(field_name.clone(), b"Anne".to_vec())].into(),
+            }
+        );
+        assert_eq!(
+            entries[3].change,
+            AuditChange::Unrelate {
+                key: "friend".into(),
+                depend: rows[0].clone(),
+                pend: rows[1].clone(),
+            }
+        );
+        assert_eq!(entries[4].session.as_deref(), Some("web"));
+        assert!(matches!(entries[4].change, AuditChange::Delete { .. }));
+
+        let at = entries[0].at;
+        drop(database);
+        let database = Database::new(dir.into(), None, 10);
+        assert_eq!(database.audit(&AuditQuery::new().actor("alice")).len(), 3);
+        assert_eq!(
+            database
+                .audit(&AuditQuery::new().actor("bob").time(at..=u64::MAX))
+                .len(),
+            3
+        );
+        assert!(database
+            .audit(&AuditQuery::new().time(0..=at - 1))
+            .is_empty());
+    });
+}
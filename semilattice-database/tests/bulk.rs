@@ -0,0 +1,182 @@
+#[cfg(test)]
+#[test]
+fn bulk_load() {
+    use semilattice_database::{
+        bulk::{BulkErrorKind, BulkRecord, BulkTarget},
+        *,
+    };
+
+    let dir = "./sl-test-bulk/";
+
+    if std::path::Path::new(dir).exists() {
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+    std::fs::create_dir_all(dir).unwrap();
+
+    let field_name = FieldName::new("name".into());
+
+    futures::executor::block_on(async {
+        let mut database = Database::new(dir.into(), None, 10);
+        let collection_person = database.collection_id_or_create("person").unwrap();
+        let collection_pet = database.collection_id_or_create("pet").unwrap();
+        database
+            .set_schema(
+                collection_person,
+                Some(
+                    Schema::new()
+                        .field(field_name.clone(), FieldSchema::new(FieldType::Utf8))
+                        .unique(vec![field_name.clone()]),
+                ),
+            )
+            .unwrap();
+        let owner = CollectionRow::new(
+            collection_person,
+            database
+                .collection_mut(collection_person)
+                .unwrap()
+                .insert(
+                    Activity::Active,
+                    Term::Default,
+                    Term::Default,
+                    [(field_name.clone(), b"Tom".to_vec())].into(),
+                )
+                .await
+                .unwrap(),
+        );
+
+        let records = vec![
+            BulkRecord::new("rex", collection_pet)
+                .field(field_name.clone(), "Rex")
+                .depend("owner", BulkTarget::Loaded("joe")),
+            BulkRecord::new("tama", collection_pet)
+                .field(field_name.clone(), "Tama")
+                .depend("owner", BulkTarget::Row(owner.clone())),
+            BulkRecord::new("joe", collection_person).field(field_name.clone(), "Joe"),
+            BulkRecord::new("ann", collection_person).field(field_name.clone(), "Ann"),
+            BulkRecord::new("pochi", collection_pet)
+                .field(field_name.clone(), "Pochi")
+                .depend("owner", BulkTarget::Loaded("joe")),
+        ];
+        let rows = database
+            .bulk_load(futures::stream::iter(records), 2)
+            .await
+            .unwrap();
+        assert_eq!(rows.len(), 5);
+        assert_eq!(rows["joe"].collection_id(), collection_person);
+        assert_eq!(
+            database
+                .collection(collection_pet)
+                .unwrap()
+                .field_bytes(rows["rex"].row(), &field_name),
+            b"Rex"
+        );
+        let mut pets: Vec<_> = database
+            .relation()
+            .pends(Some("owner".to_owned().into()), &rows["joe"], None)
+            .into_iter()
+            .cloned()
+            .collect();
+        pets.sort();
+        assert_eq!(pets, [rows["rex"].clone(), rows["pochi"].clone()]);
+        assert_eq!(
+            database.relation().pends(None, &owner, None),
+            [&rows["tama"]]
+        );
+
+        let result = database
+            .bulk_load(
+                futures::stream::iter(vec![
+                    BulkRecord::new("a", collection_person).field(field_name.clone(), "Kim"),
+                    BulkRecord::new("b", collection_person).field(field_name.clone(), "Kim"),
+                ]),
+                10,
+            )
+            .await;
+        assert!(matches!(
+            result.map_err(|e| e.kind),
+            Err(BulkErrorKind::DuplicateKey {
+                id: "b",
+                other: "a",
+                ..
+            })
+        ));
+        assert!(matches!(
+            database
+                .bulk_load(
+                    futures::stream::iter(vec![
+                        BulkRecord::new("c", collection_person).field(field_name.clone(), "Tom")
+                    ]),
+                    10,
+                )
+                .await
+                .map_err(|e| e.kind),
+            Err(BulkErrorKind::Write { id: "c", .. })
+        ));
+        assert!(matches!(
+            database
+                .bulk_load(
+                    futures::stream::iter(vec![BulkRecord::new("d", collection_pet)
+                        .depend("owner", BulkTarget::Loaded("nobody"))]),
+                    10,
+                )
+                .await
+                .map_err(|e| e.kind),
+            Err(BulkErrorKind::UnresolvedId("nobody"))
+        ));
+        let pets = database
+            .collection(collection_pet)
+            .unwrap()
+            .data()
+            .all()
+            .len();
+        let missing = CollectionRow::new(collection_person, 99.try_into().unwrap());
+        let error = database
+            .bulk_load(
+                futures::stream::iter(vec![BulkRecord::new("g", collection_pet)
+                    .depend("owner", BulkTarget::Row(missing.clone()))]),
+                10,
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(error.kind, BulkErrorKind::UnknownRow { id: "g", row } if row == missing));
+        assert!(error.rows.is_empty());
+        assert_eq!(
+            database
+                .collection(collection_pet)
+                .unwrap()
+                .data()
+                .all()
+                .len(),
+            pets
+        );
+
+        let error = database
+            .bulk_load(
+                futures::stream::iter(vec![
+                    BulkRecord::new("e", collection_person).field(field_name.clone(), "Eve"),
+                    BulkRecord::new("f", collection_person).field(field_name.clone(), "Joe"),
+                ]),
+                1,
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(error.kind, BulkErrorKind::Write { id: "f", .. }));
+        assert_eq!(error.rows.len(), 1);
+        assert_eq!(
+            database
+                .collection(collection_person)
+                .unwrap()
+                .field_bytes(error.rows["e"].row(), &field_name),
+            b"Eve"
+        );
+        assert_eq!(
+            database
+                .collection(collection_person)
+                .unwrap()
+                .data()
+                .all()
+                .len(),
+            4
+        );
+    });
+}
@@ -0,0 +1,114 @@
+#[cfg(test)]
+#[test]
+fn codec() {
+    use semilattice_database::{
+        codec::{Codec, FieldValue},
+        idx_binary::AvltrieeSearch,
+        *,
+    };
+
+    let values = [
+        i64::MIN,
+        -1_000_000,
+        -12,
+        -1,
+        0,
+        1,
+        9,
+        10,
+        57,
+        1_000_000,
+        i64::MAX,
+    ];
+    for pair in values.windows(2) {
+        assert!(
+            <idx_binary::IdxBinary as AvltrieeSearch<_, _, _>>::cmp(
+                &pair[0].encode(),
+                &pair[1].encode()
+            ) == std::cmp::Ordering::Less
+        );
+    }
+    let floats = [
+        f64::NEG_INFINITY,
+        -2.5,
+        -0.0,
+        0.0,
+        1e-9,
+        3.25,
+        f64::INFINITY,
+    ];
+    for pair in floats.windows(2) {
+        assert!(
+            <idx_binary::IdxBinary as AvltrieeSearch<_, _, _>>::cmp(
+                &pair[0].encode(),
+                &pair[1].encode()
+            ) != std::cmp::Ordering::Greater
+        );
+    }
+    assert_eq!(f64::NAN.encode(), (-f64::NAN).encode());
+    assert!(
+        <idx_binary::IdxBinary as AvltrieeSearch<_, _, _>>::cmp(
+            &f64::INFINITY.encode(),
+            &(-f64::NAN).encode()
+        ) == std::cmp::Ordering::Less
+    );
+    let strings = ["", "a", "a1", "a10", "a100b", "a9", "a90", "b", "b01", "b1"];
+    for pair in strings.windows(2) {
+        assert!(
+            <idx_binary::IdxBinary as AvltrieeSearch<_, _, _>>::cmp(
+                &pair[0].to_string().encode(),
+                &pair[1].to_string().encode()
+            ) == std::cmp::Ordering::Less
+        );
+    }
+    assert!(strings
+        .iter()
+        .all(|v| String::decode(&v.to_string().encode()).as_deref() == Some(*v)));
+    assert!(String::decode(b"a10").is_none());
+    assert!(values.iter().all(|v| i64::decode(&v.encode()) == Some(*v)));
+    assert!(floats.iter().all(|v| f64::decode(&v.encode()) == Some(*v)));
+    let now = std::time::SystemTime::now();
+    assert!(std::time::SystemTime::decode(&now.encode())
+        .is_some_and(|t| { now.duration_since(t).unwrap() < std::time::Duration::from_micros(1) }));
+    assert!(bool::decode(&true.encode()) == Some(true) && u64::decode(b"123").is_none());
+
+    let dir = "./sl-test-codec/";
+
+    if std::path::Path::new(dir).exists() {
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+    std::fs::create_dir_all(dir).unwrap();
+
+    futures::executor::block_on(async {
+        let mut database = Database::new(dir.into(), None, 10);
+
+        let collection_id = database.collection_id_or_create("item").unwrap();
+        let field_num = FieldName::new("num".into());
+
+        let collection = database.collection_mut(collection_id).unwrap();
+        for i in values {
+            collection
+                .insert(
+                    Activity::Active,
+                    Term::Default,
+                    Term::Default,
+                    [(field_num.clone(), i.encode())].into(),
+                )
+                .await
+                .unwrap();
+        }
+
+        let result = database
+            .search(collection_id)
+            .search_field_value(field_num.clone(), FieldValue::Range(-12i64, 10))
+            .result(&database)
+            .await;
+        let collection = database.collection(collection_id).unwrap();
+        let found: Vec<i64> = result
+            .sort(&database, &[Order::Asc(OrderKey::Field(field_num.clone()))])
+            .into_iter()
+            .filter_map(|row| collection.field_value(row, &field_num))
+            .collect();
+        assert_eq!(found, vec![-12, -1, 0, 1, 9, 10]);
+    });
+}
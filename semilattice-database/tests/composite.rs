@@ -0,0 +1,133 @@
+#[cfg(test)]
+#[test]
+fn composite() {
+    use semilattice_database::{
+        codec::{Codec, FieldValue},
+        search::{self, ExplainTarget},
+        *,
+    };
+
+    let dir = "./sl-test-composite/";
+
+    if std::path::Path::new(dir).exists() {
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+    std::fs::create_dir_all(dir).unwrap();
+
+    let field_status = FieldName::new("status".into());
+    let field_created = FieldName::new("created".into());
+    let orders = [
+        ("open", 30i64),
+        ("closed", 10),
+        ("open", 10),
+        ("open", 20),
+        ("closed", 20),
+        ("open", 100),
+    ];
+
+    futures::executor::block_on(async {
+        let mut database = Database::new(dir.into(), None, 10);
+        let collection_id = database.collection_id_or_create("order").unwrap();
+
+        async fn insert(
+            database: &mut Database,
+            collection_id: std::num::NonZeroI32,
+            (status, created): &(&str, i64),
+        ) {
+            database
+                .collection_mut(collection_id)
+                .unwrap()
+                .insert(
+                    Activity::Active,
+                    Term::Default,
+                    Term::Default,
+                    [
+                        (FieldName::new("status".into()), status.to_string().into()),
+                        (FieldName::new("created".into()), created.encode()),
+                    ]
+                    .into(),
+                )
+                .await
+                .unwrap();
+        }
+        for order in orders[..3].iter() {
+            insert(&mut database, collection_id, order).await;
+        }
+        database
+            .create_composite_index(
+                collection_id,
+                "status_created",
+                vec![field_status.clone(), field_created.clone()],
+            )
+            .unwrap();
+        for order in orders[3..].iter() {
+            insert(&mut database, collection_id, order).await;
+        }
+
+        let search = database
+            .search(collection_id)
+            .search_field(field_status.clone(), search::Field::Match(b"open".to_vec()))
+            .search_field_value(field_created.clone(), FieldValue::Range(10i64, 30));
+        let explain = search.explain(&database).await;
+        assert!(matches!(
+            &explain.children[0].target,
+            ExplainTarget::Composite { name, conditions } if name == "status_created" && conditions.len() == 2
+        ));
+
+        let result = search.result(&database).await;
+        let sorted = result.sort(
+            &database,
+            &[Order::Desc(OrderKey::Field(field_created.clone()))],
+        );
+        assert_eq!(
+            sorted.iter().map(|r| r.get()).collect::<Vec<_>>(),
+            vec![1, 4, 3]
+        );
+
+        database
+            .collection_mut(collection_id)
+            .unwrap()
+            .update(
+                6.try_into().unwrap(),
+                Activity::Active,
+                Term::Default,
+                Term::Default,
+                [(field_status.clone(), "closed".into())].into(),
+            )
+            .await
+            .unwrap();
+        let result = database
+            .search(collection_id)
+            .search_field(
+                field_status.clone(),
+                search::Field::Match(b"closed".to_vec()),
+            )
+            .result(&database)
+            .await;
+        assert_eq!(
+            result
+                .sort(
+                    &database,
+                    &[
+                        Order::Asc(OrderKey::Field(field_status.clone())),
+                        Order::Asc(OrderKey::Field(field_created.clone())),
+                    ],
+                )
+                .iter()
+                .map(|r| r.get())
+                .collect::<Vec<_>>(),
+            vec![2, 5, 6]
+        );
+    });
+
+    let database = Database::new(dir.into(), None, 10);
+    let collection_id = database.collection_id("order").unwrap();
+    assert_eq!(
+        database
+            .collection(collection_id)
+            .unwrap()
+            .composite("status_created")
+            .map(|index| index.fields().len()),
+        Some(2)
+    );
+}
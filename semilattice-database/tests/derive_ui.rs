@@ -0,0 +1,6 @@
+#[cfg(test)]
+#[test]
+fn derive_ui() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/*.rs");
+}
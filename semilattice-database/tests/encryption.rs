@@ -0,0 +1,224 @@
+#[cfg(test)]
+#[test]
+fn encryption() {
+    use semilattice_database::*;
+
+    let dir = "./sl-test-encryption/";
+
+    if std::path::Path::new(dir).exists() {
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    let key = EncryptionKey::new([7; 32]);
+    let field_name = FieldName::new("name".into());
+    let field_num = FieldName::new("num".into());
+
+    futures::executor::block_on(async {
+        let mut database = Database::open_encrypted(dir.into(), None, 10, &key).unwrap();
+        let collection_id = database.collection_id_or_create("person").unwrap();
+        database
+            .set_schema(
+                collection_id,
+                Some(
+                    Schema::new()
+                        .field(field_name.clone(), FieldSchema::new(FieldType::Utf8))
+                        .field(field_num.clone(), FieldSchema::new(FieldType::Bytes))
+                        .unique(vec![field_name.clone()]),
+                ),
+            )
+            .unwrap();
+        database.enable_history(collection_id).unwrap();
+        database.enable_audit().unwrap();
+        let collection = database.collection_mut(collection_id).unwrap();
+        assert!(collection.is_encrypted());
+        for (name, num) in [
+            ("Tom-secret", "1"),
+            ("Joe-secret", "2"),
+            ("Ann-secret", "3"),
+        ] {
+            collection
+                .insert(
+                    Activity::Active,
+                    Term::Overwrite(1000),
+                    Term::Default,
+                    [
+                        (field_name.clone(), name.into()),
+                        (field_num.clone(), num.into()),
+                    ]
+                    .into(),
+                )
+                .await
+                .unwrap();
+        }
+        assert!(matches!(
+            collection
+                .insert(
+                    Activity::Active,
+                    Term::Default,
+                    Term::Default,
+                    [(field_name.clone(), "Joe-secret".into())].into(),
+                )
+                .await,
+            Err(WriteError::Conflict(_))
+        ));
+
+        let row = 2.try_into().unwrap();
+        database
+            .register_relation(
+                "friend-secret",
+                &CollectionRow::new(collection_id, row),
+                &CollectionRow::new(collection_id, 1.try_into().unwrap()),
+            )
+            .await
+            .unwrap();
+        assert_eq!(database.relation().rows_by_key("friend-secret").len(), 1);
+        let relation_key = database
+            .audit(&audit::AuditQuery::new())
+            .into_iter()
+            .find_map(|entry| match entry.change {
+                audit::AuditChange::Relate { key, .. } => Some(key),
+                _ => None,
+            });
+        assert_eq!(relation_key.as_deref(), Some("friend-secret"));
+
+        let collection = database.collection(collection_id).unwrap();
+        assert_eq!(
+            collection.decrypted_bytes(row, &field_name),
+            &b"Joe-secret"[..]
+        );
+        assert_eq!(collection.field_bytes(row, &field_name).len(), 10 + 16);
+        let version = collection.history(row).pop().unwrap();
+        assert_eq!(version.term_begin, 1000);
+        let latest = collection.history(3.try_into().unwrap()).pop().unwrap();
+        assert_eq!(collection.rows_at(latest.at).len(), 3);
+        assert_eq!(
+            collection
+                .decrypt(&field_name, &version.fields[&field_name])
+                .unwrap(),
+            &b"Joe-secret"[..]
+        );
+
+        let result = database
+            .search(collection_id)
+            .search_field(
+                field_name.clone(),
+                search::Field::Match(b"Joe-secret".to_vec()),
+            )
+            .result(&database)
+            .await;
+        assert_eq!(
+            result.rows().iter().map(|r| r.get()).collect::<Vec<_>>(),
+            vec![2]
+        );
+        let result = database
+            .search(collection_id)
+            .search_field(field_num.clone(), search::Field::Min(b"2".to_vec()))
+            .result(&database)
+            .await;
+        assert_eq!(
+            result.rows().iter().map(|r| r.get()).collect::<Vec<_>>(),
+            vec![2, 3]
+        );
+        assert_eq!(
+            result.sort(
+                &database,
+                &[Order::Asc(OrderKey::Field(field_name.clone()))]
+            ),
+            vec![3.try_into().unwrap(), 2.try_into().unwrap()]
+        );
+        assert_eq!(
+            result.sort(
+                &database,
+                &[
+                    Order::Asc(OrderKey::Field(field_num.clone())),
+                    Order::Desc(OrderKey::Row)
+                ]
+            ),
+            vec![2.try_into().unwrap(), 3.try_into().unwrap()]
+        );
+        assert_eq!(
+            result.sort(&database, &[Order::Desc(OrderKey::Row)]),
+            vec![3.try_into().unwrap(), 2.try_into().unwrap()]
+        );
+        assert_eq!(
+            database.create_fulltext_index(collection_id, field_name.clone(), Default::default()),
+            Err(WriteError::Encrypted)
+        );
+        assert_eq!(
+            database.create_composite_index(collection_id, "name_num", vec![field_name.clone()]),
+            Err(WriteError::Encrypted)
+        );
+        drop(database);
+
+        fn files(dir: &std::path::Path) -> Vec<std::path::PathBuf> {
+            std::fs::read_dir(dir)
+                .unwrap()
+                .flat_map(|entry| {
+                    let path = entry.unwrap().path();
+                    if path.is_dir() {
+                        files(&path)
+                    } else {
+                        vec![path]
+                    }
+                })
+                .collect()
+        }
+        for path in files(std::path::Path::new(dir)) {
+            let bytes = std::fs::read(path).unwrap();
+            assert!(!bytes.windows(6).any(|window| window == b"secret"));
+        }
+
+        let error = Database::open_encrypted(dir.into(), None, 10, &EncryptionKey::new([8; 32]))
+            .err()
+            .unwrap();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+        let error = Database::open(dir.into(), None, 10).err().unwrap();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+        let error = Database::open_read_only(dir.into(), None).err().unwrap();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+        let error =
+            Database::open_read_only_encrypted(dir.into(), None, &EncryptionKey::new([8; 32]))
+                .err()
+                .unwrap();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+
+        let database = Database::open_read_only_encrypted(dir.into(), None, &key).unwrap();
+        let collection = database.collection(collection_id).unwrap();
+        assert_eq!(
+            collection.decrypted_bytes(3.try_into().unwrap(), &field_name),
+            &b"Ann-secret"[..]
+        );
+        assert_eq!(
+            collection.history(3.try_into().unwrap())[0].term_begin,
+            1000
+        );
+        assert_eq!(
+            database
+                .relation()
+                .keys()
+                .filter(|(_, count)| *count > 0)
+                .collect::<Vec<_>>(),
+            vec![("friend-secret", 1)]
+        );
+        drop(database);
+
+        let database = Database::open_encrypted(dir.into(), None, 10, &key).unwrap();
+        assert_eq!(database.relation().rows_by_key("friend-secret").len(), 1);
+    });
+
+    let plain = "./sl-test-encryption-plain/";
+    if std::path::Path::new(plain).exists() {
+        std::fs::remove_dir_all(plain).unwrap();
+    }
+    let mut database = Database::open(plain.into(), None, 10).unwrap();
+    database.collection_id_or_create("person").unwrap();
+    drop(database);
+    let error = Database::open_encrypted(plain.into(), None, 10, &key)
+        .err()
+        .unwrap();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+    let error = Database::open_read_only_encrypted(plain.into(), None, &key)
+        .err()
+        .unwrap();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+}
@@ -0,0 +1,186 @@
+#[cfg(test)]
+#[test]
+fn fulltext() {
+    use semilattice_database::{
+        fulltext::{FullText, FullTextOption},
+        search::Number,
+        *,
+    };
+
+    let dir = "./sl-test-fulltext/";
+
+    if std::path::Path::new(dir).exists() {
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+    std::fs::create_dir_all(dir).unwrap();
+
+    let field_title = FieldName::new("title".into());
+    let field_body = FieldName::new("body".into());
+    let docs = [
+        ("The quick brown fox", "Foxes jump over lazy dogs"),
+        (
+            "Brown bread recipe",
+            "Quick and easy brown bread with a brown crust",
+        ),
+        (
+            "A lazy afternoon",
+            "The fox sleeps. Quick naps, brown leaves",
+        ),
+    ];
+
+    futures::executor::block_on(async {
+        let mut database = Database::new(dir.into(), None, 10);
+        let collection_id = database.collection_id_or_create("doc").unwrap();
+
+        async fn insert(
+            database: &mut Database,
+            collection_id: std::num::NonZeroI32,
+            doc: &(&str, &str),
+        ) {
+            database
+                .collection_mut(collection_id)
+                .unwrap()
+                .insert(
+                    Activity::Active,
+                    Term::Default,
+                    Term::Default,
+                    [
+                        (FieldName::new("title".into()), doc.0.into()),
+                        (FieldName::new("body".into()), doc.1.into()),
+                    ]
+                    .into(),
+                )
+                .await
+                .unwrap();
+        }
+        insert(&mut database, collection_id, &docs[0]).await;
+        database
+            .create_fulltext_index(
+                collection_id,
+                field_title.clone(),
+                FullTextOption::default(),
+            )
+            .unwrap();
+        database
+            .create_fulltext_index(
+                collection_id,
+                field_body.clone(),
+                FullTextOption { ngram: Some(3) },
+            )
+            .unwrap();
+        for doc in docs[1..].iter() {
+            insert(&mut database, collection_id, doc).await;
+        }
+
+        let rows = |result: SearchResult| result.rows().iter().map(|r| r.get()).collect::<Vec<_>>();
+        let search = |field: &FieldName, query: FullText| {
+            Search::new(
+                collection_id,
+                vec![Condition::FullText(field.clone(), query)],
+                Default::default(),
+            )
+        };
+
+        assert_eq!(
+            rows(
+                search(&field_title, FullText::All("BROWN".into()))
+                    .result(&database)
+                    .await
+            ),
+            vec![1, 2]
+        );
+        assert_eq!(
+            rows(
+                search(&field_title, FullText::All("brown fox".into()))
+                    .result(&database)
+                    .await
+            ),
+            vec![1]
+        );
+        assert_eq!(
+            rows(
+                search(&field_title, FullText::Any("bread lazy".into()))
+                    .result(&database)
+                    .await
+            ),
+            vec![2, 3]
+        );
+        assert_eq!(
+            rows(
+                search(&field_body, FullText::Phrase("brown bread".into()))
+                    .result(&database)
+                    .await
+            ),
+            vec![2]
+        );
+        assert_eq!(
+            rows(
+                search(&field_body, FullText::Phrase("bread brown".into()))
+                    .result(&database)
+                    .await
+            ),
+            Vec::<u32>::new()
+        );
+        // n-grams match inside words: "fox" in "Foxes".
+        assert_eq!(
+            rows(
+                search(&field_body, FullText::All("fox".into()))
+                    .result(&database)
+                    .await
+            ),
+            vec![1, 3]
+        );
+
+        let collection = database.collection(collection_id).unwrap();
+        assert!(
+            search(&field_title, FullText::All("brown".into()))
+                .search_row(Number::In(vec![2]))
+                .result(&database)
+                .await
+                .rows()
+                .len()
+                == 1
+                && Condition::FullText(field_title.clone(), FullText::All("brown".into()))
+                    .cost(collection, database.relation())
+                    .rows
+                    == 2
+        );
+
+        let query = FullText::Any("quick brown".into());
+        let result = search(&field_body, query.clone()).result(&database).await;
+        let scores = collection.fulltext_scores(&field_body, &query, result.rows());
+        let sorted = result.sort(
+            &database,
+            &[Order::Desc(CustomOrderKey::Custom(scores.clone()))],
+        );
+        assert_eq!(
+            sorted.iter().map(|r| r.get()).collect::<Vec<_>>(),
+            vec![2, 3]
+        );
+        assert!(scores.get(sorted[0]) > scores.get(sorted[1]));
+
+        database
+            .delete(&CollectionRow::new(collection_id, 1.try_into().unwrap()))
+            .await
+            .unwrap();
+        assert_eq!(
+            rows(
+                search(&field_title, FullText::All("brown".into()))
+                    .result(&database)
+                    .await
+            ),
+            vec![2]
+        );
+    });
+
+    let database = Database::new(dir.into(), None, 10);
+    let collection_id = database.collection_id("doc").unwrap();
+    assert_eq!(
+        database
+            .collection(collection_id)
+            .unwrap()
+            .fulltext(&field_body)
+            .map(|index| index.option().clone()),
+        Some(FullTextOption { ngram: Some(3) })
+    );
+}
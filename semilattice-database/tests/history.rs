@@ -0,0 +1,106 @@
+#[cfg(test)]
+#[test]
+fn history() {
+    use semilattice_database::{search, *};
+
+    let dir = "./sl-test-history/";
+
+    if std::path::Path::new(dir).exists() {
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+    std::fs::create_dir_all(dir).unwrap();
+
+    let field_name = FieldName::new("name".into());
+    let field_age = FieldName::new("age".into());
+
+    futures::executor::block_on(async {
+        let mut database = Database::new(dir.into(), None, 10);
+        let collection_id = database.collection_id_or_create("person").unwrap();
+        let collection = database.collection_mut(collection_id).unwrap();
+        let joe = collection
+            .insert(
+                Activity::Active,
+                Term::Default,
+                Term::Default,
+                [
+                    (field_name.clone(), b"Joe".to_vec()),
+                    (field_age.clone(), b"30".to_vec()),
+                ]
+                .into(),
+            )
+            .await
+            .unwrap();
+
+        database.enable_history(collection_id).unwrap();
+        let collection = database.collection_mut(collection_id).unwrap();
+        let ann = collection
+            .insert(
+                Activity::Active,
+                Term::Default,
+                Term::Default,
+                [(field_name.clone(), b"Ann".to_vec())].into(),
+            )
+            .await
+            .unwrap();
+        // Times are in milliseconds, so Ann may be recorded after Joe.
+        let recorded = collection.history(joe)[0].at;
+        let before = collection.history(ann)[0].at;
+
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        collection
+            .update(
+                joe,
+                Activity::Inactive,
+                Term::Default,
+                Term::Default,
+                [(field_age.clone(), b"31".to_vec())].into(),
+            )
+            .await
+            .unwrap();
+        collection.delete(ann).await.unwrap();
+
+        let history = collection.history(joe);
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].fields[&field_age], b"30");
+        assert_eq!(history[1].fields[&field_age], b"31");
+        assert_eq!(history[1].fields[&field_name], b"Joe");
+        assert_eq!(history[1].activity, Activity::Inactive);
+        assert!(collection.history(ann)[1].deleted);
+
+        let past = collection.row_at(joe, before).unwrap();
+        assert_eq!(past.fields[&field_age], b"30");
+        assert_eq!(past.activity, Activity::Active);
+        assert_eq!(collection.row_at(joe, recorded - 1), None);
+        assert!(collection.row_at(ann, before).is_some());
+        assert_eq!(collection.row_at(ann, u64::MAX), None);
+
+        let result = database
+            .search(collection_id)
+            .search_field(field_age.clone(), search::Field::Match(b"30".to_vec()))
+            .search_activity(Activity::Active)
+            .at(before)
+            .result(&database)
+            .await;
+        assert_eq!(result.rows().iter().cloned().collect::<Vec<_>>(), [joe]);
+
+        let result = database
+            .search(collection_id)
+            .search_field(field_age.clone(), search::Field::Match(b"30".to_vec()))
+            .result(&database)
+            .await;
+        assert!(result.rows().is_empty());
+
+        drop(database);
+        let database = Database::new(dir.into(), None, 10);
+        assert_eq!(
+            database
+                .search(collection_id)
+                .at(before)
+                .result(&database)
+                .await
+                .rows()
+                .len(),
+            2
+        );
+    });
+}
@@ -0,0 +1,26 @@
+#[cfg(test)]
+#[test]
+fn lock() {
+    use semilattice_database::*;
+
+    let dir = "./sl-test-lock/";
+
+    if std::path::Path::new(dir).exists() {
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    let mut database = Database::open(dir.into(), None, 10).unwrap();
+    let collection_id = database.collection_id_or_create("person").unwrap();
+
+    let error = Database::open(dir.into(), None, 10).err().unwrap();
+    assert_eq!(error.kind(), std::io::ErrorKind::WouldBlock);
+    assert!(std::panic::catch_unwind(|| Database::new(dir.into(), None, 10)).is_err());
+
+    let reader = Database::open_read_only(dir.into(), None).unwrap();
+    assert_eq!(reader.collection_id("person"), Some(collection_id));
+    drop(reader);
+
+    drop(database);
+    let database = Database::open(dir.into(), None, 10).unwrap();
+    assert_eq!(database.collection_id("person"), Some(collection_id));
+}
@@ -0,0 +1,191 @@
+#[cfg(test)]
+#[test]
+fn migration() {
+    use futures::future::LocalBoxFuture;
+    use semilattice_database::{
+        migration::{Migration, MigrationChange, MigrationStep, Migrator},
+        *,
+    };
+
+    struct AddAge;
+    impl MigrationStep for AddAge {
+        fn version(&self) -> u32 {
+            1
+        }
+
+        fn description(&self) -> &str {
+            "add age"
+        }
+
+        fn run<'a>(
+            &'a self,
+            migration: &'a mut Migration<'_>,
+        ) -> LocalBoxFuture<'a, Result<(), WriteError>> {
+            Box::pin(async move {
+                let collection_id = migration.database().collection_id("person").unwrap();
+                migration
+                    .set_default(collection_id, &FieldName::new("age".into()), b"0")
+                    .await
+            })
+        }
+    }
+
+    struct RenameFriend;
+    impl MigrationStep for RenameFriend {
+        fn version(&self) -> u32 {
+            2
+        }
+
+        fn run<'a>(
+            &'a self,
+            migration: &'a mut Migration<'_>,
+        ) -> LocalBoxFuture<'a, Result<(), WriteError>> {
+            Box::pin(async move {
+                migration.rename_relation_key("friend", "buddy").await?;
+                Ok(())
+            })
+        }
+    }
+
+    /// Moves the second person into a collection of its own, with its relations.
+    struct SplitContact;
+    impl MigrationStep for SplitContact {
+        fn version(&self) -> u32 {
+            3
+        }
+
+        fn run<'a>(
+            &'a self,
+            migration: &'a mut Migration<'_>,
+        ) -> LocalBoxFuture<'a, Result<(), WriteError>> {
+            Box::pin(async move {
+                let person = migration.database().collection_id("person").unwrap();
+                let contact = migration.collection_id_or_create("contact")?;
+                let from = CollectionRow::new(person, 2.try_into().unwrap());
+                let name = migration
+                    .database()
+                    .collection(person)
+                    .unwrap()
+                    .field_bytes(from.row(), &FieldName::new("name".into()))
+                    .to_vec();
+                if let Some(to) = migration
+                    .insert(
+                        contact,
+                        Activity::Active,
+                        Term::Default,
+                        Term::Default,
+                        [(FieldName::new("name".into()), name)].into(),
+                    )
+                    .await?
+                {
+                    migration.move_relations(&from, &to).await?;
+                }
+                Ok(())
+            })
+        }
+    }
+
+    let dir = "./sl-test-migration/";
+
+    if std::path::Path::new(dir).exists() {
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+    std::fs::create_dir_all(dir).unwrap();
+
+    let field_name = FieldName::new("name".into());
+    let field_age = FieldName::new("age".into());
+
+    futures::executor::block_on(async {
+        let mut database = Database::new(dir.into(), None, 10);
+        let collection_id = database.collection_id_or_create("person").unwrap();
+        let collection = database.collection_mut(collection_id).unwrap();
+        let mut rows = vec![];
+        for (name, age) in [("Joe", None), ("Ann", Some("30"))] {
+            let mut fields: hashbrown::HashMap<_, Vec<u8>> =
+                [(field_name.clone(), name.into())].into();
+            if let Some(age) = age {
+                fields.insert(field_age.clone(), age.into());
+            }
+            rows.push(CollectionRow::new(
+                collection_id,
+                collection
+                    .insert(Activity::Active, Term::Default, Term::Default, fields)
+                    .await
+                    .unwrap(),
+            ));
+        }
+        database
+            .register_relation("friend", &rows[0], &rows[1])
+            .await
+            .unwrap();
+
+        let migrator = Migrator::new()
+            .step(RenameFriend)
+            .step(AddAge)
+            .step(SplitContact);
+
+        let report = migrator.dry_run(&database).await.unwrap();
+        assert_eq!(report.steps.len(), 3);
+        assert_eq!(report.steps[0].description, "add age");
+        assert_eq!(
+            report.steps[0].changes,
+            vec![MigrationChange::Update(rows[0].clone())]
+        );
+        assert_eq!(
+            report.steps[1].changes,
+            vec![MigrationChange::RenameRelationKey {
+                from: "friend".into(),
+                to: "buddy".into(),
+                rows: 1
+            }]
+        );
+        let contact = std::num::NonZeroI32::new(2).unwrap();
+        assert_eq!(
+            report.steps[2].changes,
+            vec![
+                MigrationChange::CreateCollection {
+                    name: "contact".into(),
+                    collection_id: contact
+                },
+                MigrationChange::Insert(contact)
+            ]
+        );
+        assert_eq!(database.collection_id("contact"), None);
+        assert_eq!(database.schema_version(), 0);
+        assert_eq!(
+            database
+                .collection(collection_id)
+                .unwrap()
+                .field_bytes(rows[0].row(), &field_age),
+            b""
+        );
+
+        let report = migrator.run(&mut database).await.unwrap();
+        assert_eq!(database.schema_version(), 3);
+        let moved = CollectionRow::new(contact, 1.try_into().unwrap());
+        assert_eq!(
+            report.steps[2].changes[2],
+            MigrationChange::MoveRelations {
+                from: rows[1].clone(),
+                to: moved.clone(),
+                rows: 1
+            }
+        );
+        assert_eq!(
+            database
+                .depends(None, contact, moved.row())
+                .iter()
+                .map(|depend| (depend.key().as_str(), depend.collection_id(), depend.row()))
+                .collect::<Vec<_>>(),
+            vec![("buddy", collection_id, rows[0].row())]
+        );
+        let collection = database.collection(collection_id).unwrap();
+        assert_eq!(collection.field_bytes(rows[0].row(), &field_age), b"0");
+        assert_eq!(collection.field_bytes(rows[1].row(), &field_age), b"30");
+        assert!(database
+            .depends(None, collection_id, rows[1].row())
+            .is_empty());
+
+        assert!(migrator.run(&mut database).await.unwrap().steps.is_empty());
+    });
+}
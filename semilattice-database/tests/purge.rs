@@ -0,0 +1,99 @@
+#[cfg(test)]
+#[test]
+fn purge_expired() {
+    use semilattice_database::*;
+
+    let dir = "./sl-test-purge/";
+
+    if std::path::Path::new(dir).exists() {
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+    std::fs::create_dir_all(dir).unwrap();
+
+    futures::executor::block_on(async {
+        let mut database = Database::new(dir.into(), None, 10);
+        let collection_offer = database.collection_id_or_create("offer").unwrap();
+        let collection_order = database.collection_id_or_create("order").unwrap();
+
+        let mut offers = vec![];
+        for term_end in [100, 0, 300] {
+            let row = database
+                .collection_mut(collection_offer)
+                .unwrap()
+                .insert(
+                    Activity::Active,
+                    Term::Overwrite(1),
+                    Term::Overwrite(term_end),
+                    Default::default(),
+                )
+                .await
+                .unwrap();
+            offers.push(CollectionRow::new(collection_offer, row));
+        }
+        let order = CollectionRow::new(
+            collection_order,
+            database
+                .collection_mut(collection_order)
+                .unwrap()
+                .insert(
+                    Activity::Active,
+                    Term::Default,
+                    Term::Default,
+                    Default::default(),
+                )
+                .await
+                .unwrap(),
+        );
+        database
+            .register_relation("offer", &offers[0], &order)
+            .await
+            .unwrap();
+
+        let report = database
+            .purge_expired(200, PurgePolicy::Deactivate)
+            .await
+            .unwrap();
+        assert_eq!(report.rows, [offers[0].clone()]);
+        let collection = database.collection(collection_offer).unwrap();
+        assert_eq!(
+            collection.activity(offers[0].row()),
+            Some(Activity::Inactive)
+        );
+        assert_eq!(collection.term_end(offers[0].row()), Some(&100));
+        assert_eq!(
+            database
+                .purge_expired(200, PurgePolicy::Deactivate)
+                .await
+                .unwrap(),
+            PurgeReport::default()
+        );
+
+        let report = database
+            .purge_expired(200, PurgePolicy::Delete)
+            .await
+            .unwrap();
+        assert_eq!(report.rows, [offers[0].clone()]);
+        assert_eq!(report.cascaded, [order]);
+        assert_eq!(
+            database
+                .collection(collection_offer)
+                .unwrap()
+                .data()
+                .all()
+                .len(),
+            2
+        );
+        assert!(database
+            .collection(collection_order)
+            .unwrap()
+            .data()
+            .all()
+            .is_empty());
+        assert!(database
+            .purge_expired(200, PurgePolicy::Delete)
+            .await
+            .unwrap()
+            .rows
+            .is_empty());
+    });
+}
@@ -0,0 +1,168 @@
+#[cfg(test)]
+#[test]
+fn read_only() {
+    use semilattice_database::{bulk::BulkRecord, transaction::TransactionError, *};
+
+    let dir = "./sl-test-read_only/";
+
+    if std::path::Path::new(dir).exists() {
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+    std::fs::create_dir_all(dir).unwrap();
+
+    let field_name = FieldName::new("name".into());
+
+    futures::executor::block_on(async {
+        assert!(Database::open_read_only("./sl-test-read_only/missing/".into(), None).is_err());
+        assert!(!std::path::Path::new("./sl-test-read_only/missing/").exists());
+
+        let mut database = Database::new(dir.into(), None, 10);
+        let collection_id = database.collection_id_or_create("person").unwrap();
+        let row = database
+            .collection_mut(collection_id)
+            .unwrap()
+            .insert(
+                Activity::Active,
+                Term::Default,
+                Term::Default,
+                [(field_name.clone(), b"Joe".to_vec())].into(),
+            )
+            .await
+            .unwrap();
+        drop(database);
+
+        let mut database = Database::open_read_only(dir.into(), None).unwrap();
+        assert!(database.is_read_only());
+        assert_eq!(database.collection_id("person"), Some(collection_id));
+        let collection = database.collection_mut(collection_id).unwrap();
+        assert_eq!(collection.field_bytes(row, &field_name), b"Joe");
+        assert_eq!(
+            collection
+                .insert(
+                    Activity::Active,
+                    Term::Default,
+                    Term::Default,
+                    [(field_name.clone(), b"Ann".to_vec())].into(),
+                )
+                .await,
+            Err(WriteError::ReadOnly)
+        );
+        assert_eq!(
+            collection
+                .update(
+                    row,
+                    Activity::Inactive,
+                    Term::Default,
+                    Term::Default,
+                    [].into()
+                )
+                .await,
+            Err(WriteError::ReadOnly)
+        );
+        assert_eq!(
+            database
+                .search(collection_id)
+                .result(&database)
+                .await
+                .rows()
+                .len(),
+            1
+        );
+
+        let mut transaction = database.transaction();
+        transaction.delete(CollectionRow::new(collection_id, row));
+        assert!(matches!(
+            transaction.commit().await,
+            Err(TransactionError::Write {
+                error: WriteError::ReadOnly,
+                ..
+            })
+        ));
+        assert!(database
+            .bulk_load(
+                futures::stream::iter(vec![BulkRecord::new(1, collection_id)]),
+                10
+            )
+            .await
+            .is_err());
+
+        assert_eq!(
+            database.collection_id_or_create("pet"),
+            Err(WriteError::ReadOnly)
+        );
+        assert_eq!(
+            database.collection_id_or_create("person"),
+            Ok(collection_id)
+        );
+        let target = CollectionRow::new(collection_id, row);
+        assert_eq!(database.delete(&target).await, Err(WriteError::ReadOnly));
+        assert_eq!(
+            database.register_relation("self", &target, &target).await,
+            Err(WriteError::ReadOnly)
+        );
+        assert_eq!(
+            database.set_schema(collection_id, None),
+            Err(WriteError::ReadOnly)
+        );
+        assert_eq!(
+            database.enable_history(collection_id),
+            Err(WriteError::ReadOnly)
+        );
+        assert_eq!(database.enable_audit(), Err(WriteError::ReadOnly));
+        assert_eq!(
+            database.purge_expired(0, PurgePolicy::Delete).await,
+            Err(WriteError::ReadOnly)
+        );
+        assert_eq!(
+            database.delete_collection("person").await,
+            Err(WriteError::ReadOnly)
+        );
+        assert!(database
+            .collection_mut(collection_id)
+            .unwrap()
+            .data_mut()
+            .is_err());
+        assert!(database.collection_id("pet").is_none());
+        assert_eq!(
+            database
+                .collection(collection_id)
+                .unwrap()
+                .data()
+                .all()
+                .len(),
+            1
+        );
+        #[cfg(target_os = "linux")]
+        {
+            let dir = std::fs::canonicalize(dir).unwrap();
+            let maps = std::fs::read_to_string("/proc/self/maps").unwrap();
+            let permissions: Vec<_> = maps
+                .lines()
+                .filter(|line| line.contains(dir.to_str().unwrap()))
+                .map(|line| line.split_whitespace().nth(1).unwrap())
+                .collect();
+            assert!(!permissions.is_empty());
+            assert!(permissions
+                .iter()
+                .all(|permissions| !permissions.contains('w')));
+        }
+        drop(database);
+
+        let relation_dir = std::path::Path::new(dir).join("relation");
+        std::fs::remove_dir_all(&relation_dir).unwrap();
+        std::fs::create_dir(&relation_dir).unwrap();
+        let database = Database::open_read_only(dir.into(), None).unwrap();
+        assert!(database.relation().depends(None, &target).is_empty());
+        assert_eq!(relation_dir.read_dir().unwrap().count(), 0);
+        drop(database);
+
+        std::fs::write(relation_dir.join("key.i"), []).unwrap();
+        assert_eq!(
+            Database::open_read_only(dir.into(), None)
+                .err()
+                .unwrap()
+                .kind(),
+            std::io::ErrorKind::InvalidData
+        );
+    });
+}
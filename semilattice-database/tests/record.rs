@@ -0,0 +1,84 @@
+#[cfg(test)]
+#[test]
+fn record() {
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use semilattice_database::{codec::FieldValue, *};
+    use semilattice_database_derive::CollectionRecord;
+
+    #[derive(CollectionRecord, Debug, PartialEq)]
+    struct Person {
+        #[record(max_length = 16)]
+        name: String,
+        age: i64,
+        #[record(rename = "birth")]
+        born: Option<SystemTime>,
+    }
+
+    let dir = "./sl-test-record/";
+
+    if std::path::Path::new(dir).exists() {
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+    std::fs::create_dir_all(dir).unwrap();
+
+    futures::executor::block_on(async {
+        let mut database = Database::new(dir.into(), None, 10);
+        let collection_id = database.collection_id_or_create("person").unwrap();
+        database
+            .set_schema(collection_id, Some(Person::schema()))
+            .unwrap();
+
+        let people = [
+            Person {
+                name: "Joe".into(),
+                age: 52,
+                born: Some(UNIX_EPOCH + Duration::from_secs(83_548_800)),
+            },
+            Person {
+                name: "Ann".into(),
+                age: 9,
+                born: None,
+            },
+        ];
+        let collection = database.collection_mut(collection_id).unwrap();
+        let mut rows = vec![];
+        for person in people.iter() {
+            rows.push(
+                collection
+                    .insert(
+                        Activity::Active,
+                        Term::Default,
+                        Term::Default,
+                        person.to_fields(),
+                    )
+                    .await
+                    .unwrap(),
+            );
+        }
+        assert!(collection
+            .insert(
+                Activity::Active,
+                Term::Default,
+                Term::Default,
+                [(FieldName::new("age".into()), b"9".to_vec())].into(),
+            )
+            .await
+            .is_err());
+
+        let result = database
+            .search(collection_id)
+            .search(Person::age_condition(FieldValue::Min(10)))
+            .result(&database)
+            .await;
+        assert_eq!(
+            result.rows().iter().cloned().collect::<Vec<_>>(),
+            vec![rows[0]]
+        );
+
+        let collection = database.collection(collection_id).unwrap();
+        for (row, person) in rows.iter().zip(people.iter()) {
+            assert_eq!(collection.record::<Person>(*row).as_ref(), Ok(person));
+        }
+    });
+}
@@ -0,0 +1,242 @@
+#[cfg(test)]
+#[test]
+fn schema() {
+    use semilattice_database::{codec::Codec, *};
+
+    let dir = "./sl-test-schema/";
+
+    if std::path::Path::new(dir).exists() {
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+    std::fs::create_dir_all(dir).unwrap();
+
+    let field_name = FieldName::new("name".into());
+    let field_age = FieldName::new("age".into());
+    let schema = Schema::new()
+        .field(
+            field_name.clone(),
+            FieldSchema::new(FieldType::Utf8).required().max_length(8),
+        )
+        .field(field_age.clone(), FieldSchema::new(FieldType::I64));
+
+    futures::executor::block_on(async {
+        let mut database = Database::new(dir.into(), None, 10);
+        let collection_id = database.collection_id_or_create("person").unwrap();
+        database
+            .set_schema(collection_id, Some(schema.clone()))
+            .unwrap();
+
+        let collection = database.collection_mut(collection_id).unwrap();
+        let row = collection
+            .insert(
+                Activity::Active,
+                Term::Default,
+                Term::Default,
+                [
+                    (field_name.clone(), "Joe".into()),
+                    (field_age.clone(), 52i64.encode()),
+                ]
+                .into(),
+            )
+            .await
+            .unwrap();
+
+        let error = collection
+            .insert(
+                Activity::Active,
+                Term::Default,
+                Term::Default,
+                [
+                    (field_age.clone(), "52".into()),
+                    (FieldName::new("nmae".into()), "Ann".into()),
+                ]
+                .into(),
+            )
+            .await
+            .unwrap_err();
+        let WriteError::Validation(error) = error else {
+            panic!("{}", error);
+        };
+        assert_eq!(
+            error.errors,
+            vec![
+                FieldError::Type {
+                    field_name: field_age.clone(),
+                    expected: FieldType::I64
+                },
+                FieldError::Missing(field_name.clone()),
+                FieldError::Unknown(FieldName::new("nmae".into())),
+            ]
+        );
+
+        assert!(collection
+            .update(
+                row,
+                Activity::Active,
+                Term::Default,
+                Term::Default,
+                [(field_age.clone(), 53i64.encode())].into(),
+            )
+            .await
+            .is_ok());
+        assert_eq!(
+            collection
+                .update(
+                    row,
+                    Activity::Active,
+                    Term::Default,
+                    Term::Default,
+                    [(field_name.clone(), "Joe Smith".into())].into(),
+                )
+                .await,
+            Err(WriteError::Validation(ValidationError {
+                collection_id,
+                errors: vec![FieldError::TooLong {
+                    field_name: field_name.clone(),
+                    max_length: 8,
+                    length: 9
+                }]
+            }))
+        );
+        assert_eq!(
+            collection
+                .update(
+                    row,
+                    Activity::Active,
+                    Term::Default,
+                    Term::Default,
+                    [(field_name.clone(), "R2D2".into())].into(),
+                )
+                .await,
+            Err(WriteError::Validation(ValidationError {
+                collection_id,
+                errors: vec![FieldError::Type {
+                    field_name: field_name.clone(),
+                    expected: FieldType::Utf8
+                }]
+            }))
+        );
+        assert!(collection
+            .update(
+                row,
+                Activity::Active,
+                Term::Default,
+                Term::Default,
+                [(field_name.clone(), "R2D2".to_owned().encode())].into(),
+            )
+            .await
+            .is_ok());
+    });
+
+    let database = Database::new(dir.into(), None, 10);
+    let collection_id = database.collection_id("person").unwrap();
+    assert_eq!(
+        database.collection(collection_id).unwrap().schema(),
+        Some(&schema)
+    );
+    drop(database);
+
+    std::fs::write(
+        format!("{dir}collection/{collection_id}_person/schema.toml"),
+        "unique = [",
+    )
+    .unwrap();
+    let error = Database::open(dir.into(), None, 10).err().unwrap();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+}
+
+#[cfg(test)]
+#[test]
+fn unique() {
+    use semilattice_database::*;
+
+    let dir = "./sl-test-unique/";
+
+    if std::path::Path::new(dir).exists() {
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+    std::fs::create_dir_all(dir).unwrap();
+
+    let field_site = FieldName::new("site".into());
+    let field_slug = FieldName::new("slug".into());
+
+    futures::executor::block_on(async {
+        let mut database = Database::new(dir.into(), None, 10);
+        let collection_id = database.collection_id_or_create("page").unwrap();
+        database
+            .set_schema(
+                collection_id,
+                Some(
+                    Schema::new()
+                        .field(field_site.clone(), FieldSchema::new(FieldType::Utf8))
+                        .field(field_slug.clone(), FieldSchema::new(FieldType::Utf8))
+                        .unique(vec![field_site.clone(), field_slug.clone()]),
+                ),
+            )
+            .unwrap();
+
+        let collection = database.collection_mut(collection_id).unwrap();
+        let mut rows = vec![];
+        for (site, slug) in [("a", "top"), ("b", "top"), ("a", "about")] {
+            rows.push(
+                collection
+                    .insert(
+                        Activity::Active,
+                        Term::Default,
+                        Term::Default,
+                        [
+                            (field_site.clone(), site.into()),
+                            (field_slug.clone(), slug.into()),
+                        ]
+                        .into(),
+                    )
+                    .await
+                    .unwrap(),
+            );
+        }
+        let conflict = Err(WriteError::Conflict(UniqueConflict {
+            collection_id,
+            field_names: vec![field_site.clone(), field_slug.clone()],
+            existing: CollectionRow::new(collection_id, rows[0]),
+        }));
+        assert_eq!(
+            collection
+                .insert(
+                    Activity::Active,
+                    Term::Default,
+                    Term::Default,
+                    [
+                        (field_site.clone(), "a".into()),
+                        (field_slug.clone(), "top".into()),
+                    ]
+                    .into(),
+                )
+                .await,
+            conflict
+        );
+        // The slug is checked together with the site kept from the row.
+        assert_eq!(
+            collection
+                .update(
+                    rows[2],
+                    Activity::Active,
+                    Term::Default,
+                    Term::Default,
+                    [(field_slug.clone(), "top".into())].into(),
+                )
+                .await
+                .map(|_| rows[2]),
+            conflict.clone()
+        );
+        assert!(collection
+            .update(
+                rows[0],
+                Activity::Active,
+                Term::Default,
+                Term::Default,
+                [(field_slug.clone(), "top".into())].into(),
+            )
+            .await
+            .is_ok());
+    });
+}
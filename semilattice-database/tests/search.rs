@@ -0,0 +1,266 @@
+#[cfg(test)]
+#[test]
+fn search_plan() {
+    use semilattice_database::*;
+
+    let dir = "./sl-test-search/";
+
+    if std::path::Path::new(dir).exists() {
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+    std::fs::create_dir_all(dir).unwrap();
+
+    futures::executor::block_on(async {
+        let mut database = Database::new(dir.into(), None, 10);
+
+        let collection_id = database.collection_id_or_create("item").unwrap();
+        let field_name = FieldName::new("name".into());
+        let field_num = FieldName::new("num".into());
+
+        let collection = database.collection_mut(collection_id).unwrap();
+        for i in 1..=100 {
+            collection
+                .insert(
+                    if i % 2 == 0 {
+                        Activity::Active
+                    } else {
+                        Activity::Inactive
+                    },
+                    Term::Default,
+                    Term::Default,
+                    [
+                        (field_name.clone(), format!("item-{}", i).into()),
+                        (field_num.clone(), i.to_string().into()),
+                    ]
+                    .into(),
+                )
+                .await
+                .unwrap();
+        }
+
+        let collection = database.collection(collection_id).unwrap();
+        let uuid = *collection.uuid(10.try_into().unwrap()).unwrap();
+
+        let partial = Condition::Field(
+            field_name.clone(),
+            search::Field::Partial("item-1".to_owned().into()),
+        );
+        let range = Condition::Field(
+            field_num.clone(),
+            search::Field::Range(b"5".to_vec(), b"50".to_vec()),
+        );
+        assert!(
+            partial.cost(collection, database.relation()).access == search::Access::Scan
+                && Condition::Uuid(vec![uuid])
+                    .cost(collection, database.relation())
+                    .rows
+                    == 1
+        );
+
+        let result = database
+            .search(collection_id)
+            .search(partial.clone())
+            .search(range)
+            .search_activity(Activity::Active)
+            .result(&database)
+            .await;
+        assert_eq!(
+            result.rows().iter().map(|r| r.get()).collect::<Vec<_>>(),
+            vec![10, 12, 14, 16, 18]
+        );
+
+        let result = database
+            .search(collection_id)
+            .search(partial)
+            .search(Condition::Uuid(vec![uuid]))
+            .default()
+            .result(&database)
+            .await;
+        assert_eq!(
+            result.rows().iter().map(|r| r.get()).collect::<Vec<_>>(),
+            vec![10]
+        );
+    });
+}
+
+#[cfg(test)]
+#[test]
+fn search_explain() {
+    use std::sync::Arc;
+
+    use hashbrown::HashMap;
+    use semilattice_database::*;
+
+    let dir = "./sl-test-explain/";
+
+    if std::path::Path::new(dir).exists() {
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+    std::fs::create_dir_all(dir).unwrap();
+
+    futures::executor::block_on(async {
+        let mut database = Database::new(dir.into(), None, 10);
+
+        let collection_person = database.collection_id_or_create("person").unwrap();
+        let collection_history = database.collection_id_or_create("history").unwrap();
+        let field_name = FieldName::new("name".into());
+
+        for name in ["Joe", "Ann", "Bob"] {
+            let person = database
+                .collection_mut(collection_person)
+                .unwrap()
+                .insert(
+                    Activity::Active,
+                    Term::Default,
+                    Term::Default,
+                    [(field_name.clone(), name.into())].into(),
+                )
+                .await
+                .unwrap();
+            let history = database
+                .collection_mut(collection_history)
+                .unwrap()
+                .insert(
+                    Activity::Active,
+                    Term::Default,
+                    Term::Default,
+                    [(field_name.clone(), format!("{} born", name).into())].into(),
+                )
+                .await
+                .unwrap();
+            database
+                .register_relation(
+                    "history",
+                    &CollectionRow::new(collection_person, person),
+                    &CollectionRow::new(collection_history, history),
+                )
+                .await
+                .unwrap();
+        }
+
+        let search = Search::new(
+            collection_person,
+            vec![
+                Condition::Field(
+                    field_name.clone(),
+                    search::Field::Partial("o".to_owned().into()),
+                ),
+                Condition::Activity(Activity::Active),
+            ],
+            [(
+                Arc::new("history".to_owned()),
+                SearchJoin::new(
+                    collection_history,
+                    vec![],
+                    Some(Arc::new("history".to_owned())),
+                    HashMap::new(),
+                ),
+            )]
+            .into(),
+        );
+        let (result, explain) = search.clone().result_explain(&database).await;
+        assert_eq!(result, search.clone().result(&database).await);
+
+        println!("{}", explain);
+        assert_eq!(explain.rows, 2);
+        assert_eq!(explain.children.len(), 3);
+        assert_eq!(explain.children[0].method, Some(search::Method::Index));
+        assert_eq!(explain.children[1].method, Some(search::Method::Filter));
+        assert_eq!(explain.children[2].rows, 2);
+
+        let records = result.records(
+            &database,
+            &search::RecordSelect {
+                fields: Some(vec![field_name.clone()]),
+                depends: false,
+                join: [(
+                    Arc::new("history".to_owned()),
+                    search::RecordSelect {
+                        depends: true,
+                        ..Default::default()
+                    },
+                )]
+                .into(),
+            },
+            &search::Utf8Decoder,
+        );
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].fields[&field_name], "Joe");
+        let history = &records[1].join[&Arc::new("history".to_owned())];
+        assert_eq!(history[0].fields[&field_name], "Bob born");
+        assert_eq!(
+            history[0].depends.as_ref().unwrap()[0].collection_id(),
+            collection_person
+        );
+        let json = serde_json::to_value(&records).unwrap();
+        assert_eq!(json[1]["join"]["history"][0]["fields"]["name"], "Bob born");
+        assert_eq!(json[1]["activity"], "Active");
+    });
+}
+
+#[cfg(test)]
+#[test]
+fn search_serde() {
+    use std::{num::NonZeroI32, sync::Arc};
+
+    use hashbrown::HashMap;
+    use semilattice_database::*;
+
+    let person = NonZeroI32::new(1).unwrap();
+    let history = NonZeroI32::new(2).unwrap();
+    let search = Search::new(
+        person,
+        vec![
+            Condition::Activity(Activity::Active),
+            Condition::Term(search::Term::In(1700000000)),
+            Condition::Row(search::Number::Range(1..=10)),
+            Condition::Uuid(vec![0x67e55044_10b1_426f_9247_bb680e5fe0c8]),
+            Condition::Field(
+                FieldName::new("name".into()),
+                search::Field::Match(b"Joe".to_vec()),
+            ),
+            Condition::FullText(
+                FieldName::new("body".into()),
+                fulltext::FullText::All("quick brown".into()),
+            ),
+            Condition::Wide(vec![Condition::Depend(
+                Some(Arc::new("history".into())),
+                CollectionRow::new(history, 3.try_into().unwrap()),
+            )]),
+        ],
+        [(
+            Arc::new("history".to_owned()),
+            SearchJoin::new(history, vec![], None, HashMap::new()),
+        )]
+        .into(),
+    )
+    .at(1700000000000);
+
+    let json = serde_json::to_string(&search).unwrap();
+    assert_eq!(
+        json,
+        concat!(
+            r#"{"collection_id":1,"conditions":["#,
+            r#"{"Activity":"Active"},"#,
+            r#"{"Term":{"In":1700000000}},"#,
+            r#"{"Row":{"Range":{"start":1,"end":10}}},"#,
+            r#"{"Uuid":["67e55044-10b1-426f-9247-bb680e5fe0c8"]},"#,
+            r#"{"Field":["name",{"Match":[74,111,101]}]},"#,
+            r#"{"FullText":["body",{"All":"quick brown"}]},"#,
+            r#"{"Wide":[{"Depend":["history",{"collection_id":2,"row":3}]}]}],"#,
+            r#""join":{"history":{"collection_id":2,"relation_key":null,"conditions":[],"join":{}}},"as_of":1700000000000}"#
+        )
+    );
+    assert_eq!(serde_json::from_str::<Search>(&json).unwrap(), search);
+
+    for row in [
+        "{}",
+        "null",
+        r#"{"collection_id":2}"#,
+        r#"{"collection_id":0,"row":3}"#,
+        r#"{"collection_id":2,"row":0}"#,
+    ] {
+        assert!(serde_json::from_str::<CollectionRow>(row).is_err());
+    }
+    assert!(serde_json::from_str::<Condition>(r#"{"Uuid":[1]}"#).is_err());
+}
@@ -0,0 +1,96 @@
+#[cfg(test)]
+#[test]
+fn stats() {
+    use semilattice_database::*;
+
+    let dir = "./sl-test-stats/";
+
+    if std::path::Path::new(dir).exists() {
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+    std::fs::create_dir_all(dir).unwrap();
+
+    let field_name = FieldName::new("name".into());
+
+    futures::executor::block_on(async {
+        let mut database = Database::new(dir.into(), None, 10);
+        let collection_id = database.collection_id_or_create("person").unwrap();
+        let collection = database.collection_mut(collection_id).unwrap();
+        let mut rows = vec![];
+        for (name, activity) in [
+            ("Joe", Activity::Active),
+            ("Ann", Activity::Inactive),
+            ("Tom", Activity::Active),
+        ] {
+            rows.push(CollectionRow::new(
+                collection_id,
+                collection
+                    .insert(
+                        activity,
+                        Term::Default,
+                        Term::Default,
+                        [(field_name.clone(), name.into())].into(),
+                    )
+                    .await
+                    .unwrap(),
+            ));
+        }
+        database
+            .register_relation("friend", &rows[0], &rows[1])
+            .await
+            .unwrap();
+        database
+            .register_relation("friend", &rows[0], &rows[2])
+            .await
+            .unwrap();
+        database
+            .register_relation("parent", &rows[1], &rows[2])
+            .await
+            .unwrap();
+        let relation_row = database.relation().rows_by_depend(&rows[1]).next();
+        database
+            .relation_mut()
+            .delete(relation_row.unwrap())
+            .await
+            .unwrap();
+
+        let stats = database.stats();
+        let collection = &stats.collections[0];
+        assert_eq!(
+            (
+                collection.name.as_str(),
+                collection.rows,
+                collection.active_rows,
+                collection.inactive_rows
+            ),
+            ("person", 3, 2, 1)
+        );
+        assert!(collection.fields[&field_name] > 0);
+        assert!(collection.indexes.contains_key("uuid.i"));
+        assert!(collection.bytes() > collection.fields[&field_name]);
+
+        assert_eq!(stats.relation.relations, 2);
+        assert_eq!(
+            stats.relation.keys,
+            [("friend".to_owned(), 2)].into_iter().collect()
+        );
+        assert_eq!(stats.relation.free_rows, 1);
+
+        assert_eq!(
+            database.relation().keys().collect::<Vec<_>>(),
+            [("friend", 2), ("parent", 0)]
+        );
+        assert_eq!(
+            database.collection_entries().collect::<Vec<_>>(),
+            [(collection_id, "person")]
+        );
+        assert_eq!(
+            database
+                .collection(collection_id)
+                .unwrap()
+                .field_names()
+                .collect::<Vec<_>>(),
+            [&field_name]
+        );
+    });
+}
@@ -0,0 +1,147 @@
+#[cfg(test)]
+#[test]
+fn relation_storage() {
+    use std::sync::Arc;
+
+    use semilattice_database::*;
+
+    let dir = "./sl-test-storage/";
+
+    if std::path::Path::new(dir).exists() {
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    futures::executor::block_on(async {
+        let mut results = vec![];
+        for mut database in [
+            Database::open(dir.into(), None, 10).unwrap(),
+            Database::temporary(None, 10),
+        ] {
+            if database.dir() == std::path::Path::new(dir) {
+                // Reopen the first one with relations kept in memory.
+                drop(database);
+                database =
+                    Database::open_with(dir.into(), None, Box::new(MemoryRelationStorage::new()))
+                        .unwrap();
+            }
+            let collection_id = database.collection_id_or_create("person").unwrap();
+            let mut rows = vec![];
+            for name in ["Joe", "Ann", "Tom"] {
+                let row = database
+                    .collection_mut(collection_id)
+                    .unwrap()
+                    .insert_unchecked(
+                        Activity::Active,
+                        Term::Default,
+                        Term::Default,
+                        [(FieldName::new("name".into()), name.as_bytes().to_vec())].into(),
+                    )
+                    .await
+                    .unwrap();
+                rows.push(CollectionRow::new(collection_id, row));
+            }
+            database
+                .register_relation("parent", &rows[0], &rows[1])
+                .await
+                .unwrap();
+            database
+                .register_relation("parent", &rows[0], &rows[2])
+                .await
+                .unwrap();
+            database
+                .register_relation("friend", &rows[2], &rows[1])
+                .await
+                .unwrap();
+            database
+                .relation_mut()
+                .rename_key("friend", "buddy")
+                .await
+                .unwrap();
+
+            let relation = database.relation();
+            let depends = relation.depends(None, &rows[1]);
+            let pends = relation
+                .pends(Some(Arc::new("parent".into())), &rows[0], None)
+                .into_iter()
+                .cloned()
+                .collect::<Vec<_>>();
+            let keys = relation.stats().keys;
+            assert_eq!(relation.rows_by_key("friend").len(), 0);
+
+            database.delete(&rows[2]).await.unwrap();
+            let stats = database.relation().stats();
+            results.push((depends, pends, keys, stats.relations, stats.free_rows));
+        }
+        assert_eq!(results[0], results[1]);
+        let (depends, pends, keys, relations, free_rows) = &results[0];
+        assert_eq!(depends.len(), 2);
+        assert_eq!(pends.len(), 2);
+        assert_eq!(keys["buddy"], 1);
+        // Deleting Tom deletes Ann, who depends on Tom, and so every relation.
+        assert_eq!(*relations, 0);
+        assert_eq!(*free_rows, 3);
+    });
+}
+
+#[cfg(test)]
+#[test]
+fn relation_storage_reopen() {
+    use semilattice_database::*;
+
+    fn send<T: Send>(_: &T) {}
+
+    let dir = "./sl-test-storage-reopen/";
+
+    if std::path::Path::new(dir).exists() {
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    futures::executor::block_on(async {
+        let mut database = Database::open(dir.into(), None, 10).unwrap();
+        send(&database);
+        let collection_id = database.collection_id_or_create("person").unwrap();
+        let mut rows = vec![];
+        for name in ["Joe", "Ann", "Tom"] {
+            let row = database
+                .collection_mut(collection_id)
+                .unwrap()
+                .insert_unchecked(
+                    Activity::Active,
+                    Term::Default,
+                    Term::Default,
+                    [(FieldName::new("name".into()), name.as_bytes().to_vec())].into(),
+                )
+                .await
+                .unwrap();
+            rows.push(CollectionRow::new(collection_id, row));
+        }
+        database
+            .register_relation("parent", &rows[0], &rows[1])
+            .await
+            .unwrap();
+        database
+            .register_relation("parent", &rows[0], &rows[2])
+            .await
+            .unwrap();
+        database.delete(&rows[1]).await.unwrap();
+        assert_eq!(database.relation().stats().free_rows, 1);
+        drop(database);
+
+        let mut database = Database::open(dir.into(), None, 10).unwrap();
+        assert_eq!(database.relation().stats().free_rows, 1);
+        #[allow(deprecated)]
+        let depend_rows = database.relation().index_depend().iter_by(&rows[0]).count();
+        assert_eq!(depend_rows, 1);
+
+        // The freed row is reused and stays in place after reopening.
+        database
+            .register_relation("parent", &rows[2], &rows[0])
+            .await
+            .unwrap();
+        assert_eq!(database.relation().stats().free_rows, 0);
+        drop(database);
+        let database = Database::open(dir.into(), None, 10).unwrap();
+        assert_eq!(database.relation().stats().free_rows, 0);
+        assert_eq!(database.relation().stats().relations, 2);
+    });
+}
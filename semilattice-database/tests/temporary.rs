@@ -0,0 +1,41 @@
+#[cfg(test)]
+#[test]
+fn temporary() {
+    use semilattice_database::*;
+
+    futures::executor::block_on(async {
+        let mut database = Database::temporary(None, 10);
+        let other = Database::temporary(None, 10);
+        assert!(database.is_temporary());
+        assert_ne!(database.dir(), other.dir());
+
+        let collection_id = database.collection_id_or_create("person").unwrap();
+        let row = database
+            .collection_mut(collection_id)
+            .unwrap()
+            .insert(
+                Activity::Active,
+                Term::Default,
+                Term::Default,
+                [(FieldName::new("name".into()), b"Joe".to_vec())].into(),
+            )
+            .await
+            .unwrap();
+        let joe = CollectionRow::new(collection_id, row);
+        database
+            .register_relation("self", &joe, &joe)
+            .await
+            .unwrap();
+        assert_eq!(database.relation().depends(None, &joe).len(), 1);
+        assert!(other.collection_id("person").is_none());
+
+        let dir = database.dir().to_path_buf();
+        assert!(dir.exists());
+        drop(database);
+        assert!(!dir.exists());
+
+        let mut relation = RelationIndex::temporary();
+        relation.insert("self", &joe, &joe).await.unwrap();
+        assert_eq!(relation.depends(None, &joe).len(), 1);
+    });
+}
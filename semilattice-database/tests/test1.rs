@@ -6,19 +6,11 @@ fn test() {
     use semilattice_database::*;
     use versatile_data::FieldName;
 
-    let dir = "./sl-test/";
-
-    if std::path::Path::new(dir).exists() {
-        std::fs::remove_dir_all(dir).unwrap();
-        std::fs::create_dir_all(dir).unwrap();
-    } else {
-        std::fs::create_dir_all(dir).unwrap();
-    }
     futures::executor::block_on(async {
-        let mut database = Database::new(dir.into(), None, 10);
+        let mut database = Database::temporary(None, 10);
 
-        let collection_person_id = database.collection_id_or_create("person");
-        let collection_history_id = database.collection_id_or_create("history");
+        let collection_person_id = database.collection_id_or_create("person").unwrap();
+        let collection_history_id = database.collection_id_or_create("history").unwrap();
         let collection_person = database.collection_mut(collection_person_id).unwrap();
 
         let id_name = FieldName::new("name".into());
@@ -35,7 +27,8 @@ fn test() {
                 ]
                 .into(),
             )
-            .await;
+            .await
+            .unwrap();
 
         let depend = CollectionRow::new(collection_person_id, row);
         let mut pends = vec![];
@@ -56,7 +49,8 @@ fn test() {
                 ]
                 .into(),
             )
-            .await;
+            .await
+            .unwrap();
         pends.push((
             Arc::new("history".to_owned()),
             CollectionRow::new(collection_history_id, history_row),
@@ -72,12 +66,13 @@ fn test() {
                 ]
                 .into(),
             )
-            .await;
+            .await
+            .unwrap();
         pends.push((
             Arc::new("history".to_owned()),
             CollectionRow::new(collection_history_id, history_row),
         ));
-        database.register_relations(&depend, pends).await;
+        database.register_relations(&depend, pends).await.unwrap();
 
         if let (Some(person), Some(history)) = (
             database.collection(collection_person_id),
@@ -87,7 +82,7 @@ fn test() {
                 .search(collection_person_id)
                 .result(&database)
                 .await;
-            for row in result.rows().into_iter() {
+            for row in result.rows().iter() {
                 println!(
                     "{},{}",
                     std::str::from_utf8(person.field_bytes(*row, &id_name)).unwrap(),
@@ -102,7 +97,7 @@ fn test() {
                     .result(&database)
                     .await
                     .rows()
-                    .into_iter()
+                    .iter()
                 {
                     println!(
                         " {} : {}",
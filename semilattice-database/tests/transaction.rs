@@ -0,0 +1,305 @@
+#[cfg(test)]
+#[test]
+fn transaction() {
+    use semilattice_database::{
+        transaction::{TransactionError, TransactionRow},
+        *,
+    };
+
+    let dir = "./sl-test-transaction/";
+
+    if std::path::Path::new(dir).exists() {
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+    std::fs::create_dir_all(dir).unwrap();
+
+    let field_name = FieldName::new("name".into());
+
+    futures::executor::block_on(async {
+        let mut database = Database::new(dir.into(), None, 10);
+        let collection_person = database.collection_id_or_create("person").unwrap();
+        let collection_pet = database.collection_id_or_create("pet").unwrap();
+        database
+            .set_schema(
+                collection_person,
+                Some(
+                    Schema::new()
+                        .field(field_name.clone(), FieldSchema::new(FieldType::Utf8))
+                        .unique(vec![field_name.clone()]),
+                ),
+            )
+            .unwrap();
+        let person = |name: &str| -> hashbrown::HashMap<FieldName, Vec<u8>> {
+            [(field_name.clone(), name.as_bytes().to_vec())].into()
+        };
+        let tom = CollectionRow::new(
+            collection_person,
+            database
+                .collection_mut(collection_person)
+                .unwrap()
+                .insert(
+                    Activity::Active,
+                    Term::Default,
+                    Term::Default,
+                    person("Tom"),
+                )
+                .await
+                .unwrap(),
+        );
+
+        let mut transaction = database.transaction();
+        let joe = transaction.insert(
+            collection_person,
+            Activity::Active,
+            Term::Default,
+            Term::Default,
+            person("Joe"),
+        );
+        let rex = transaction.insert(
+            collection_pet,
+            Activity::Active,
+            Term::Default,
+            Term::Default,
+            person("Rex"),
+        );
+        assert_eq!(rex, TransactionRow::Inserted(1));
+        transaction.register_relation("owner", joe.clone(), rex.clone());
+        transaction.register_relation("owner", tom.clone(), rex);
+        transaction.rollback();
+        assert_eq!(
+            database
+                .collection(collection_person)
+                .unwrap()
+                .data()
+                .all()
+                .len(),
+            1
+        );
+        assert_eq!(
+            database
+                .collection(collection_pet)
+                .unwrap()
+                .data()
+                .all()
+                .len(),
+            0
+        );
+
+        let mut transaction = database.transaction();
+        transaction.insert(
+            collection_person,
+            Activity::Active,
+            Term::Default,
+            Term::Default,
+            person("Joe"),
+        );
+        transaction.insert(
+            collection_person,
+            Activity::Active,
+            Term::Default,
+            Term::Default,
+            person("Tom"),
+        );
+        match transaction.commit().await {
+            Err(TransactionError::Write {
+                operation: 1,
+                error: WriteError::Conflict(conflict),
+            }) => assert_eq!(conflict.existing, tom),
+            other => panic!("{:?}", other),
+        }
+        assert_eq!(
+            database
+                .collection(collection_person)
+                .unwrap()
+                .data()
+                .all()
+                .len(),
+            1
+        );
+
+        let mut transaction = database.transaction();
+        let joe = transaction.insert(
+            collection_person,
+            Activity::Active,
+            Term::Default,
+            Term::Default,
+            person("Tom"),
+        );
+        transaction.update(
+            tom.clone(),
+            Activity::Active,
+            Term::Default,
+            Term::Default,
+            person("Thomas"),
+        );
+        let rex = transaction.insert(
+            collection_pet,
+            Activity::Active,
+            Term::Default,
+            Term::Default,
+            person("Rex"),
+        );
+        transaction.update(
+            joe.clone(),
+            Activity::Active,
+            Term::Default,
+            Term::Default,
+            person("Joe"),
+        );
+        transaction.register_relation("owner", joe, rex.clone());
+        transaction.register_relation("owner", tom.clone(), rex);
+        let inserted = transaction.commit().await.unwrap();
+        let (joe, rex) = (inserted[0].clone(), inserted[1].clone());
+        let collection = database.collection(collection_person).unwrap();
+        assert_eq!(collection.field_bytes(joe.row(), &field_name), b"Joe");
+        assert_eq!(collection.field_bytes(tom.row(), &field_name), b"Thomas");
+        assert_eq!(database.relation().depends(None, &rex).len(), 2);
+
+        let mut transaction = database.transaction();
+        transaction.register_relation("owner", tom.clone(), TransactionRow::Inserted(0));
+        assert!(matches!(
+            transaction.commit().await,
+            Err(TransactionError::UnknownRow {
+                operation: 0,
+                row: TransactionRow::Inserted(0)
+            })
+        ));
+        let mut transaction = database.transaction();
+        transaction.update(
+            TransactionRow::Inserted(3),
+            Activity::Active,
+            Term::Default,
+            Term::Default,
+            person("Ann"),
+        );
+        assert!(matches!(
+            transaction.commit().await,
+            Err(TransactionError::UnknownRow { operation: 0, .. })
+        ));
+        let mut transaction = database.transaction();
+        let missing = CollectionRow::new(collection_pet, 100.try_into().unwrap());
+        transaction.register_relation("owner", tom.clone(), missing.clone());
+        assert!(matches!(
+            transaction.commit().await,
+            Err(TransactionError::UnknownRow { row: TransactionRow::Row(row), .. }) if row == missing
+        ));
+
+        let mut transaction = database.transaction();
+        transaction.unregister_relation("owner", tom.clone(), rex.clone());
+        transaction.delete(joe.clone());
+        transaction.commit().await.unwrap();
+        assert!(database.relation().depends(None, &rex).is_empty());
+        assert_eq!(
+            database
+                .collection(collection_person)
+                .unwrap()
+                .data()
+                .all()
+                .len(),
+            1
+        );
+        assert_eq!(
+            database
+                .collection(collection_pet)
+                .unwrap()
+                .data()
+                .all()
+                .len(),
+            0
+        );
+
+        let mut transaction = database.transaction();
+        let missing = CollectionRow::new(collection_person, 99.try_into().unwrap());
+        transaction.update(
+            missing.clone(),
+            Activity::Active,
+            Term::Default,
+            Term::Default,
+            person("Ann"),
+        );
+        assert!(matches!(
+            transaction.commit().await,
+            Err(TransactionError::UnknownRow { operation: 0, row: TransactionRow::Row(row) }) if row == missing
+        ));
+        let mut transaction = database.transaction();
+        let ann = transaction.insert(
+            collection_person,
+            Activity::Active,
+            Term::Default,
+            Term::Default,
+            person("Ann"),
+        );
+        transaction.delete(ann.clone());
+        transaction.update(
+            ann,
+            Activity::Active,
+            Term::Default,
+            Term::Default,
+            person("Anne"),
+        );
+        assert!(matches!(
+            transaction.commit().await,
+            Err(TransactionError::UnknownRow {
+                operation: 2,
+                row: TransactionRow::Inserted(0)
+            })
+        ));
+        let mut transaction = database.transaction();
+        let fido = transaction.insert(
+            collection_pet,
+            Activity::Active,
+            Term::Default,
+            Term::Default,
+            person("Fido"),
+        );
+        transaction.delete(tom.clone());
+        transaction.register_relation("owner", tom.clone(), fido);
+        assert!(matches!(
+            transaction.commit().await,
+            Err(TransactionError::UnknownRow { operation: 2, row: TransactionRow::Row(row) }) if row == tom
+        ));
+
+        let mut transaction = database.transaction();
+        let fido = transaction.insert(
+            collection_pet,
+            Activity::Active,
+            Term::Default,
+            Term::Default,
+            person("Fido"),
+        );
+        transaction.register_relation("owner", tom.clone(), fido);
+        let fido = transaction.commit().await.unwrap()[0].clone();
+        let mut transaction = database.transaction();
+        transaction.insert(
+            collection_person,
+            Activity::Active,
+            Term::Default,
+            Term::Default,
+            person("Kim"),
+        );
+        transaction.delete(tom.clone());
+        transaction.update(
+            fido.clone(),
+            Activity::Active,
+            Term::Default,
+            Term::Default,
+            person("Rex"),
+        );
+        match transaction.commit().await {
+            Err(TransactionError::PartlyApplied { error, inserted }) => {
+                assert!(matches!(
+                    *error,
+                    TransactionError::UnknownRow { operation: 2, row: TransactionRow::Row(ref row) } if *row == fido
+                ));
+                assert_eq!(inserted.len(), 1);
+                let collection = database.collection(collection_person).unwrap();
+                assert_eq!(
+                    collection.field_bytes(inserted[0].row(), &field_name),
+                    b"Kim"
+                );
+                assert!(collection.uuid(tom.row()).is_none());
+            }
+            other => panic!("{:?}", other),
+        }
+    });
+}
@@ -0,0 +1,8 @@
+use semilattice_database_derive::CollectionRecord;
+
+#[derive(CollectionRecord)]
+enum Person {
+    Named(String),
+}
+
+fn main() {}
@@ -0,0 +1,6 @@
+use semilattice_database_derive::CollectionRecord;
+
+#[derive(CollectionRecord)]
+struct Person(String);
+
+fn main() {}
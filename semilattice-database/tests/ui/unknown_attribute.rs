@@ -0,0 +1,9 @@
+use semilattice_database_derive::CollectionRecord;
+
+#[derive(CollectionRecord)]
+struct Person {
+    #[record(index)]
+    name: String,
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use semilattice_database_derive::CollectionRecord;
+
+#[derive(CollectionRecord)]
+struct Person {
+    name: String,
+    tags: Vec<String>,
+}
+
+fn main() {}